@@ -1,17 +1,18 @@
 //! Build a single disassembled file.
 
-use crate::builders::build_xml_string;
+use crate::builders::{build_xml_fragment, build_xml_string};
+use crate::error::Error;
+use crate::observer::Event;
+use crate::options::Format;
 use crate::parsers::parse_unique_id_element;
+use crate::sidecar::extract_sidecars;
+use crate::sink;
 use crate::transformers::transform_format;
 use crate::types::BuildDisassembledFileOptions;
 use serde_json::{Map, Value};
 use std::path::Path;
-use tokio::fs;
-use tokio::io::AsyncWriteExt;
 
-pub async fn build_disassembled_file(
-    options: BuildDisassembledFileOptions<'_>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub fn build_disassembled_file(options: BuildDisassembledFileOptions<'_>) -> Result<(), Error> {
     let BuildDisassembledFileOptions {
         content,
         disassembled_path,
@@ -24,7 +25,12 @@ pub async fn build_disassembled_file(
         xml_declaration,
         format,
         unique_id_elements,
+        bare,
+        sidecars,
+        sink,
+        parts,
     } = options;
+    sink::check_cancelled(sink)?;
 
     let target_directory = if let Some(subdir) = subdirectory {
         Path::new(disassembled_path).join(subdir)
@@ -46,51 +52,101 @@ pub async fn build_disassembled_file(
     };
 
     let output_path = target_directory.join(&file_name);
+    // Part paths relative to the disassembly directory, checked against the source's limits.
+    let take_part = |name: &str| match parts {
+        Some(parts) => parts.take(&Path::new(subdirectory.unwrap_or("")).join(name)),
+        None => Ok(()),
+    };
+    take_part(&file_name)?;
 
-    fs::create_dir_all(&target_directory).await?;
-
-    let root_attrs_obj = root_attributes.as_object().cloned().unwrap_or_default();
-    let mut inner = root_attrs_obj.clone();
+    sink::create_dir_all(sink, &target_directory)?;
 
+    let mut body = Map::new();
     if let Some(wk) = wrap_key {
-        inner.insert(wk.to_string(), content.clone());
+        body.insert(wk.to_string(), content.clone());
     } else if let Some(obj) = content.as_object() {
         for (k, v) in obj {
-            inner.insert(k.clone(), v.clone());
+            body.insert(k.clone(), v.clone());
         }
     }
 
-    let mut wrapped_inner = Map::new();
-    wrapped_inner.insert(root_element_name.to_string(), Value::Object(inner));
-
-    if let Some(decl) = xml_declaration.filter(|d| d.is_object()) {
-        let mut root = Map::new();
-        root.insert("?xml".to_string(), decl);
-        for (k, v) in wrapped_inner {
-            root.insert(k, v);
+    if let Some(rules) = sidecars.filter(|r| !r.is_empty()) {
+        let part_stem = Path::new(&file_name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&file_name);
+        for (name, bytes) in extract_sidecars(&mut body, rules, part_stem) {
+            take_part(&name)?;
+            sink::write_file(sink, &target_directory.join(&name), bytes)?;
         }
-        wrapped_inner = root;
     }
 
-    let wrapped_xml = Value::Object(wrapped_inner);
-
-    let output_string = if let Some(s) = transform_format(format, &wrapped_xml).await {
-        s
+    let output_string = if bare {
+        // Root name, attributes and declaration live in .root.json; the part holds only the body.
+        match transform_format(format, &Value::Object(body.clone())) {
+            Some(s) => s,
+            None => build_xml_fragment(&body),
+        }
     } else {
-        build_xml_string(&wrapped_xml)
+        let mut inner = root_attributes.as_object().cloned().unwrap_or_default();
+        inner.extend(body);
+
+        let mut wrapped_inner = Map::new();
+        wrapped_inner.insert(root_element_name.to_string(), Value::Object(inner));
+
+        if let Some(decl) = xml_declaration.filter(|d| d.is_object()) {
+            let mut root = Map::new();
+            root.insert("?xml".to_string(), decl);
+            for (k, v) in wrapped_inner {
+                root.insert(k, v);
+            }
+            wrapped_inner = root;
+        }
+
+        let wrapped_xml = Value::Object(wrapped_inner);
+
+        if let Some(s) = transform_format(format, &wrapped_xml) {
+            s
+        } else {
+            build_xml_string(&wrapped_xml)
+        }
     };
 
-    let mut file = fs::File::create(&output_path).await?;
-    file.write_all(output_string.as_bytes()).await?;
+    sink::write_file(sink, &output_path, &output_string)?;
     log::debug!("Created disassembled file: {}", output_path.display());
+    sink::notify(sink, || {
+        let (tag, id) = match wrap_key {
+            Some(_) => part_tag_and_id(&file_name, format),
+            None => (root_element_name.to_string(), None),
+        };
+        Event::PartWritten {
+            path: output_path.clone(),
+            tag,
+            id,
+            bytes: output_string.len(),
+        }
+    });
 
     Ok(())
 }
 
+/// Tag and id of a part from its file name: `{id}.{tag}-meta.{format}`, or `{tag}.{format}` for
+/// the parts holding every element of a tag.
+fn part_tag_and_id(file_name: &str, format: Format) -> (String, Option<String>) {
+    let stem = file_name
+        .strip_suffix(&format!(".{}", format))
+        .unwrap_or(file_name);
+    match stem.strip_suffix("-meta").and_then(|s| s.rsplit_once('.')) {
+        Some((id, tag)) => (tag.to_string(), Some(id.to_string())),
+        None => (stem.to_string(), None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::fs;
 
     fn opts_base(disassembled_path: &str) -> BuildDisassembledFileOptions<'_> {
         BuildDisassembledFileOptions {
@@ -103,13 +159,32 @@ mod tests {
             root_element_name: "Root",
             root_attributes: Value::Object(Map::new()),
             xml_declaration: None,
-            format: "xml",
+            format: Format::Xml,
             unique_id_elements: None,
+            bare: false,
+            sidecars: None,
+            sink: None,
+            parts: None,
         }
     }
 
-    #[tokio::test]
-    async fn build_disassembled_file_file_name_output_when_wrap_key_no_output_name_grouped_array() {
+    #[test]
+    fn part_tag_and_id_follow_the_part_naming() {
+        assert_eq!(
+            part_tag_and_id("Account.Name.fieldPermissions-meta.xml", Format::Xml),
+            (
+                "fieldPermissions".to_string(),
+                Some("Account.Name".to_string())
+            )
+        );
+        assert_eq!(
+            part_tag_and_id("recordTypeVisibilities.yaml", Format::Yaml),
+            ("recordTypeVisibilities".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn build_disassembled_file_file_name_output_when_wrap_key_no_output_name_grouped_array() {
         // wrap_key Some, is_grouped_array true → file_name = "output"
         let temp = tempfile::tempdir().unwrap();
         let path = temp.path().to_str().unwrap();
@@ -118,12 +193,12 @@ mod tests {
         opts.wrap_key = Some("wrap");
         opts.is_grouped_array = true;
         opts.content = json!([{ "x": "1" }]);
-        build_disassembled_file(opts).await.unwrap();
+        build_disassembled_file(opts).unwrap();
         assert!(temp.path().join("output").exists());
     }
 
-    #[tokio::test]
-    async fn build_disassembled_file_file_name_output_when_wrap_key_content_not_object() {
+    #[test]
+    fn build_disassembled_file_file_name_output_when_wrap_key_content_not_object() {
         // wrap_key Some, content not object (e.g. Array) → file_name = "output"
         let temp = tempfile::tempdir().unwrap();
         let path = temp.path().to_str().unwrap();
@@ -132,24 +207,24 @@ mod tests {
         opts.wrap_key = Some("wrap");
         opts.is_grouped_array = false;
         opts.content = json!([{ "id": "a" }]);
-        build_disassembled_file(opts).await.unwrap();
+        build_disassembled_file(opts).unwrap();
         assert!(temp.path().join("output").exists());
     }
 
-    #[tokio::test]
-    async fn build_disassembled_file_file_name_output_when_no_wrap_key_no_output_name() {
+    #[test]
+    fn build_disassembled_file_file_name_output_when_no_wrap_key_no_output_name() {
         // No output_file_name, no wrap_key → file_name = "output"
         let temp = tempfile::tempdir().unwrap();
         let path = temp.path().to_str().unwrap();
         let mut opts = opts_base(path);
         opts.output_file_name = None;
         opts.wrap_key = None;
-        build_disassembled_file(opts).await.unwrap();
+        build_disassembled_file(opts).unwrap();
         assert!(temp.path().join("output").exists());
     }
 
-    #[tokio::test]
-    async fn build_disassembled_file_content_not_object_no_spread() {
+    #[test]
+    fn build_disassembled_file_content_not_object_no_spread() {
         // No wrap_key, content not object → inner not updated from content (only root_attributes)
         let temp = tempfile::tempdir().unwrap();
         let path = temp.path().to_str().unwrap();
@@ -157,12 +232,41 @@ mod tests {
         opts.output_file_name = Some("single.xml");
         opts.wrap_key = None;
         opts.content = json!(42);
-        build_disassembled_file(opts).await.unwrap();
-        let out = fs::read_to_string(temp.path().join("single.xml"))
-            .await
-            .unwrap();
+        build_disassembled_file(opts).unwrap();
+        let out = fs::read_to_string(temp.path().join("single.xml")).unwrap();
         assert!(out.contains("<Root>"));
         // content 42 is not spread (only objects are); root is empty
         assert!(out.contains("</Root>"));
     }
+
+    #[test]
+    fn build_disassembled_file_bare_omits_root_and_declaration() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().to_str().unwrap();
+        let mut opts = opts_base(path);
+        opts.root_attributes = json!({ "@xmlns": "urn:x" });
+        opts.xml_declaration = Some(json!({ "@version": "1.0" }));
+        opts.wrap_key = Some("item");
+        opts.content = json!({ "name": "A" });
+        opts.bare = true;
+        build_disassembled_file(opts).unwrap();
+        let out = fs::read_to_string(temp.path().join("out.xml")).unwrap();
+        assert_eq!(out, "<item>\n    <name>A</name>\n</item>");
+    }
+
+    #[test]
+    fn build_disassembled_file_dry_run_sink_records_without_writing() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().to_str().unwrap();
+        let sink = sink::OutputSink::new(true);
+        let mut opts = opts_base(path);
+        opts.subdirectory = Some("sub");
+        opts.sink = Some(&sink);
+        build_disassembled_file(opts).unwrap();
+        assert!(!temp.path().join("sub").exists());
+        let changes = sink.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, temp.path().join("sub").join("out.xml"));
+        assert_eq!(changes[0].kind, sink::ChangeKind::Create);
+    }
 }
@@ -1,16 +1,25 @@
 //! Build disassembled files from source XML file.
 
 use crate::builders::{build_disassembled_file, extract_root_attributes};
-use crate::parsers::{extract_xml_declaration_from_raw, parse_element_unified};
+use crate::error::Error;
+use crate::limits::{Limits, PartBudget};
+use crate::options::{DecomposeMode, Format, Strategy};
+use crate::parsers::{
+    extract_xml_declaration_from_raw, parse_element_unified, parse_unique_id_element,
+    parse_xml_from_str_with_limits,
+};
+use crate::sink::{self, OutputSink};
+use crate::transform::apply_transforms;
 use crate::types::{
-    BuildDisassembledFilesOptions, DecomposeRule, XmlElementArrayMap, XmlElementParams,
+    BuildDisassembledFilesOptions, DecomposeRule, LeafSplitRule, NestedItemMarker, RootWrapper,
+    SidecarRule, XmlElementArrayMap, XmlElementParams,
 };
-use crate::utils::normalize_path_unix;
+use crate::utils::{collect_task_errors, normalize_path_unix, TaskQueue};
 use serde_json::{Map, Value};
 use std::collections::HashMap;
-use tokio::fs;
+use std::path::{Path, PathBuf};
 
-const BATCH_SIZE: usize = 20;
+type BuildResult<T = ()> = Result<T, Error>;
 
 fn get_root_info(parsed_xml: &Value) -> Option<(String, Value)> {
     let obj = parsed_xml.as_object()?;
@@ -29,8 +38,46 @@ fn order_xml_element_keys(content: &Map<String, Value>, key_order: &[String]) ->
     Value::Object(ordered)
 }
 
+/// Split the occurrences of one tag into tasks that can run concurrently. Under unique-id, nested
+/// elements that resolve to the same part file stay in one task so the last one in document order
+/// wins, as when writing sequentially. Each task keeps the elements' document positions.
+fn partition_element_tasks(
+    elements: Vec<Value>,
+    strategy: Strategy,
+    unique_id_elements: Option<&str>,
+    jobs: usize,
+) -> Vec<Vec<(usize, Value)>> {
+    let indexed = elements.into_iter().enumerate();
+    if jobs <= 1 {
+        return vec![indexed.collect()];
+    }
+    let mut tasks: Vec<Vec<(usize, Value)>> = Vec::new();
+    let mut by_target: HashMap<String, usize> = HashMap::new();
+    for (idx, element) in indexed {
+        let target = match (strategy, &element) {
+            (Strategy::UniqueId, Value::Object(_)) => {
+                Some(parse_unique_id_element(&element, unique_id_elements))
+            }
+            // Arrays are all written to the same "output" file.
+            (Strategy::UniqueId, Value::Array(_)) => Some(String::new()),
+            _ => None,
+        };
+        match target {
+            Some(target) => match by_target.get(&target) {
+                Some(&task) => tasks[task].push((idx, element)),
+                None => {
+                    by_target.insert(target, tasks.len());
+                    tasks.push(vec![(idx, element)]);
+                }
+            },
+            None => tasks.push(vec![(idx, element)]),
+        }
+    }
+    tasks
+}
+
 #[allow(clippy::too_many_arguments)]
-async fn disassemble_element_keys(
+fn disassemble_element_keys(
     root_element: &Value,
     key_order: &[String],
     disassembled_path: &str,
@@ -38,9 +85,14 @@ async fn disassemble_element_keys(
     root_attributes: &Value,
     xml_declaration: Option<&Value>,
     unique_id_elements: Option<&str>,
-    strategy: &str,
-    format: &str,
-) -> (Map<String, Value>, XmlElementArrayMap, usize, bool) {
+    strategy: Strategy,
+    format: Format,
+    bare: bool,
+    sidecars: Option<&[SidecarRule]>,
+    jobs: usize,
+    sink: Option<&OutputSink>,
+    parts: Option<&PartBudget>,
+) -> BuildResult<(Map<String, Value>, XmlElementArrayMap, usize, bool)> {
     let mut leaf_content = Map::new();
     let mut nested_groups = XmlElementArrayMap::new();
     let mut leaf_count = 0usize;
@@ -48,6 +100,7 @@ async fn disassemble_element_keys(
 
     let empty_map = Map::new();
     let root_obj = root_element.as_object().unwrap_or(&empty_map);
+    let queue = TaskQueue::new(jobs);
 
     // Iterate root_obj in key_order's ordering: we consume only keys that are present,
     // which matches the caller's invariant and keeps the loop body branch-free.
@@ -61,57 +114,82 @@ async fn disassemble_element_keys(
             None => vec![val.clone()],
         };
 
-        for chunk in elements.chunks(BATCH_SIZE) {
-            for element in chunk {
-                let result = parse_element_unified(XmlElementParams {
-                    element: element.clone(),
-                    disassembled_path,
-                    unique_id_elements,
-                    root_element_name,
-                    root_attributes: root_attributes.clone(),
-                    key,
-                    leaf_content: Value::Object(Map::new()),
-                    leaf_count,
-                    has_nested_elements,
-                    format,
-                    xml_declaration: xml_declaration.cloned(),
-                    strategy,
-                })
-                .await;
-
-                if let Some(arr) = result.leaf_content.as_object().and_then(|o| o.get(key)) {
-                    match leaf_content.get_mut(key).and_then(|v| v.as_array_mut()) {
-                        Some(existing_arr) => {
-                            if let Some(new_arr) = arr.as_array() {
-                                existing_arr.extend(new_arr.iter().cloned());
-                            }
-                        }
-                        None => {
-                            leaf_content.insert(key.clone(), arr.clone());
-                        }
-                    }
+        let tasks = partition_element_tasks(elements, strategy, unique_id_elements, jobs);
+        let task_results = queue.run_all(tasks.into_iter().map(|task| {
+            move || {
+                let mut results = Vec::with_capacity(task.len());
+                for (idx, element) in task {
+                    let result = parse_element_unified(XmlElementParams {
+                        element,
+                        disassembled_path,
+                        unique_id_elements,
+                        root_element_name,
+                        root_attributes: root_attributes.clone(),
+                        key,
+                        leaf_content: Value::Object(Map::new()),
+                        leaf_count: 0,
+                        has_nested_elements: false,
+                        format,
+                        xml_declaration: xml_declaration.cloned(),
+                        strategy,
+                        bare,
+                        sidecars,
+                        sink,
+                        parts,
+                    })?;
+                    results.push((idx, result));
                 }
-
-                if strategy == "grouped-by-tag" {
-                    if let Some(groups) = result.nested_groups {
-                        for (tag, arr) in groups {
-                            nested_groups.entry(tag).or_default().extend(arr);
+                BuildResult::Ok(results)
+            }
+        }));
+        let mut results = Vec::new();
+        for task_result in task_results {
+            results.extend(task_result?);
+        }
+        results.sort_by_key(|(idx, _)| *idx);
+
+        for (_, result) in results {
+            if let Some(arr) = result.leaf_content.as_object().and_then(|o| o.get(key)) {
+                match leaf_content.get_mut(key).and_then(|v| v.as_array_mut()) {
+                    Some(existing_arr) => {
+                        if let Some(new_arr) = arr.as_array() {
+                            existing_arr.extend(new_arr.iter().cloned());
                         }
                     }
+                    None => {
+                        leaf_content.insert(key.clone(), arr.clone());
+                    }
                 }
+            }
 
-                leaf_count = result.leaf_count;
-                has_nested_elements = result.has_nested_elements;
+            if let (Strategy::GroupedByTag, Some(groups)) = (strategy, result.nested_groups) {
+                for (tag, arr) in groups {
+                    nested_groups.entry(tag).or_default().extend(arr);
+                }
             }
+
+            leaf_count += result.leaf_count;
+            has_nested_elements |= result.has_nested_elements;
         }
     }
 
-    (leaf_content, nested_groups, leaf_count, has_nested_elements)
+    Ok((leaf_content, nested_groups, leaf_count, has_nested_elements))
 }
 
 /// Extract string from an element's field - handles direct strings and objects with #text (XML leaf elements).
+/// A field starting with '@' targets an attribute on the element itself (e.g. "@id", "@xml:lang");
+/// "child/@attr" targets an attribute on a direct child element.
 fn get_field_value(element: &Value, field: &str) -> Option<String> {
-    let v = element.as_object()?.get(field)?;
+    let obj = element.as_object()?;
+    if let Some((child, attr)) = field.split_once("/@") {
+        return obj
+            .get(child)?
+            .as_object()?
+            .get(&format!("@{}", attr))?
+            .as_str()
+            .map(|s| s.to_string());
+    }
+    let v = obj.get(field)?;
     if let Some(s) = v.as_str() {
         return Some(s.to_string());
     }
@@ -139,116 +217,315 @@ fn sanitize_filename(s: &str) -> String {
         .collect()
 }
 
-async fn write_nested_groups(
+fn write_nested_groups(
     nested_groups: &XmlElementArrayMap,
-    strategy: &str,
+    strategy: Strategy,
     options: &WriteNestedOptions<'_>,
-) {
-    if strategy != "grouped-by-tag" {
-        return;
+) -> BuildResult {
+    if strategy == Strategy::UniqueId {
+        return Ok(());
     }
     let decompose_by_tag: HashMap<&str, &DecomposeRule> = options
         .decompose_rules
         .map(|rules| rules.iter().map(|r| (r.tag.as_str(), r)).collect())
         .unwrap_or_default();
 
-    for (tag, arr) in nested_groups {
-        let rule = decompose_by_tag.get(tag.as_str());
-        let path_segment = rule
-            .map(|r| {
-                if r.path_segment.is_empty() {
-                    &r.tag
-                } else {
-                    &r.path_segment
-                }
-            })
-            .unwrap_or(tag);
-
-        if let Some(r) = rule {
-            if r.mode == "split" {
-                for (idx, item) in arr.iter().enumerate() {
-                    let name = get_field_value(item, &r.field)
-                        .as_deref()
-                        .map(sanitize_filename)
-                        .filter(|s: &String| !s.is_empty())
-                        .unwrap_or_else(|| idx.to_string());
-                    let file_name = format!("{}.{}-meta.{}", name, tag, options.format);
-                    let _ = build_disassembled_file(crate::types::BuildDisassembledFileOptions {
-                        content: item.clone(),
-                        disassembled_path: options.disassembled_path,
-                        output_file_name: Some(&file_name),
-                        subdirectory: Some(path_segment),
-                        wrap_key: Some(tag),
-                        is_grouped_array: false,
-                        root_element_name: options.root_element_name,
-                        root_attributes: options.root_attributes.clone(),
-                        format: options.format,
-                        xml_declaration: options.xml_declaration.clone(),
-                        unique_id_elements: None,
-                    })
-                    .await;
-                }
-            } else if r.mode == "group" {
-                let mut by_key: HashMap<String, Vec<Value>> = HashMap::new();
-                for item in arr {
-                    let key = get_field_value(item, &r.field)
-                        .as_deref()
-                        .map(group_key_from_field_value)
-                        .map(sanitize_filename)
-                        .filter(|s: &String| !s.is_empty())
-                        .unwrap_or_else(|| "unknown".to_string());
-                    by_key.entry(key).or_default().push(item.clone());
-                }
-                // Sort keys for deterministic cross-platform output order
-                let mut sorted_keys: Vec<_> = by_key.keys().cloned().collect();
-                sorted_keys.sort();
-                for key in sorted_keys {
-                    let group = by_key.remove(&key).unwrap();
-                    let file_name = format!("{}.{}-meta.{}", key, tag, options.format);
-                    let _ = build_disassembled_file(crate::types::BuildDisassembledFileOptions {
-                        content: Value::Array(group),
-                        disassembled_path: options.disassembled_path,
-                        output_file_name: Some(&file_name),
-                        subdirectory: Some(path_segment),
-                        wrap_key: Some(tag),
-                        is_grouped_array: true,
-                        root_element_name: options.root_element_name,
-                        root_attributes: options.root_attributes.clone(),
-                        format: options.format,
-                        xml_declaration: options.xml_declaration.clone(),
-                        unique_id_elements: None,
-                    })
-                    .await;
-                }
-            } else {
-                fallback_write_one_file(tag, arr, path_segment, options).await;
+    // Each tag writes its own files and subdirectories, so tags are written concurrently.
+    let queue = TaskQueue::new(options.jobs);
+    let results = queue.run_all(nested_groups.iter().map(|(tag, arr)| {
+        let rule = decompose_by_tag.get(tag.as_str()).copied();
+        move || {
+            let result = match rule {
+                Some(rule) => write_decomposed(tag, arr, rule, "", &[], options),
+                None => fallback_write_one_file(tag, arr, "", &[], options),
+            };
+            (tag.clone(), result)
+        }
+    }));
+    collect_task_errors(results)
+}
+
+fn rule_path_segment(rule: &DecomposeRule) -> &str {
+    if rule.path_segment.is_empty() {
+        &rule.tag
+    } else {
+        &rule.path_segment
+    }
+}
+
+/// Join a relative subdirectory with a segment ("" means the disassembled root).
+fn join_subdir(parent: &str, segment: &str) -> String {
+    if parent.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}/{}", parent, segment)
+    }
+}
+
+/// Wrap `content` for tag `tag` nested under `ancestors` (outermost first). Returns the wrap key
+/// for `build_disassembled_file` and the content to place under it, so a part written for an item
+/// nested in a split item still reads root > ancestors... > tag > content.
+fn wrap_for_ancestors(ancestors: &[String], tag: &str, content: Value) -> (String, Value) {
+    let mut chain: Vec<&str> = ancestors.iter().map(String::as_str).collect();
+    chain.push(tag);
+    let mut value = content;
+    while chain.len() > 1 {
+        let inner = chain.pop().unwrap_or_default();
+        let mut wrapped = Map::new();
+        wrapped.insert(inner.to_string(), value);
+        value = Value::Object(wrapped);
+    }
+    (chain[0].to_string(), value)
+}
+
+/// Write `arr` (all occurrences of `tag`) according to `rule` under `subdir` (relative to the
+/// disassembled path). `ancestors` holds the tags of enclosing split items for nested rules.
+fn write_decomposed<'a>(
+    tag: &'a str,
+    arr: &'a [Value],
+    rule: &'a DecomposeRule,
+    subdir: &'a str,
+    ancestors: &'a [String],
+    options: &'a WriteNestedOptions<'a>,
+) -> BuildResult {
+    let path_segment = join_subdir(subdir, rule_path_segment(rule));
+    if rule.mode == DecomposeMode::Split {
+        for (idx, item) in arr.iter().enumerate() {
+            let name = get_field_value(item, &rule.field)
+                .as_deref()
+                .map(sanitize_filename)
+                .filter(|s: &String| !s.is_empty())
+                .unwrap_or_else(|| idx.to_string());
+            let file_name = format!("{}.{}-meta.{}", name, tag, options.format);
+            if split_item_with_children(
+                tag,
+                item,
+                rule,
+                &path_segment,
+                &name,
+                &file_name,
+                ancestors,
+                options,
+            )? {
+                continue;
             }
-        } else {
-            fallback_write_one_file(tag, arr, path_segment, options).await;
+            let (wrap_key, content) = wrap_for_ancestors(ancestors, tag, item.clone());
+            build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+                content,
+                disassembled_path: options.disassembled_path,
+                output_file_name: Some(&file_name),
+                subdirectory: Some(&path_segment),
+                wrap_key: Some(&wrap_key),
+                is_grouped_array: false,
+                root_element_name: options.root_element_name,
+                root_attributes: options.root_attributes.clone(),
+                format: options.format,
+                xml_declaration: options.xml_declaration.clone(),
+                unique_id_elements: None,
+                bare: options.bare,
+                sidecars: options.sidecars,
+                sink: options.sink,
+                parts: options.parts,
+            })?;
+        }
+    } else {
+        if !rule.children.is_empty() {
+            log::warn!(
+                "Child rules on \"{}\" only apply in split mode and will be ignored.",
+                tag
+            );
+        }
+        let mut by_key: HashMap<String, Vec<Value>> = HashMap::new();
+        for item in arr {
+            let key = get_field_value(item, &rule.field)
+                .as_deref()
+                .map(group_key_from_field_value)
+                .map(sanitize_filename)
+                .filter(|s: &String| !s.is_empty())
+                .unwrap_or_else(|| "unknown".to_string());
+            by_key.entry(key).or_default().push(item.clone());
+        }
+        // Sort keys for deterministic cross-platform output order
+        let mut sorted_keys: Vec<_> = by_key.keys().cloned().collect();
+        sorted_keys.sort();
+        for key in sorted_keys {
+            let group = by_key.remove(&key).unwrap();
+            let file_name = format!("{}.{}-meta.{}", key, tag, options.format);
+            let (wrap_key, content) = wrap_for_ancestors(ancestors, tag, Value::Array(group));
+            build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+                content,
+                disassembled_path: options.disassembled_path,
+                output_file_name: Some(&file_name),
+                subdirectory: Some(&path_segment),
+                wrap_key: Some(&wrap_key),
+                is_grouped_array: true,
+                root_element_name: options.root_element_name,
+                root_attributes: options.root_attributes.clone(),
+                format: options.format,
+                xml_declaration: options.xml_declaration.clone(),
+                unique_id_elements: None,
+                bare: options.bare,
+                sidecars: options.sidecars,
+                sink: options.sink,
+                parts: options.parts,
+            })?;
         }
     }
+    Ok(())
+}
+
+/// When a split rule has child rules and the item contains any of their tags, write the item into
+/// its own directory: the remaining content as `{name}.{tag}-meta.{format}`, each child tag per its
+/// rule under `{name}/{child path_segment}/`, plus a `.nested_item.json` marker for reassembly.
+/// Returns false (nothing written) when no child rule matches so the caller writes a plain part.
+#[allow(clippy::too_many_arguments)]
+fn split_item_with_children(
+    tag: &str,
+    item: &Value,
+    rule: &DecomposeRule,
+    path_segment: &str,
+    name: &str,
+    file_name: &str,
+    ancestors: &[String],
+    options: &WriteNestedOptions<'_>,
+) -> BuildResult<bool> {
+    let Some(item_obj) = item.as_object() else {
+        return Ok(false);
+    };
+    let matching: Vec<&DecomposeRule> = rule
+        .children
+        .iter()
+        .filter(|c| item_obj.contains_key(&c.tag))
+        .collect();
+    if matching.is_empty() {
+        return Ok(false);
+    }
+
+    let item_dir = join_subdir(path_segment, name);
+    let mut item_ancestors = ancestors.to_vec();
+    item_ancestors.push(tag.to_string());
+
+    let remaining: Map<String, Value> = item_obj
+        .iter()
+        .filter(|(k, _)| !matching.iter().any(|c| &c.tag == *k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    let (wrap_key, content) = wrap_for_ancestors(ancestors, tag, Value::Object(remaining));
+    build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+        content,
+        disassembled_path: options.disassembled_path,
+        output_file_name: Some(file_name),
+        subdirectory: Some(&item_dir),
+        wrap_key: Some(&wrap_key),
+        is_grouped_array: false,
+        root_element_name: options.root_element_name,
+        root_attributes: options.root_attributes.clone(),
+        format: options.format,
+        xml_declaration: options.xml_declaration.clone(),
+        unique_id_elements: None,
+        bare: options.bare,
+        sidecars: options.sidecars,
+        sink: options.sink,
+        parts: options.parts,
+    })?;
+
+    for child in matching {
+        let child_items: Vec<Value> = match &item_obj[&child.tag] {
+            Value::Array(a) => a.clone(),
+            other => vec![other.clone()],
+        };
+        write_decomposed(
+            &child.tag,
+            &child_items,
+            child,
+            &item_dir,
+            &item_ancestors,
+            options,
+        )?;
+    }
+
+    let marker = NestedItemMarker {
+        tags: item_ancestors,
+        key_order: item_obj
+            .keys()
+            .filter(|k| !k.starts_with('@'))
+            .cloned()
+            .collect(),
+    };
+    // serde_json::to_string never fails for this struct.
+    let marker_path = std::path::Path::new(options.disassembled_path)
+        .join(&item_dir)
+        .join(".nested_item.json");
+    let json = serde_json::to_string(&marker).unwrap_or_default();
+    sink::write_file(options.sink, &marker_path, json)?;
+    Ok(true)
 }
 
-async fn fallback_write_one_file(
+fn fallback_write_one_file(
     tag: &str,
     arr: &[Value],
-    _path_segment: &str,
+    subdir: &str,
+    ancestors: &[String],
     options: &WriteNestedOptions<'_>,
-) {
-    let _ = build_disassembled_file(crate::types::BuildDisassembledFileOptions {
-        content: Value::Array(arr.to_vec()),
+) -> BuildResult {
+    let (wrap_key, content) = wrap_for_ancestors(ancestors, tag, Value::Array(arr.to_vec()));
+    build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+        content,
         disassembled_path: options.disassembled_path,
         output_file_name: Some(&format!("{}.{}", tag, options.format)),
-        subdirectory: None,
-        wrap_key: Some(tag),
+        subdirectory: (!subdir.is_empty()).then_some(subdir),
+        wrap_key: Some(&wrap_key),
         is_grouped_array: true,
         root_element_name: options.root_element_name,
         root_attributes: options.root_attributes.clone(),
         format: options.format,
         xml_declaration: options.xml_declaration.clone(),
         unique_id_elements: None,
+        bare: options.bare,
+        sidecars: options.sidecars,
+        sink: options.sink,
+        parts: options.parts,
     })
-    .await;
+}
+
+/// Leaf tags moved out of the base file, in document order.
+type SplitLeaves = Vec<(String, Vec<Value>)>;
+
+/// Move leaf tags selected by the rule out of `leaf_content`, in document order.
+/// Tags that also have nested elements are kept in the base file: under grouped-by-tag their
+/// nested elements already use `{tag}.{format}`. So is a tag named `base_name`, whose
+/// `{tag}.{format}` file would overwrite the base file.
+fn partition_split_leaves(
+    mut leaf_content: Map<String, Value>,
+    key_order: &[String],
+    nested_groups: &XmlElementArrayMap,
+    leaf_split: Option<&LeafSplitRule>,
+    base_name: &str,
+) -> (Map<String, Value>, SplitLeaves) {
+    let Some(rule) = leaf_split.filter(|r| !r.is_empty()) else {
+        return (leaf_content, Vec::new());
+    };
+    let mut split = Vec::new();
+    for tag in key_order {
+        let count = match leaf_content.get(tag).and_then(|v| v.as_array()) {
+            Some(arr) => arr.len(),
+            None => continue,
+        };
+        if !rule.matches(tag, count) || nested_groups.contains_key(tag) {
+            continue;
+        }
+        if tag.eq_ignore_ascii_case(base_name) {
+            log::warn!(
+                "Leaf tag {} stays in the base file: its own file would overwrite it",
+                tag
+            );
+            continue;
+        }
+        if let Some(Value::Array(arr)) = leaf_content.remove(tag) {
+            split.push((tag.clone(), arr));
+        }
+    }
+    (leaf_content, split)
 }
 
 struct WriteNestedOptions<'a> {
@@ -256,13 +533,20 @@ struct WriteNestedOptions<'a> {
     root_element_name: &'a str,
     root_attributes: Value,
     xml_declaration: Option<Value>,
-    format: &'a str,
+    format: Format,
     decompose_rules: Option<&'a [DecomposeRule]>,
+    bare: bool,
+    sidecars: Option<&'a [SidecarRule]>,
+    jobs: usize,
+    sink: Option<&'a OutputSink>,
+    parts: Option<&'a PartBudget>,
 }
 
-pub async fn build_disassembled_files_unified(
+/// Disassemble one source file. A file that cannot be disassembled (unreadable, not XML, only
+/// leaves, ...) returns an [`Error`], which the caller treats as a skip.
+pub fn build_disassembled_files_unified(
     options: BuildDisassembledFilesOptions<'_>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), Error> {
     let BuildDisassembledFilesOptions {
         file_path,
         disassembled_path,
@@ -272,24 +556,41 @@ pub async fn build_disassembled_files_unified(
         unique_id_elements,
         strategy,
         decompose_rules,
+        leaf_split,
+        bare,
+        sidecars,
+        jobs,
+        sink,
+        limits,
+        transforms,
     } = options;
 
     let file_path = normalize_path_unix(file_path);
 
-    let xml_content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
-        Err(_) => return Ok(()),
-    };
-
-    let parsed_xml = match crate::parsers::parse_xml_from_str(&xml_content, &file_path) {
-        Some(p) => p,
-        None => return Ok(()),
-    };
-
-    let (root_element_name, root_element) = match get_root_info(&parsed_xml) {
-        Some(info) => info,
-        None => return Ok(()),
-    };
+    let default_limits = Limits::default();
+    let limits = limits.unwrap_or(&default_limits);
+    // Checked before reading, so an oversized file is never loaded.
+    // A second-level source is a part the run has written so far.
+    let source = Path::new(&file_path);
+    if let Ok(metadata) = sink::metadata(sink, source) {
+        limits.check_document_size(&file_path, metadata.len())?;
+    }
+    let xml_content = sink::read_to_string(sink, source).map_err(|err| Error::Unreadable {
+        path: PathBuf::from(&file_path),
+        reason: err.to_string(),
+    })?;
+
+    let mut parsed_xml = parse_xml_from_str_with_limits(&xml_content, &file_path, limits)?.ok_or(
+        Error::InvalidXml {
+            path: PathBuf::from(&file_path),
+        },
+    )?;
+    apply_transforms(&mut parsed_xml, transforms);
+    let parts = PartBudget::new(&file_path, limits);
+
+    let (root_element_name, root_element) = get_root_info(&parsed_xml).ok_or(Error::NoRoot {
+        path: PathBuf::from(&file_path),
+    })?;
     // The custom parser ignores <?xml ?>; always recover it from raw XML.
     let xml_declaration = extract_xml_declaration_from_raw(&xml_content);
 
@@ -298,6 +599,11 @@ pub async fn build_disassembled_files_unified(
         .as_object()
         .map(|o| o.keys().filter(|k| !k.starts_with('@')).cloned().collect())
         .unwrap_or_default();
+    if key_order.is_empty() {
+        return Err(Error::EmptyRoot {
+            path: PathBuf::from(&file_path),
+        });
+    }
 
     let (leaf_content, nested_groups, leaf_count, has_nested_elements) = disassemble_element_keys(
         &root_element,
@@ -309,15 +615,25 @@ pub async fn build_disassembled_files_unified(
         unique_id_elements,
         strategy,
         format,
-    )
-    .await;
+        bare,
+        sidecars,
+        jobs,
+        sink,
+        Some(&parts),
+    )?;
+
+    let (leaf_content, split_leaves) = partition_split_leaves(
+        leaf_content,
+        &key_order,
+        &nested_groups,
+        leaf_split,
+        base_name,
+    );
 
-    if !has_nested_elements && leaf_count > 0 {
-        log::error!(
-            "The XML file {} only has leaf elements. This file will not be disassembled.",
-            &file_path
-        );
-        return Ok(());
+    if !has_nested_elements && leaf_count > 0 && split_leaves.is_empty() {
+        return Err(Error::LeafOnly {
+            path: PathBuf::from(&file_path),
+        });
     }
 
     let write_opts = WriteNestedOptions {
@@ -327,23 +643,41 @@ pub async fn build_disassembled_files_unified(
         xml_declaration: xml_declaration.clone(),
         format,
         decompose_rules,
+        bare,
+        sidecars,
+        jobs,
+        sink,
+        parts: Some(&parts),
     };
-    write_nested_groups(&nested_groups, strategy, &write_opts).await;
+    write_nested_groups(&nested_groups, strategy, &write_opts)?;
 
-    // Persist root key order so reassembly can match original document order.
-    // serde_json::to_string never fails for Vec<String>; writes are best-effort.
-    let key_order_path = std::path::Path::new(disassembled_path).join(".key_order.json");
-    let json = serde_json::to_string(&key_order).unwrap_or_else(|_| "[]".to_string());
-    let _ = fs::write(key_order_path, json).await;
+    for (tag, arr) in split_leaves {
+        build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+            content: Value::Array(arr),
+            disassembled_path,
+            output_file_name: Some(&format!("{}.{}", tag, format)),
+            subdirectory: None,
+            wrap_key: Some(&tag),
+            is_grouped_array: true,
+            root_element_name: &root_element_name,
+            root_attributes: root_attributes.clone(),
+            format,
+            xml_declaration: xml_declaration.clone(),
+            unique_id_elements: None,
+            bare,
+            sidecars,
+            sink,
+            parts: Some(&parts),
+        })?;
+    }
 
-    if leaf_count > 0 {
-        let final_leaf_content = if strategy == "grouped-by-tag" {
-            order_xml_element_keys(&leaf_content, &key_order)
-        } else {
-            Value::Object(leaf_content.clone())
+    if !leaf_content.is_empty() {
+        let final_leaf_content = match strategy {
+            Strategy::GroupedByTag => order_xml_element_keys(&leaf_content, &key_order),
+            Strategy::UniqueId => Value::Object(leaf_content.clone()),
         };
 
-        let _ = build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+        build_disassembled_file(crate::types::BuildDisassembledFileOptions {
             content: final_leaf_content,
             disassembled_path,
             output_file_name: Some(&format!("{}.{}", base_name, format)),
@@ -355,13 +689,35 @@ pub async fn build_disassembled_files_unified(
             format,
             xml_declaration: xml_declaration.clone(),
             unique_id_elements: None,
-        })
-        .await;
+            bare,
+            sidecars,
+            sink,
+            parts: Some(&parts),
+        })?;
+    }
+
+    // Persist root key order so reassembly can match original document order.
+    // Written last: the output directory only exists once a part file has been written.
+    // serde_json::to_string never fails for Vec<String>.
+    let key_order_path = std::path::Path::new(disassembled_path).join(".key_order.json");
+    let json = serde_json::to_string(&key_order).unwrap_or_else(|_| "[]".to_string());
+    sink::write_file(sink, &key_order_path, json)?;
+
+    if bare {
+        let wrapper = RootWrapper {
+            name: root_element_name.clone(),
+            attributes: root_attributes.as_object().cloned().unwrap_or_default(),
+            declaration: xml_declaration.clone(),
+        };
+        let root_path = std::path::Path::new(disassembled_path).join(".root.json");
+        let json = serde_json::to_string(&wrapper).unwrap_or_default();
+        sink::write_file(sink, &root_path, json)?;
     }
 
     if post_purge {
-        // Best-effort purge; a failure here is benign (file may have been removed concurrently).
-        let _ = fs::remove_file(&file_path).await;
+        // Every part was written (or staged) above; within a transaction the source is only
+        // removed when it commits.
+        sink::remove_file(sink, Path::new(&file_path))?;
     }
 
     Ok(())
@@ -393,6 +749,20 @@ mod tests {
         assert!(get_field_value(&el, "field").is_none());
     }
 
+    #[test]
+    fn get_field_value_reads_attributes() {
+        let el = json!({ "@id": "a1", "@xml:lang": "fr", "target": { "@state": "final" } });
+        assert_eq!(get_field_value(&el, "@id"), Some("a1".to_string()));
+        assert_eq!(get_field_value(&el, "@xml:lang"), Some("fr".to_string()));
+        assert_eq!(
+            get_field_value(&el, "target/@state"),
+            Some("final".to_string())
+        );
+        assert!(get_field_value(&el, "@missing").is_none());
+        assert!(get_field_value(&el, "target/@missing").is_none());
+        assert!(get_field_value(&el, "missing/@state").is_none());
+    }
+
     #[test]
     fn group_key_from_field_value_takes_prefix_before_dot() {
         assert_eq!(group_key_from_field_value("Account.Name"), "Account");
@@ -417,6 +787,59 @@ mod tests {
         assert_eq!(keys, vec![&"a".to_string(), &"b".to_string()]);
     }
 
+    #[test]
+    fn partition_split_leaves_moves_listed_and_over_threshold_tags() {
+        let mut leaves = Map::new();
+        leaves.insert("label".to_string(), json!([{ "#text": "L" }]));
+        leaves.insert(
+            "tabs".to_string(),
+            json!([{ "#text": "a" }, { "#text": "b" }]),
+        );
+        leaves.insert("brand".to_string(), json!([{ "#text": "x" }]));
+        let order: Vec<String> = ["tabs", "label", "brand"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rule = LeafSplitRule {
+            tags: vec!["brand".to_string()],
+            threshold: Some(1),
+        };
+        let (rest, split) = partition_split_leaves(
+            leaves.clone(),
+            &order,
+            &XmlElementArrayMap::new(),
+            Some(&rule),
+            "App",
+        );
+        let split_tags: Vec<&str> = split.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(split_tags, vec!["tabs", "brand"]);
+        assert_eq!(split[0].1.len(), 2);
+        assert!(rest.contains_key("label") && rest.len() == 1);
+
+        // Tags that also have nested elements stay in the base file.
+        let mut nested = XmlElementArrayMap::new();
+        nested.insert("tabs".to_string(), vec![json!({ "a": "b" })]);
+        let (rest, split) =
+            partition_split_leaves(leaves.clone(), &order, &nested, Some(&rule), "App");
+        assert_eq!(split.len(), 1);
+        assert!(rest.contains_key("tabs"));
+
+        // A tag named like the base file would overwrite it.
+        let (rest, split) = partition_split_leaves(
+            leaves.clone(),
+            &order,
+            &XmlElementArrayMap::new(),
+            Some(&rule),
+            "brand",
+        );
+        assert_eq!(split.len(), 1);
+        assert!(rest.contains_key("brand"));
+
+        let (rest, split) = partition_split_leaves(leaves, &order, &nested, None, "App");
+        assert!(split.is_empty());
+        assert_eq!(rest.len(), 3);
+    }
+
     #[test]
     fn get_root_info_returns_name_and_element() {
         let parsed = json!({ "?xml": {"@version": "1.0"}, "Root": { "child": 1 } });
@@ -431,24 +854,34 @@ mod tests {
         assert!(get_root_info(&json!({ "?xml": {} })).is_none());
     }
 
-    #[tokio::test]
-    async fn unified_build_returns_ok_when_source_unreadable() {
-        // Missing source file: unified build should short-circuit with Ok(()).
+    #[test]
+    fn unified_build_reports_unreadable_source() {
+        // Missing source file: unified build short-circuits with a skip before writing anything.
         let dir = tempfile::tempdir().unwrap();
         let disassembled = dir.path().join("out");
         let missing = dir.path().join("does_not_exist.xml");
-        build_disassembled_files_unified(BuildDisassembledFilesOptions {
+        let err = build_disassembled_files_unified(BuildDisassembledFilesOptions {
             file_path: missing.to_str().unwrap(),
             disassembled_path: disassembled.to_str().unwrap(),
             base_name: "does_not_exist",
             post_purge: false,
-            format: "xml",
+            format: Format::Xml,
             unique_id_elements: None,
-            strategy: "unique-id",
+            strategy: Strategy::UniqueId,
             decompose_rules: None,
+            leaf_split: None,
+            bare: false,
+            sidecars: None,
+            jobs: 1,
+            sink: None,
+            limits: None,
+            transforms: &[],
         })
-        .await
-        .unwrap();
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Unreadable { path, .. } if path == missing
+        ));
         assert!(!disassembled.exists());
     }
 }
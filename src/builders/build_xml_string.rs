@@ -16,11 +16,56 @@ fn value_to_string(v: &Value) -> String {
     }
 }
 
+/// Output still to produce for an element whose start tag has been written.
+enum Step<'a> {
+    Element {
+        name: &'a str,
+        content: &'a Value,
+        indent_level: usize,
+    },
+    Text(String),
+    End(&'a str),
+}
+
+fn indent_text(indent_level: usize) -> Step<'static> {
+    Step::Text(format!("\n{}", "    ".repeat(indent_level)))
+}
+
+/// Write `content` as element `name`. Nested elements are kept on an explicit stack rather than
+/// written recursively, so deeply nested input cannot overflow the call stack.
 fn write_element<W: std::io::Write>(
     writer: &mut Writer<W>,
     name: &str,
     content: &Value,
     indent_level: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut stack = vec![Step::Element {
+        name,
+        content,
+        indent_level,
+    }];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Element {
+                name,
+                content,
+                indent_level,
+            } => write_element_step(writer, name, content, indent_level, &mut stack)?,
+            Step::Text(text) => writer.write_event(Event::Text(BytesText::new(&text)))?,
+            Step::End(name) => writer.write_event(Event::End(BytesEnd::new(name)))?,
+        }
+    }
+    Ok(())
+}
+
+/// Write what comes first of one element and push the rest (children, closing tag) onto
+/// `stack`, last step first.
+fn write_element_step<'a, W: std::io::Write>(
+    writer: &mut Writer<W>,
+    name: &'a str,
+    content: &'a Value,
+    indent_level: usize,
+    stack: &mut Vec<Step<'a>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let indent = "    ".repeat(indent_level);
     let child_indent = "    ".repeat(indent_level + 1);
@@ -78,6 +123,8 @@ fn write_element<W: std::io::Write>(
                     format!("\n{}", child_indent).as_str(),
                 )))?;
 
+                // Steps in document order; pushed reversed below.
+                let mut steps = Vec::new();
                 let child_count = child_elements.len();
                 for (idx, (child_name, child_value)) in child_elements.iter().enumerate() {
                     let is_last = idx == child_count - 1;
@@ -86,47 +133,31 @@ fn write_element<W: std::io::Write>(
                             let arr_len = arr.len();
                             for (i, item) in arr.iter().enumerate() {
                                 let arr_last = i == arr_len - 1;
-                                write_element(writer, child_name, item, indent_level + 1)?;
+                                steps.push(Step::Element {
+                                    name: child_name.as_str(),
+                                    content: item,
+                                    indent_level: indent_level + 1,
+                                });
                                 if !arr_last {
-                                    writer.write_event(Event::Text(BytesText::new(
-                                        format!("\n{}", child_indent).as_str(),
-                                    )))?;
+                                    steps.push(indent_text(indent_level + 1));
                                 }
                             }
-                            if !is_last {
-                                writer.write_event(Event::Text(BytesText::new(
-                                    format!("\n{}", child_indent).as_str(),
-                                )))?;
-                            }
-                        }
-                        Value::Object(_) => {
-                            write_element(writer, child_name, child_value, indent_level + 1)?;
-                            if !is_last {
-                                writer.write_event(Event::Text(BytesText::new(
-                                    format!("\n{}", child_indent).as_str(),
-                                )))?;
-                            }
-                        }
-                        _ => {
-                            writer
-                                .write_event(Event::Start(BytesStart::new(child_name.as_str())))?;
-                            // BytesText::new() expects unescaped content; the writer escapes when writing
-                            writer.write_event(Event::Text(BytesText::new(
-                                value_to_string(child_value).as_str(),
-                            )))?;
-                            writer.write_event(Event::End(BytesEnd::new(child_name.as_str())))?;
-                            if !is_last {
-                                writer.write_event(Event::Text(BytesText::new(
-                                    format!("\n{}", child_indent).as_str(),
-                                )))?;
-                            }
                         }
+                        // Objects and scalars (written as a text-only element) alike.
+                        _ => steps.push(Step::Element {
+                            name: child_name.as_str(),
+                            content: child_value,
+                            indent_level: indent_level + 1,
+                        }),
+                    }
+                    if !is_last {
+                        steps.push(indent_text(indent_level + 1));
                     }
                 }
-
-                writer.write_event(Event::Text(BytesText::new(
-                    format!("\n{}", indent).as_str(),
-                )))?;
+                steps.push(indent_text(indent_level));
+                steps.push(Step::End(name));
+                stack.extend(steps.into_iter().rev());
+                return Ok(());
             } else if !cdata_content.is_empty()
                 || !text_content.is_empty()
                 || !comment_content.is_empty()
@@ -162,9 +193,11 @@ fn write_element<W: std::io::Write>(
             writer.write_event(Event::End(BytesEnd::new(name)))?;
         }
         Value::Array(arr) => {
-            for item in arr {
-                write_element(writer, name, item, indent_level)?;
-            }
+            stack.extend(arr.iter().rev().map(|item| Step::Element {
+                name,
+                content: item,
+                indent_level,
+            }));
         }
         _ => {
             writer.write_event(Event::Start(BytesStart::new(name)))?;
@@ -185,16 +218,14 @@ fn build_xml_from_object(
     // Use Writer::new (no indent) so leaf elements stay compact and match fixture format
     let mut writer = Writer::new(Vec::new());
 
+    let empty = Value::Object(Map::new());
     let (declaration, root_key, root_value) = if let Some(decl) = element.get("?xml") {
         let root_key = element
             .keys()
             .find(|k| *k != "?xml")
             .cloned()
             .unwrap_or_else(|| "root".to_string());
-        let root_value = element
-            .get(&root_key)
-            .cloned()
-            .unwrap_or_else(|| Value::Object(Map::new()));
+        let root_value = element.get(&root_key).unwrap_or(&empty);
         (Some(decl), root_key, root_value)
     } else {
         let root_key = element
@@ -202,10 +233,7 @@ fn build_xml_from_object(
             .next()
             .cloned()
             .unwrap_or_else(|| "root".to_string());
-        let root_value = element
-            .get(&root_key)
-            .cloned()
-            .unwrap_or_else(|| Value::Object(Map::new()));
+        let root_value = element.get(&root_key).unwrap_or(&empty);
         (None, root_key, root_value)
     };
 
@@ -220,12 +248,45 @@ fn build_xml_from_object(
         writer.write_event(Event::Text(BytesText::new("\n")))?;
     }
 
-    write_element(&mut writer, &root_key, &root_value, 0)?;
+    write_element(&mut writer, &root_key, root_value, 0)?;
 
     let result = String::from_utf8(writer.into_inner())?;
     Ok(result.trim_end().to_string())
 }
 
+fn build_xml_fragment_from_object(
+    children: &Map<String, Value>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut writer = Writer::new(Vec::new());
+    let mut first = true;
+    for (key, value) in children.iter().filter(|(k, _)| !k.starts_with('@')) {
+        let items: Vec<&Value> = match value {
+            Value::Array(arr) => arr.iter().collect(),
+            other => vec![other],
+        };
+        for item in items {
+            if !first {
+                writer.write_event(Event::Text(BytesText::new("\n")))?;
+            }
+            first = false;
+            let text = value_to_string(item);
+            match key.as_str() {
+                "#comment" => writer.write_event(Event::Comment(BytesText::new(&text)))?,
+                "#cdata" => writer.write_event(Event::CData(BytesCData::new(&text)))?,
+                "#text" | "#text-tail" => writer.write_event(Event::Text(BytesText::new(&text)))?,
+                _ => write_element(&mut writer, key, item, 0)?,
+            }
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Build an XML fragment: each child of `children` written as a top-level element (no
+/// declaration, no enclosing root). Used for bare part files.
+pub fn build_xml_fragment(children: &Map<String, Value>) -> String {
+    build_xml_fragment_from_object(children).unwrap_or_default()
+}
+
 /// Build XML string from XmlElement.
 pub fn build_xml_string(element: &XmlElement) -> String {
     match element {
@@ -245,6 +306,21 @@ mod tests {
         assert!(build_xml_string(&Value::Null).is_empty());
     }
 
+    #[test]
+    fn build_xml_fragment_writes_siblings_without_root() {
+        let children = json!({
+            "@xmlns": "ignored",
+            "#comment": " note ",
+            "tabs": ["a", "b"],
+            "item": { "@id": "x", "value": "1" }
+        });
+        let out = build_xml_fragment(children.as_object().unwrap());
+        assert_eq!(
+            out,
+            "<!-- note -->\n<tabs>a</tabs>\n<tabs>b</tabs>\n<item id=\"x\">\n    <value>1</value>\n</item>"
+        );
+    }
+
     #[test]
     fn build_xml_string_simple_root() {
         let el = json!({
@@ -398,4 +474,23 @@ mod tests {
         assert!(out.contains("child"));
         assert!(out.contains("v"));
     }
+
+    #[test]
+    fn deeply_nested_element_does_not_overflow_the_stack() {
+        let depth = 2_000;
+        let mut node = json!("leaf");
+        for _ in 0..depth {
+            let mut obj = Map::new();
+            obj.insert("a".to_string(), Value::Array(vec![node]));
+            node = Value::Object(obj);
+        }
+        let mut root = Map::new();
+        root.insert("root".to_string(), node);
+        let root = Value::Object(root);
+        let out = build_xml_string(&root);
+        assert_eq!(out.matches("<a>").count(), depth);
+        assert!(out.contains("<a>leaf</a>"));
+        // Dropping the tree would recurse as deep as it nests.
+        std::mem::forget(root);
+    }
 }
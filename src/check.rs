@@ -0,0 +1,196 @@
+//! Drift check: compare source files with their disassembled directories without writing.
+
+use crate::handlers::DisassembleXmlFileHandler;
+use crate::options::DisassembleOptions;
+use crate::sink::ChangeKind;
+use crate::storage::Storage;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Drift between one source file and its disassembled directory. Part paths are relative to
+/// `directory`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileCheck {
+    pub source: PathBuf,
+    pub directory: PathBuf,
+    /// Parts a disassembly would create.
+    pub missing: Vec<PathBuf>,
+    /// Parts whose content differs from what a disassembly would write.
+    pub stale: Vec<PathBuf>,
+    /// Files in the directory a disassembly would not write.
+    pub extra: Vec<PathBuf>,
+}
+
+impl FileCheck {
+    pub fn is_up_to_date(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Result of [`check_disassembly`].
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub up_to_date: bool,
+    pub files: Vec<FileCheck>,
+}
+
+impl CheckReport {
+    /// Machine-readable report (one JSON object) for hooks and pipeline gates.
+    pub fn to_json(&self) -> String {
+        // Only strings, paths and booleans: serialization cannot fail.
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let drifted: Vec<&FileCheck> = self.files.iter().filter(|c| !c.is_up_to_date()).collect();
+        for check in &drifted {
+            writeln!(
+                f,
+                "{} -> {}",
+                check.source.display(),
+                check.directory.display()
+            )?;
+            for (label, parts) in [
+                ("missing", &check.missing),
+                ("stale", &check.stale),
+                ("extra", &check.extra),
+            ] {
+                for part in parts {
+                    writeln!(f, "  {:<7} {}", label, part.display())?;
+                }
+            }
+        }
+        if drifted.is_empty() {
+            writeln!(
+                f,
+                "All {} disassembled file(s) are up to date",
+                self.files.len()
+            )
+        } else {
+            writeln!(
+                f,
+                "{} of {} disassembled file(s) are out of date",
+                drifted.len(),
+                self.files.len()
+            )
+        }
+    }
+}
+
+/// Compare the file (or every file of the directory) at `path` with the directories `handler`
+/// would disassemble it into with `options`, without writing anything. Purging does not apply and
+/// multi-level disassembly is not checked.
+pub fn check_disassembly(
+    mut handler: DisassembleXmlFileHandler,
+    path: &str,
+    options: &DisassembleOptions,
+) -> Result<CheckReport, Box<dyn std::error::Error + Send + Sync>> {
+    let options = DisassembleOptions {
+        pre_purge: false,
+        post_purge: false,
+        multi_level: None,
+        dry_run: true,
+        ..options.clone()
+    };
+    handler.disassemble(path, &options)?;
+    let changes = handler.take_planned_changes();
+    let cwd = std::env::current_dir().unwrap_or_default();
+
+    let mut files = Vec::new();
+    for (source, directory) in handler.take_planned_outputs() {
+        let relative = |p: &Path| p.strip_prefix(&directory).unwrap_or(p).to_path_buf();
+        let mut planned = HashSet::new();
+        let mut missing = Vec::new();
+        let mut stale = Vec::new();
+        for change in changes.iter().filter(|c| c.path.starts_with(&directory)) {
+            planned.insert(change.path.clone());
+            match change.kind {
+                ChangeKind::Create => missing.push(relative(&change.path)),
+                ChangeKind::Overwrite => stale.push(relative(&change.path)),
+                ChangeKind::Unchanged | ChangeKind::Delete => {}
+            }
+        }
+        let extra = list_files(handler.storage(), &directory)?
+            .into_iter()
+            .filter(|p| !planned.contains(p))
+            .map(|p| relative(&p))
+            .collect();
+        files.push(FileCheck {
+            source: source.strip_prefix(&cwd).unwrap_or(&source).to_path_buf(),
+            directory: directory
+                .strip_prefix(&cwd)
+                .unwrap_or(&directory)
+                .to_path_buf(),
+            missing,
+            stale,
+            extra,
+        });
+    }
+    Ok(CheckReport {
+        up_to_date: files.iter().all(FileCheck::is_up_to_date),
+        files,
+    })
+}
+
+/// Every file below `dir` (none when it does not exist), sorted.
+fn list_files(
+    storage: &dyn Storage,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut files = Vec::new();
+    if !storage.is_dir(dir) {
+        return Ok(files);
+    }
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for path in storage.list(&current)? {
+            if storage.is_dir(&path) {
+                stack.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_drift_and_serializes() {
+        let report = CheckReport {
+            up_to_date: false,
+            files: vec![
+                FileCheck {
+                    source: PathBuf::from("a.xml"),
+                    directory: PathBuf::from("a"),
+                    missing: vec![PathBuf::from("x.xml")],
+                    stale: Vec::new(),
+                    extra: vec![PathBuf::from("old.xml")],
+                },
+                FileCheck {
+                    source: PathBuf::from("b.xml"),
+                    directory: PathBuf::from("b"),
+                    missing: Vec::new(),
+                    stale: Vec::new(),
+                    extra: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(
+            report.to_string(),
+            "a.xml -> a\n  missing x.xml\n  extra   old.xml\n1 of 2 disassembled file(s) are out of date\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["up_to_date"], false);
+        assert_eq!(json["files"][0]["missing"][0], "x.xml");
+        assert_eq!(json["files"][1]["extra"].as_array().unwrap().len(), 0);
+    }
+}
@@ -3,7 +3,15 @@
 //! Kept in the library crate so it can be exercised by unit tests and
 //! the binary stays a thin shim.
 
-use crate::{DecomposeRule, DisassembleXmlFileHandler, MultiLevelRule, ReassembleXmlFileHandler};
+use crate::check::check_disassembly;
+use crate::sink::{render_report, ChangeCounts};
+use crate::verify::{verify_round_trip, CompareMode, VerifyOptions};
+use crate::{
+    DecomposeRule, DisassembleOptions, DisassembleXmlFileHandler, LeafSplitRule, Limits,
+    MultiLevelRule, OptionsError, ReassembleOptions, ReassembleXmlFileHandler, RuleAction,
+    RunReport, SidecarRule, Strategy, TransformRule,
+};
+use std::sync::Arc;
 
 /// Options parsed from disassemble CLI args.
 pub struct DisassembleOpts<'a> {
@@ -16,44 +24,217 @@ pub struct DisassembleOpts<'a> {
     pub strategy: Option<&'a str>,
     pub multi_level: Option<String>,
     pub split_tags: Option<String>,
+    pub split_leaves: Option<String>,
+    pub split_leaves_over: Option<usize>,
+    pub bare: bool,
+    pub sidecars: Option<String>,
+    pub transforms: Option<&'a str>,
+    pub output_dir: Option<&'a str>,
+    pub type_suffix_dirs: bool,
+    pub include: Option<&'a str>,
+    pub exclude: Option<&'a str>,
+    pub extensions: Option<&'a str>,
+    pub jobs: Option<usize>,
+    pub dry_run: bool,
+    pub sync: bool,
+    pub hash_index: bool,
+    pub strict: bool,
+    pub max_depth: Option<usize>,
+    pub max_document_size: Option<u64>,
+    pub max_parts: Option<usize>,
+    pub max_filename_length: Option<usize>,
+}
+
+/// Options parsed from reassemble CLI args.
+pub struct ReassembleOpts<'a> {
+    pub path: Option<&'a str>,
+    pub extension: Option<&'a str>,
+    pub post_purge: bool,
+    pub output: Option<&'a str>,
+    pub output_dir: Option<&'a str>,
+    pub recursive: bool,
+    pub jobs: Option<usize>,
+    pub dry_run: bool,
+    pub strict: bool,
+    pub transforms: Option<&'a str>,
+}
+
+/// Parse --sidecar spec. Comma-separated rules; each rule is `tag:extension` or
+/// `tag:extension:min_len` (e.g. `htmlContent:html,body:bin:1024`). Rules without a tag or
+/// extension are dropped; a `min_len` that is not a number is an error.
+pub fn parse_sidecar_spec(spec: &str) -> Result<Vec<SidecarRule>, OptionsError> {
+    let mut rules = Vec::new();
+    for part in spec.split(',') {
+        let segs: Vec<&str> = part.trim().split(':').collect();
+        let (tag, extension, min_len) = match segs.as_slice() {
+            [tag, ext] => (*tag, *ext, 0),
+            [tag, ext, min] => (*tag, *ext, parse_number("--sidecar min_len", min)?),
+            _ => continue,
+        };
+        if tag.is_empty() || extension.is_empty() {
+            continue;
+        }
+        rules.push(SidecarRule {
+            tag: tag.to_string(),
+            extension: extension.trim_start_matches('.').to_string(),
+            min_len,
+        });
+    }
+    Ok(rules)
+}
+
+/// Parse --transform spec. Comma-separated rules: `drop:<selector>`, `rename:<selector>:<tag>` or
+/// `replace:<selector>:<from>:<to>` (`to` may contain colons). The selector is a tag or a
+/// `/`-separated path (see [`TransformRule`]). Invalid rules are dropped with a warning.
+pub fn parse_transform_spec(spec: &str) -> Vec<TransformRule> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .filter_map(|part| {
+            let segs: Vec<&str> = part.splitn(4, ':').collect();
+            let action = match segs.as_slice() {
+                ["drop", selector] if !selector.is_empty() => RuleAction::Drop,
+                ["rename", selector, tag] if !selector.is_empty() && !tag.is_empty() => {
+                    RuleAction::Rename(tag.to_string())
+                }
+                ["replace", selector, from, to] if !selector.is_empty() && !from.is_empty() => {
+                    RuleAction::Replace {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    }
+                }
+                _ => {
+                    log::warn!("Ignoring invalid transform rule \"{}\"", part);
+                    return None;
+                }
+            };
+            Some(TransformRule {
+                selector: segs[1].to_string(),
+                action,
+            })
+        })
+        .collect()
+}
+
+/// Build the leaf split rule from `--split-leaves` (comma-separated tags) and
+/// `--split-leaves-over` (a tag repeated more than this many times).
+pub fn parse_leaf_split(tags: Option<&str>, threshold: Option<usize>) -> LeafSplitRule {
+    LeafSplitRule {
+        tags: tags.map(parse_list).unwrap_or_default(),
+        threshold,
+    }
+}
+
+/// Split a comma-separated option value, trimming entries and dropping empty ones.
+pub fn parse_list(spec: &str) -> Vec<String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
 }
 
 /// Parse --split-tags spec for grouped-by-tag. Comma-separated rules; each rule:
 /// `tag:mode:field` (path_segment defaults to tag) or `tag:path:mode:field`.
 /// mode = "split" (one file per item) or "group" (group by field).
+/// field may name an attribute with a leading '@' (e.g. `@id`) or a child's attribute
+/// (`child/@id`); namespaced attributes such as `@xml:lang` keep their colon because everything
+/// from the segment naming the attribute on is the field.
+/// Chain rules with '>' to decompose inside each split item, e.g.
+/// `profileSection:split:name>recordTypeVisibilities:group:recordType`; chains sharing a parent
+/// tag are merged into one rule with several children.
 pub fn parse_decompose_spec(spec: &str) -> Vec<DecomposeRule> {
     let mut rules = Vec::new();
     for part in spec.split(',') {
-        let part = part.trim();
-        let segments: Vec<&str> = part.splitn(4, ':').collect();
-        if segments.len() >= 3 {
-            let tag = segments[0].to_string();
-            let (path_segment, mode, field) = if segments.len() == 3 {
-                (
-                    tag.clone(),
-                    segments[1].to_string(),
-                    segments[2].to_string(),
-                )
-            } else {
-                (
-                    segments[1].to_string(),
-                    segments[2].to_string(),
-                    segments[3].to_string(),
-                )
-            };
-            if !tag.is_empty() && !mode.is_empty() && !field.is_empty() {
-                rules.push(DecomposeRule {
-                    tag,
-                    path_segment,
-                    mode,
-                    field,
-                });
-            }
+        let chain: Option<Vec<DecomposeRule>> = part.split('>').map(parse_decompose_rule).collect();
+        if let Some(chain) = chain {
+            insert_rule_chain(&mut rules, chain);
         }
     }
     rules
 }
 
+/// Parse a single `tag:mode:field` / `tag:path:mode:field` rule.
+/// A rule with an unknown mode is dropped with a warning.
+fn parse_decompose_rule(part: &str) -> Option<DecomposeRule> {
+    let segments = split_rule_segments(part.trim());
+    if segments.len() < 3 {
+        return None;
+    }
+    let tag = segments[0].to_string();
+    let (path_segment, mode, field) = if segments.len() == 3 {
+        (
+            tag.clone(),
+            segments[1].to_string(),
+            segments[2].to_string(),
+        )
+    } else {
+        (
+            segments[1].to_string(),
+            segments[2].to_string(),
+            segments[3].to_string(),
+        )
+    };
+    if tag.is_empty() || mode.is_empty() || field.is_empty() {
+        return None;
+    }
+    let mode = match mode.parse() {
+        Ok(mode) => mode,
+        Err(err) => {
+            log::warn!("Ignoring split-tags rule \"{}\": {}", part.trim(), err);
+            return None;
+        }
+    };
+    Some(DecomposeRule {
+        tag,
+        path_segment,
+        mode,
+        field,
+        ..Default::default()
+    })
+}
+
+/// Insert `parent > child > ...` into the rule tree, reusing an existing rule for the same tag.
+fn insert_rule_chain(rules: &mut Vec<DecomposeRule>, chain: Vec<DecomposeRule>) {
+    let mut chain = chain.into_iter();
+    let Some(head) = chain.next() else {
+        return;
+    };
+    let rest: Vec<DecomposeRule> = chain.collect();
+    match rules.iter_mut().find(|r| r.tag == head.tag) {
+        Some(existing) => insert_rule_chain(&mut existing.children, rest),
+        None => {
+            let mut head = head;
+            insert_rule_chain(&mut head.children, rest);
+            rules.push(head);
+        }
+    }
+}
+
+/// Split one decompose rule into at most 4 segments; the field (last segment) keeps any ':'
+/// when it is an attribute or a path to one, so `tag:split:@xml:lang` yields
+/// `["tag", "split", "@xml:lang"]` and `tag:group:child/@xml:lang` yields
+/// `["tag", "group", "child/@xml:lang"]`.
+fn split_rule_segments(part: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = part;
+    while segments.len() < 3 {
+        let next = rest.split(':').next().unwrap_or_default();
+        if segments.len() >= 2 && (next.starts_with('@') || next.contains("/@")) {
+            break;
+        }
+        match rest.split_once(':') {
+            Some((head, tail)) => {
+                segments.push(head);
+                rest = tail;
+            }
+            None => break,
+        }
+    }
+    segments.push(rest);
+    segments
+}
+
 /// Parse --multi-level spec: `file_pattern:root_to_strip:unique_id_elements`.
 pub fn parse_multi_level_spec(spec: &str) -> Option<MultiLevelRule> {
     let parts: Vec<&str> = spec.splitn(3, ':').collect();
@@ -75,8 +256,19 @@ pub fn parse_multi_level_spec(spec: &str) -> Option<MultiLevelRule> {
     })
 }
 
-/// Parse disassemble args: `<path> [options]`.
-pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
+/// Parse the value of a numeric option; anything but a non-negative integer is a usage error.
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, OptionsError> {
+    value.trim().parse().map_err(|_| {
+        OptionsError::Invalid(format!(
+            "{} expects a non-negative number, got \"{}\"",
+            option, value
+        ))
+    })
+}
+
+/// Parse disassemble args: `<path> [options]`. A numeric option with a value that is not a
+/// number is an error.
+pub fn parse_disassemble_args(args: &[String]) -> Result<DisassembleOpts<'_>, OptionsError> {
     let mut path = None;
     let mut unique_id_elements = None;
     let mut pre_purge = false;
@@ -86,6 +278,25 @@ pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
     let mut strategy = None;
     let mut multi_level = None;
     let mut split_tags = None;
+    let mut split_leaves = None;
+    let mut split_leaves_over = None;
+    let mut bare = false;
+    let mut sidecars = None;
+    let mut transforms = None;
+    let mut output_dir = None;
+    let mut type_suffix_dirs = false;
+    let mut include = None;
+    let mut exclude = None;
+    let mut extensions = None;
+    let mut jobs = None;
+    let mut dry_run = false;
+    let mut sync = false;
+    let mut hash_index = false;
+    let mut strict = false;
+    let mut max_depth = None;
+    let mut max_document_size = None;
+    let mut max_parts = None;
+    let mut max_filename_length = None;
 
     let mut i = 0;
     while i < args.len() {
@@ -93,9 +304,27 @@ pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
         if arg == "--postpurge" {
             post_purge = true;
             i += 1;
+        } else if arg == "--sync" {
+            sync = true;
+            i += 1;
+        } else if arg == "--hash-index" {
+            hash_index = true;
+            i += 1;
+        } else if arg == "--strict" {
+            strict = true;
+            i += 1;
+        } else if arg == "--type-suffix-dirs" {
+            type_suffix_dirs = true;
+            i += 1;
+        } else if arg == "--dry-run" {
+            dry_run = true;
+            i += 1;
         } else if arg == "--prepurge" {
             pre_purge = true;
             i += 1;
+        } else if arg == "--bare" {
+            bare = true;
+            i += 1;
         } else if let Some(rest) = arg.strip_prefix("--unique-id-elements=") {
             unique_id_elements = Some(rest);
             i += 1;
@@ -150,6 +379,123 @@ pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
                 split_tags = Some(args[i].clone());
                 i += 1;
             }
+        } else if let Some(rest) = arg.strip_prefix("--split-leaves=") {
+            split_leaves = Some(rest.to_string());
+            i += 1;
+        } else if arg == "--split-leaves" {
+            i += 1;
+            if i < args.len() {
+                split_leaves = Some(args[i].clone());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--split-leaves-over=") {
+            split_leaves_over = Some(parse_number("--split-leaves-over", rest)?);
+            i += 1;
+        } else if arg == "--split-leaves-over" {
+            i += 1;
+            if i < args.len() {
+                split_leaves_over = Some(parse_number("--split-leaves-over", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--sidecar=") {
+            sidecars = Some(rest.to_string());
+            i += 1;
+        } else if arg == "--sidecar" {
+            i += 1;
+            if i < args.len() {
+                sidecars = Some(args[i].clone());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--transform=") {
+            transforms = Some(rest);
+            i += 1;
+        } else if arg == "--transform" {
+            i += 1;
+            if i < args.len() {
+                transforms = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--output-dir=") {
+            output_dir = Some(rest);
+            i += 1;
+        } else if arg == "--output-dir" {
+            i += 1;
+            if i < args.len() {
+                output_dir = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--include=") {
+            include = Some(rest);
+            i += 1;
+        } else if arg == "--include" {
+            i += 1;
+            if i < args.len() {
+                include = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--exclude=") {
+            exclude = Some(rest);
+            i += 1;
+        } else if arg == "--exclude" {
+            i += 1;
+            if i < args.len() {
+                exclude = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--extensions=") {
+            extensions = Some(rest);
+            i += 1;
+        } else if arg == "--extensions" {
+            i += 1;
+            if i < args.len() {
+                extensions = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--jobs=") {
+            jobs = Some(parse_number("--jobs", rest)?);
+            i += 1;
+        } else if arg == "--jobs" {
+            i += 1;
+            if i < args.len() {
+                jobs = Some(parse_number("--jobs", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--max-depth=") {
+            max_depth = Some(parse_number("--max-depth", rest)?);
+            i += 1;
+        } else if arg == "--max-depth" {
+            i += 1;
+            if i < args.len() {
+                max_depth = Some(parse_number("--max-depth", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--max-document-size=") {
+            max_document_size = Some(parse_number("--max-document-size", rest)?);
+            i += 1;
+        } else if arg == "--max-document-size" {
+            i += 1;
+            if i < args.len() {
+                max_document_size = Some(parse_number("--max-document-size", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--max-parts=") {
+            max_parts = Some(parse_number("--max-parts", rest)?);
+            i += 1;
+        } else if arg == "--max-parts" {
+            i += 1;
+            if i < args.len() {
+                max_parts = Some(parse_number("--max-parts", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--max-filename-length=") {
+            max_filename_length = Some(parse_number("--max-filename-length", rest)?);
+            i += 1;
+        } else if arg == "--max-filename-length" {
+            i += 1;
+            if i < args.len() {
+                max_filename_length = Some(parse_number("--max-filename-length", &args[i])?);
+                i += 1;
+            }
         } else if arg.starts_with("--") {
             i += 1;
         } else if path.is_none() {
@@ -160,7 +506,7 @@ pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
         }
     }
 
-    DisassembleOpts {
+    Ok(DisassembleOpts {
         path,
         unique_id_elements,
         pre_purge,
@@ -170,24 +516,116 @@ pub fn parse_disassemble_args(args: &[String]) -> DisassembleOpts<'_> {
         strategy,
         multi_level,
         split_tags,
-    }
+        split_leaves,
+        split_leaves_over,
+        bare,
+        sidecars,
+        transforms,
+        output_dir,
+        type_suffix_dirs,
+        include,
+        exclude,
+        extensions,
+        jobs,
+        dry_run,
+        sync,
+        hash_index,
+        strict,
+        max_depth,
+        max_document_size,
+        max_parts,
+        max_filename_length,
+    })
 }
 
-/// Parse reassemble args: `<path> [extension] [--postpurge]`.
-pub fn parse_reassemble_args(args: &[String]) -> (Option<&str>, Option<&str>, bool) {
+/// Parse reassemble args: `<path> [extension] [--postpurge] [--recursive] [--dry-run] [--strict] [--transform <spec>] [--output <file>] [--output-dir <dir>]`.
+/// A `--jobs` value that is not a number is an error.
+pub fn parse_reassemble_args(args: &[String]) -> Result<ReassembleOpts<'_>, OptionsError> {
     let mut path = None;
     let mut extension = None;
     let mut post_purge = false;
-    for arg in args {
+    let mut output = None;
+    let mut output_dir = None;
+    let mut recursive = false;
+    let mut jobs = None;
+    let mut dry_run = false;
+    let mut strict = false;
+    let mut transforms = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
         if arg == "--postpurge" {
             post_purge = true;
+            i += 1;
+        } else if arg == "--strict" {
+            strict = true;
+            i += 1;
+        } else if arg == "--recursive" || arg == "-r" {
+            recursive = true;
+            i += 1;
+        } else if arg == "--dry-run" {
+            dry_run = true;
+            i += 1;
+        } else if let Some(rest) = arg.strip_prefix("--jobs=") {
+            jobs = Some(parse_number("--jobs", rest)?);
+            i += 1;
+        } else if arg == "--jobs" {
+            i += 1;
+            if i < args.len() {
+                jobs = Some(parse_number("--jobs", &args[i])?);
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--transform=") {
+            transforms = Some(rest);
+            i += 1;
+        } else if arg == "--transform" {
+            i += 1;
+            if i < args.len() {
+                transforms = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--output=") {
+            output = Some(rest);
+            i += 1;
+        } else if arg == "--output" {
+            i += 1;
+            if i < args.len() {
+                output = Some(args[i].as_str());
+                i += 1;
+            }
+        } else if let Some(rest) = arg.strip_prefix("--output-dir=") {
+            output_dir = Some(rest);
+            i += 1;
+        } else if arg == "--output-dir" {
+            i += 1;
+            if i < args.len() {
+                output_dir = Some(args[i].as_str());
+                i += 1;
+            }
         } else if path.is_none() {
             path = Some(arg.as_str());
+            i += 1;
         } else if extension.is_none() {
             extension = Some(arg.as_str());
+            i += 1;
+        } else {
+            i += 1;
         }
     }
-    (path, extension, post_purge)
+
+    Ok(ReassembleOpts {
+        path,
+        extension,
+        post_purge,
+        output,
+        output_dir,
+        recursive,
+        jobs,
+        dry_run,
+        strict,
+        transforms,
+    })
 }
 
 /// Print CLI usage to stderr.
@@ -196,6 +634,8 @@ pub fn print_usage() {
     eprintln!("  disassemble <path> [options]     - Disassemble XML file or directory");
     eprintln!("    --postpurge                    - Delete original file/dir after disassembling (default: false)");
     eprintln!("    --prepurge                     - Remove existing disassembly output before running (default: false)");
+    eprintln!("    --sync                         - Remove previously generated parts this run no longer produces (default: false)");
+    eprintln!("    --hash-index                   - Store part hashes in .hashes.json to skip unchanged parts faster (default: false)");
     eprintln!(
         "    --unique-id-elements <list>    - Comma-separated element names for nested filenames"
     );
@@ -208,11 +648,43 @@ pub fn print_usage() {
     );
     eprintln!("    --multi-level <spec>          - Further disassemble matching files: file_pattern:root_to_strip:unique_id_elements");
     eprintln!("    -p, --split-tags <spec>       - With grouped-by-tag: split/group nested tags (e.g. objectPermissions:split:object,fieldPermissions:group:field)");
-    eprintln!("  reassemble <path> [extension] [--postpurge]  - Reassemble directory (default extension: xml)");
+    eprintln!("    --split-leaves <list>          - Comma-separated leaf tags written to their own file (e.g. tabs)");
+    eprintln!("    --split-leaves-over <n>        - Write any leaf tag repeated more than n times to its own file");
+    eprintln!("    --bare                         - Write parts without declaration/root wrapper (stored once in .root.json)");
+    eprintln!("    --sidecar <spec>               - Extract tag content to raw files: tag:ext[:min_len],... (ext bin = base64)");
+    eprintln!("    --transform <spec>             - Change elements before splitting: drop:<sel>, rename:<sel>:<tag>, replace:<sel>:<from>:<to>,...");
+    eprintln!("    --output-dir <dir>             - Write disassembled output under dir, mirroring relative paths");
+    eprintln!("    --include <globs>              - Directories: only disassemble files matching these globs (e.g. **/*.permissionset-meta.xml)");
+    eprintln!("    --exclude <globs>              - Directories: skip files and folders matching these globs");
+    eprintln!("    --extensions <list>            - Directories: extensions treated as XML (default: xml; e.g. xml,xsd,wsdl,resx)");
+    eprintln!("    --jobs <n>                     - Process up to n files and n parts at a time (default: 1)");
+    eprintln!(
+        "    --max-depth <n>                - Fail on elements nested deeper than n (default: 256)"
+    );
+    eprintln!("    --max-document-size <bytes>    - Fail on source files larger than this (default: 268435456)");
+    eprintln!("    --max-parts <n>                - Fail when a source file would produce more than n parts (default: 100000)");
+    eprintln!("    --max-filename-length <n>      - Fail when a part file or folder name is longer than n bytes (default: 255)");
+    eprintln!("    --type-suffix-dirs             - Name output dirs with the type suffix (Admin.permissionset/), not just on collisions");
+    eprintln!("    --dry-run                      - Report files that would be created, overwritten or deleted; write nothing");
+    eprintln!("    --strict                       - Fail on files that would be skipped (not XML, unparsable, only leaves, ...)");
+    eprintln!("  reassemble <path> [extension] [--postpurge]  - Reassemble directory (default: original file name, else xml)");
+    eprintln!(
+        "    -r, --recursive                - Reassemble every disassembled directory under path"
+    );
+    eprintln!("    --jobs <n>                     - Parse up to n parts and rebuild up to n directories at a time (default: 1)");
+    eprintln!("    --output <file>                - Write the reassembled file to this path");
+    eprintln!("    --output-dir <dir>             - Write the reassembled file into dir");
+    eprintln!("    --dry-run                      - Report the files that would change, with a diff; write nothing");
+    eprintln!("    --strict                       - Fail on directories that would be skipped (no parts, no root, ...)");
+    eprintln!("    --transform <spec>             - Change elements of the merged file: drop:<sel>, rename:<sel>:<tag>, replace:<sel>:<from>:<to>,...");
+    eprintln!("  verify <file> [disassemble options] [--bytes]  - Round-trip the file in a temp dir and report differences");
+    eprintln!("    --bytes                        - Compare bytes instead of parsed content (default: semantic)");
+    eprintln!("  check <path> [disassemble options] [--json]  - Report missing, stale and extra parts without writing");
+    eprintln!("    --json                         - Print the report as JSON");
 }
 
 /// Run the CLI with the given args. `args[0]` is expected to be the program name.
-pub async fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     if args.len() < 2 {
         print_usage();
         return Ok(());
@@ -220,8 +692,10 @@ pub async fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error + Se
 
     let command = &args[1];
     match command.as_str() {
-        "disassemble" => run_disassemble(&args[2..]).await?,
-        "reassemble" => run_reassemble(&args[2..]).await?,
+        "disassemble" => run_disassemble(&args[2..])?,
+        "reassemble" => run_reassemble(&args[2..])?,
+        "verify" => run_verify(&args[2..])?,
+        "check" => run_check(&args[2..])?,
         _ => {
             eprintln!("Unknown command: {}", command);
         }
@@ -230,60 +704,206 @@ pub async fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error + Se
     Ok(())
 }
 
-async fn run_disassemble(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let opts = parse_disassemble_args(args);
-    let path = opts.path.unwrap_or(".");
-    let strategy = opts.strategy.unwrap_or("unique-id");
-    let multi_level_rule = opts
-        .multi_level
+/// Disassembly options requested on the command line (shared with `verify` and `check`).
+/// Unknown strategies and formats and invalid numbers, sidecar specs or globs are errors; an
+/// invalid multi-level spec is reported and skipped, and split tags only apply to the
+/// grouped-by-tag strategy.
+fn disassemble_options(opts: &DisassembleOpts<'_>) -> Result<DisassembleOptions, OptionsError> {
+    let strategy: Strategy = opts
+        .strategy
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or_default();
+    let mut builder = DisassembleOptions::builder()
+        .strategy(strategy)
+        .format(opts.format.parse()?)
+        .pre_purge(opts.pre_purge)
+        .post_purge(opts.post_purge)
+        .ignore_path(opts.ignore_path)
+        .strict(opts.strict)
+        .leaf_split(parse_leaf_split(
+            opts.split_leaves.as_deref(),
+            opts.split_leaves_over,
+        ))
+        .bare_parts(opts.bare)
+        .type_suffix_dirs(opts.type_suffix_dirs)
+        .include(opts.include.map(parse_list).unwrap_or_default())
+        .exclude(opts.exclude.map(parse_list).unwrap_or_default())
+        .extensions(opts.extensions.map(parse_list).unwrap_or_default())
+        .jobs(opts.jobs.unwrap_or(1))
+        .dry_run(opts.dry_run)
+        .sync(opts.sync)
+        .hash_index(opts.hash_index)
+        .limits(disassemble_limits(opts))
+        .sidecars(
+            opts.sidecars
+                .as_deref()
+                .map(parse_sidecar_spec)
+                .transpose()?
+                .unwrap_or_default(),
+        );
+    if let Some(dir) = opts.output_dir {
+        builder = builder.output_dir(dir);
+    }
+    if let Some(elements) = opts.unique_id_elements {
+        builder = builder.unique_id_elements(elements);
+    }
+    if let Some(spec) = &opts.multi_level {
+        match parse_multi_level_spec(spec) {
+            Some(rule) => builder = builder.multi_level(rule),
+            None => eprintln!(
+                "Invalid --multi-level spec; use file_pattern:root_to_strip:unique_id_elements"
+            ),
+        }
+    }
+    if let Some(spec) = opts
+        .split_tags
         .as_ref()
-        .and_then(|s| parse_multi_level_spec(s));
-    if opts.multi_level.is_some() && multi_level_rule.is_none() {
-        eprintln!("Invalid --multi-level spec; use file_pattern:root_to_strip:unique_id_elements");
-    }
-    let decompose_rules: Vec<DecomposeRule> = if strategy == "grouped-by-tag" {
-        opts.split_tags
-            .as_ref()
-            .map(|s| parse_decompose_spec(s))
-            .unwrap_or_default()
+        .filter(|_| strategy == Strategy::GroupedByTag)
+    {
+        builder = builder.decompose_rules(parse_decompose_spec(spec));
+    }
+    for rule in opts
+        .transforms
+        .map(parse_transform_spec)
+        .unwrap_or_default()
+    {
+        builder = builder.transform(Arc::new(rule));
+    }
+    builder.build()
+}
+
+/// Limits requested by disassemble options; unset ones keep their defaults.
+fn disassemble_limits(opts: &DisassembleOpts<'_>) -> Limits {
+    let defaults = Limits::default();
+    Limits {
+        max_depth: opts.max_depth.unwrap_or(defaults.max_depth),
+        max_document_size: opts.max_document_size.unwrap_or(defaults.max_document_size),
+        max_parts: opts.max_parts.unwrap_or(defaults.max_parts),
+        max_filename_length: opts
+            .max_filename_length
+            .unwrap_or(defaults.max_filename_length),
+    }
+}
+
+/// Point out skipped files after a run; each reason was already logged.
+fn report_skipped(report: &RunReport) {
+    if !report.skipped.is_empty() {
+        eprintln!(
+            "Skipped {} of {}; pass --strict to fail instead",
+            report.skipped.len(),
+            report.skipped.len() + report.processed.len()
+        );
+    }
+}
+
+fn run_disassemble(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let opts = parse_disassemble_args(args)?;
+    let path = opts.path.unwrap_or(".");
+    let options = disassemble_options(&opts)?;
+    let mut handler = DisassembleXmlFileHandler::new();
+    handler.disassemble(path, &options)?;
+    let changes = handler.take_planned_changes();
+    if opts.dry_run {
+        print!("{}", render_report(&changes));
     } else {
-        Vec::new()
+        println!("Disassembled: {}", ChangeCounts::from_changes(&changes));
+    }
+    report_skipped(&handler.take_report());
+    Ok(())
+}
+
+/// Round-trip a file with the given disassemble options and compare the result with the original
+/// (`--bytes` for a byte comparison). Differences are printed and make the command fail.
+fn run_verify(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let opts = parse_disassemble_args(args)?;
+    let Some(path) = opts.path else {
+        return Err("verify needs the path of an XML file".into());
     };
-    let decompose_rules_ref = if decompose_rules.is_empty() {
-        None
-    } else {
-        Some(decompose_rules.as_slice())
+    let options = VerifyOptions {
+        disassemble: disassemble_options(&opts)?,
+        mode: if args.iter().any(|a| a == "--bytes") {
+            CompareMode::Bytes
+        } else {
+            CompareMode::Semantic
+        },
     };
-    let mut handler = DisassembleXmlFileHandler::new();
-    handler
-        .disassemble(
-            path,
-            opts.unique_id_elements,
-            Some(strategy),
-            opts.pre_purge,
-            opts.post_purge,
-            opts.ignore_path,
-            opts.format,
-            multi_level_rule.as_ref(),
-            decompose_rules_ref,
+    let report = verify_round_trip(DisassembleXmlFileHandler::new(), path, &options)?;
+    print!("{}", report);
+    if report.is_lossless() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not reproduced by a disassemble/reassemble round trip",
+            path
         )
-        .await?;
-    Ok(())
+        .into())
+    }
+}
+
+/// Compare source files with their disassembled directories without writing (`--json` for
+/// machine-readable output). Drift makes the command fail.
+fn run_check(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let opts = parse_disassemble_args(args)?;
+    let path = opts.path.unwrap_or(".");
+    if opts.multi_level.is_some() {
+        eprintln!("check does not compare multi-level disassembly; --multi-level is ignored");
+    }
+    let options = disassemble_options(&opts)?;
+    let report = check_disassembly(DisassembleXmlFileHandler::new(), path, &options)?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+    if report.up_to_date {
+        Ok(())
+    } else {
+        Err(format!("disassembled output for {} is out of date", path).into())
+    }
 }
 
-async fn run_reassemble(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let (path, extension, post_purge) = parse_reassemble_args(args);
-    let path = path.unwrap_or(".");
-    let handler = ReassembleXmlFileHandler::new();
-    handler
-        .reassemble(path, extension.or(Some("xml")), post_purge)
-        .await?;
+fn run_reassemble(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let opts = parse_reassemble_args(args)?;
+    let path = opts.path.unwrap_or(".");
+    let mut options = ReassembleOptions::builder()
+        .post_purge(opts.post_purge)
+        .strict(opts.strict)
+        .recursive(opts.recursive)
+        .jobs(opts.jobs.unwrap_or(1))
+        .dry_run(opts.dry_run);
+    if let Some(output) = opts.output {
+        options = options.output(output);
+    }
+    if let Some(dir) = opts.output_dir {
+        options = options.output_dir(dir);
+    }
+    if let Some(extension) = opts.extension {
+        options = options.extension(extension);
+    }
+    for rule in opts
+        .transforms
+        .map(parse_transform_spec)
+        .unwrap_or_default()
+    {
+        options = options.transform(Arc::new(rule));
+    }
+    let mut handler = ReassembleXmlFileHandler::new();
+    handler.reassemble(path, &options.build()?)?;
+    let changes = handler.take_planned_changes();
+    if opts.dry_run {
+        print!("{}", render_report(&changes));
+    } else {
+        println!("Reassembled: {}", ChangeCounts::from_changes(&changes));
+    }
+    report_skipped(&handler.take_report());
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DecomposeMode;
 
     fn sv(s: &str) -> String {
         s.to_string()
@@ -296,7 +916,7 @@ mod tests {
         let r = &rules[0];
         assert_eq!(r.tag, "objectPermissions");
         assert_eq!(r.path_segment, "objectPermissions");
-        assert_eq!(r.mode, "split");
+        assert_eq!(r.mode, DecomposeMode::Split);
         assert_eq!(r.field, "object");
     }
 
@@ -307,7 +927,7 @@ mod tests {
         let r = &rules[0];
         assert_eq!(r.tag, "fieldPermissions");
         assert_eq!(r.path_segment, "fieldPerms");
-        assert_eq!(r.mode, "group");
+        assert_eq!(r.mode, DecomposeMode::Group);
         assert_eq!(r.field, "field");
     }
 
@@ -321,6 +941,55 @@ mod tests {
         assert_eq!(rules[2].path_segment, "x");
     }
 
+    #[test]
+    fn parse_decompose_spec_attribute_field_keeps_namespace_colon() {
+        let rules = parse_decompose_spec("trans-unit:split:@xml:lang,item:items:group:@id");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].tag, "trans-unit");
+        assert_eq!(rules[0].path_segment, "trans-unit");
+        assert_eq!(rules[0].mode, DecomposeMode::Split);
+        assert_eq!(rules[0].field, "@xml:lang");
+        assert_eq!(rules[1].path_segment, "items");
+        assert_eq!(rules[1].mode, DecomposeMode::Group);
+        assert_eq!(rules[1].field, "@id");
+    }
+
+    #[test]
+    fn parse_decompose_spec_attribute_path_field_keeps_namespace_colon() {
+        let rules =
+            parse_decompose_spec("tag:group:child/@xml:lang,item:items:split:entry/@xml:lang");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].tag, "tag");
+        assert_eq!(rules[0].path_segment, "tag");
+        assert_eq!(rules[0].mode, DecomposeMode::Group);
+        assert_eq!(rules[0].field, "child/@xml:lang");
+        assert_eq!(rules[1].path_segment, "items");
+        assert_eq!(rules[1].mode, DecomposeMode::Split);
+        assert_eq!(rules[1].field, "entry/@xml:lang");
+    }
+
+    #[test]
+    fn parse_decompose_spec_chains_build_nested_children() {
+        let rules = parse_decompose_spec(
+            "profileSection:split:name>recordTypeVisibilities:group:recordType,\
+             profileSection:split:name>layouts:split:layout>items:split:@id",
+        );
+        assert_eq!(rules.len(), 1);
+        let parent = &rules[0];
+        assert_eq!(parent.tag, "profileSection");
+        assert_eq!(parent.children.len(), 2);
+        assert_eq!(parent.children[0].tag, "recordTypeVisibilities");
+        assert_eq!(parent.children[0].mode, DecomposeMode::Group);
+        assert_eq!(parent.children[1].tag, "layouts");
+        assert_eq!(parent.children[1].children[0].tag, "items");
+        assert_eq!(parent.children[1].children[0].field, "@id");
+    }
+
+    #[test]
+    fn parse_decompose_spec_drops_chain_with_invalid_link() {
+        assert!(parse_decompose_spec("a:split:f>bad").is_empty());
+    }
+
     #[test]
     fn parse_decompose_spec_rejects_empty_segments() {
         // Too few segments
@@ -359,26 +1028,58 @@ mod tests {
             "path/to/file.xml",
             "--postpurge",
             "--prepurge",
+            "--bare",
+            "--type-suffix-dirs",
             "--unique-id-elements=name,id",
             "--ignore-path=.foo",
             "--format=json",
             "--strategy=grouped-by-tag",
             "--multi-level=pattern:Root:ids",
             "--split-tags=a:split:b",
+            "--split-leaves=tabs,brand",
+            "--split-leaves-over=10",
+            "--sidecar=html:html",
+            "--output-dir=out",
+            "--include=**/*.permissionset-meta.xml",
+            "--exclude=legacy/**",
+            "--extensions=xml,xsd",
+            "--jobs=4",
+            "--dry-run",
+            "--sync",
+            "--hash-index",
+            "--strict",
+            "--max-depth=64",
+            "--max-document-size=1048576",
         ]
         .iter()
         .map(|s| sv(s))
         .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert_eq!(opts.path, Some("path/to/file.xml"));
         assert!(opts.pre_purge);
         assert!(opts.post_purge);
+        assert!(opts.bare);
+        assert!(opts.type_suffix_dirs);
         assert_eq!(opts.unique_id_elements, Some("name,id"));
         assert_eq!(opts.ignore_path, ".foo");
         assert_eq!(opts.format, "json");
         assert_eq!(opts.strategy, Some("grouped-by-tag"));
         assert_eq!(opts.multi_level.as_deref(), Some("pattern:Root:ids"));
         assert_eq!(opts.split_tags.as_deref(), Some("a:split:b"));
+        assert_eq!(opts.split_leaves.as_deref(), Some("tabs,brand"));
+        assert_eq!(opts.split_leaves_over, Some(10));
+        assert_eq!(opts.sidecars.as_deref(), Some("html:html"));
+        assert_eq!(opts.output_dir, Some("out"));
+        assert_eq!(opts.include, Some("**/*.permissionset-meta.xml"));
+        assert_eq!(opts.exclude, Some("legacy/**"));
+        assert_eq!(opts.extensions, Some("xml,xsd"));
+        assert_eq!(opts.jobs, Some(4));
+        assert!(opts.dry_run);
+        assert!(opts.sync);
+        assert!(opts.hash_index);
+        assert!(opts.strict);
+        assert_eq!(opts.max_depth, Some(64));
+        assert_eq!(opts.max_document_size, Some(1_048_576));
     }
 
     #[test]
@@ -397,11 +1098,29 @@ mod tests {
             "p:R:ids",
             "--split-tags",
             "t:split:f",
+            "--split-leaves",
+            "tabs",
+            "--split-leaves-over",
+            "5",
+            "--output-dir",
+            "build/parts",
+            "--include",
+            "a/*.xml",
+            "--exclude",
+            "b",
+            "--extensions",
+            "wsdl",
+            "--jobs",
+            "2",
+            "--max-parts",
+            "500",
+            "--max-filename-length",
+            "100",
         ]
         .iter()
         .map(|s| sv(s))
         .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert_eq!(opts.path, Some("file.xml"));
         assert_eq!(opts.unique_id_elements, Some("name"));
         assert_eq!(opts.ignore_path, ".gitignore");
@@ -409,6 +1128,90 @@ mod tests {
         assert_eq!(opts.strategy, Some("unique-id"));
         assert_eq!(opts.multi_level.as_deref(), Some("p:R:ids"));
         assert_eq!(opts.split_tags.as_deref(), Some("t:split:f"));
+        assert_eq!(opts.split_leaves.as_deref(), Some("tabs"));
+        assert_eq!(opts.split_leaves_over, Some(5));
+        assert_eq!(opts.output_dir, Some("build/parts"));
+        assert_eq!(opts.include, Some("a/*.xml"));
+        assert_eq!(opts.exclude, Some("b"));
+        assert_eq!(opts.extensions, Some("wsdl"));
+        assert_eq!(opts.jobs, Some(2));
+        assert_eq!(opts.max_parts, Some(500));
+        assert_eq!(opts.max_filename_length, Some(100));
+    }
+
+    #[test]
+    fn parse_sidecar_spec_reads_extension_and_min_len() {
+        let rules = parse_sidecar_spec("htmlContent:html, body:.bin:1024,bad,x::1").unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].tag, "htmlContent");
+        assert_eq!(rules[0].extension, "html");
+        assert_eq!(rules[0].min_len, 0);
+        assert_eq!(rules[1].tag, "body");
+        assert_eq!(rules[1].extension, "bin");
+        assert_eq!(rules[1].min_len, 1024);
+    }
+
+    #[test]
+    fn invalid_numbers_are_usage_errors() {
+        let err = parse_sidecar_spec("y:js:nan").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--sidecar min_len expects a non-negative number, got \"nan\""
+        );
+        for args in [
+            vec!["file.xml", "--split-leaves-over=ten"],
+            vec!["file.xml", "--split-leaves-over", "-1"],
+            vec!["file.xml", "--jobs=many"],
+            vec!["file.xml", "--max-depth", "deep"],
+            vec!["file.xml", "--max-parts=1e3"],
+        ] {
+            let args: Vec<String> = args.into_iter().map(sv).collect();
+            assert!(matches!(
+                parse_disassemble_args(&args),
+                Err(OptionsError::Invalid(_))
+            ));
+        }
+        let args = ["dir", "--jobs", "x"].map(sv);
+        assert!(parse_reassemble_args(&args).is_err());
+        assert!(run(["xml-disassembler", "disassemble", "f.xml", "--jobs=x"]
+            .map(sv)
+            .to_vec())
+        .is_err());
+    }
+
+    #[test]
+    fn parse_transform_spec_reads_each_action() {
+        let rules = parse_transform_spec(
+            "drop:perm/stamp, rename:label:title,replace:url:http:https://x,bad,drop:,rename:a",
+        );
+        assert_eq!(
+            rules,
+            vec![
+                TransformRule {
+                    selector: sv("perm/stamp"),
+                    action: RuleAction::Drop,
+                },
+                TransformRule {
+                    selector: sv("label"),
+                    action: RuleAction::Rename(sv("title")),
+                },
+                TransformRule {
+                    selector: sv("url"),
+                    action: RuleAction::Replace {
+                        from: sv("http"),
+                        to: sv("https://x"),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_leaf_split_trims_tags_and_keeps_threshold() {
+        let rule = parse_leaf_split(Some(" tabs , ,brand"), Some(3));
+        assert_eq!(rule.tags, vec![sv("tabs"), sv("brand")]);
+        assert_eq!(rule.threshold, Some(3));
+        assert!(parse_leaf_split(None, None).is_empty());
     }
 
     #[test]
@@ -417,23 +1220,62 @@ mod tests {
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert_eq!(opts.split_tags.as_deref(), Some("a:split:b"));
     }
 
+    #[test]
+    fn disassemble_options_reject_unknown_strategy_and_format() {
+        let args = ["file.xml", "--strategy=by-tag"]
+            .iter()
+            .map(|s| sv(s))
+            .collect::<Vec<_>>();
+        let err = disassemble_options(&parse_disassemble_args(&args).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown strategy \"by-tag\" (expected one of: unique-id, grouped-by-tag)"
+        );
+        let args = ["file.xml", "--format", "toml"]
+            .iter()
+            .map(|s| sv(s))
+            .collect::<Vec<_>>();
+        let err = disassemble_options(&parse_disassemble_args(&args).unwrap()).unwrap_err();
+        assert!(matches!(
+            err,
+            OptionsError::UnknownValue {
+                option: "format",
+                ..
+            }
+        ));
+
+        let args = [
+            "file.xml",
+            "--strategy",
+            "grouped-by-tag",
+            "-p",
+            "a:group:b",
+        ]
+        .iter()
+        .map(|s| sv(s))
+        .collect::<Vec<_>>();
+        let options = disassemble_options(&parse_disassemble_args(&args).unwrap()).unwrap();
+        assert_eq!(options.strategy(), Strategy::GroupedByTag);
+        assert_eq!(options.decompose_rules()[0].mode, DecomposeMode::Group);
+    }
+
     #[test]
     fn parse_disassemble_args_unknown_long_flag_is_skipped() {
         let args = ["file.xml", "--unknown"]
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert_eq!(opts.path, Some("file.xml"));
     }
 
     #[test]
     fn parse_disassemble_args_defaults_when_empty() {
-        let opts = parse_disassemble_args(&[]);
+        let opts = parse_disassemble_args(&[]).unwrap();
         assert!(opts.path.is_none());
         assert!(opts.strategy.is_none());
         assert!(opts.unique_id_elements.is_none());
@@ -449,7 +1291,7 @@ mod tests {
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert!(opts.unique_id_elements.is_none());
     }
 
@@ -459,64 +1301,89 @@ mod tests {
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let opts = parse_disassemble_args(&args);
+        let opts = parse_disassemble_args(&args).unwrap();
         assert_eq!(opts.path, Some("first.xml"));
     }
 
     #[test]
     fn parse_reassemble_args_picks_path_extension_and_flag() {
-        let args = ["some/dir", "json", "--postpurge"]
+        let args = ["some/dir", "json", "--postpurge", "--strict"]
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let (path, ext, purge) = parse_reassemble_args(&args);
-        assert_eq!(path, Some("some/dir"));
-        assert_eq!(ext, Some("json"));
-        assert!(purge);
+        let opts = parse_reassemble_args(&args).unwrap();
+        assert_eq!(opts.path, Some("some/dir"));
+        assert_eq!(opts.extension, Some("json"));
+        assert!(opts.post_purge);
+        assert!(opts.strict);
     }
 
     #[test]
     fn parse_reassemble_args_defaults_and_extra_args_ignored() {
-        let (p, e, purge) = parse_reassemble_args(&[]);
-        assert!(p.is_none());
-        assert!(e.is_none());
-        assert!(!purge);
+        let opts = parse_reassemble_args(&[]).unwrap();
+        assert!(opts.path.is_none());
+        assert!(opts.extension.is_none());
+        assert!(!opts.post_purge);
 
         let args = ["dir", "xml", "extra"]
             .iter()
             .map(|s| sv(s))
             .collect::<Vec<_>>();
-        let (p, e, _) = parse_reassemble_args(&args);
-        assert_eq!(p, Some("dir"));
-        assert_eq!(e, Some("xml"));
+        let opts = parse_reassemble_args(&args).unwrap();
+        assert_eq!(opts.path, Some("dir"));
+        assert_eq!(opts.extension, Some("xml"));
     }
 
-    #[tokio::test]
-    async fn run_no_args_prints_usage_and_succeeds() {
-        run(vec![sv("xml-disassembler")]).await.unwrap();
+    #[test]
+    fn parse_reassemble_args_output_options() {
+        let args = [
+            "dir",
+            "--output",
+            "out/file.xml",
+            "--output-dir=build",
+            "-r",
+            "--jobs",
+            "8",
+            "--dry-run",
+            "--transform=drop:stamp",
+        ]
+        .iter()
+        .map(|s| sv(s))
+        .collect::<Vec<_>>();
+        let opts = parse_reassemble_args(&args).unwrap();
+        assert_eq!(opts.transforms, Some("drop:stamp"));
+        assert_eq!(opts.path, Some("dir"));
+        assert!(opts.extension.is_none());
+        assert!(opts.recursive);
+        assert_eq!(opts.jobs, Some(8));
+        assert!(opts.dry_run);
+        assert_eq!(opts.output, Some("out/file.xml"));
+        assert_eq!(opts.output_dir, Some("build"));
     }
 
-    #[tokio::test]
-    async fn run_unknown_command_is_not_an_error() {
-        run(vec![sv("xml-disassembler"), sv("unknown")])
-            .await
-            .unwrap();
+    #[test]
+    fn run_no_args_prints_usage_and_succeeds() {
+        run(vec![sv("xml-disassembler")]).unwrap();
+    }
+
+    #[test]
+    fn run_unknown_command_is_not_an_error() {
+        run(vec![sv("xml-disassembler"), sv("unknown")]).unwrap();
     }
 
-    #[tokio::test]
-    async fn run_reassemble_missing_path_returns_err() {
+    #[test]
+    fn run_reassemble_missing_path_returns_err() {
         // Missing directory path propagates an error from fs::metadata.
         let err = run(vec![
             sv("xml-disassembler"),
             sv("reassemble"),
             sv("/definitely/not/here/xyz"),
-        ])
-        .await;
+        ]);
         assert!(err.is_err());
     }
 
-    #[tokio::test]
-    async fn run_disassemble_writes_expected_output() {
+    #[test]
+    fn run_disassemble_writes_expected_output() {
         let dir = tempfile::tempdir().unwrap();
         let xml_path = dir.path().join("sample.xml");
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -530,13 +1397,69 @@ mod tests {
             sv("disassemble"),
             xml_path.to_string_lossy().to_string(),
         ])
-        .await
         .unwrap();
         assert!(dir.path().join("sample").exists());
     }
 
-    #[tokio::test]
-    async fn run_disassemble_with_invalid_multi_level_spec_warns_and_continues() {
+    #[test]
+    fn run_check_fails_on_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml_path = dir.path().join("sample.xml");
+        std::fs::write(
+            &xml_path,
+            "<Root>\n    <child>\n        <name>one</name>\n    </child>\n</Root>\n",
+        )
+        .unwrap();
+        let check = |json: bool| {
+            let mut args = vec![
+                sv("xml-disassembler"),
+                sv("check"),
+                xml_path.to_string_lossy().to_string(),
+                sv("--unique-id-elements=name"),
+            ];
+            if json {
+                args.push(sv("--json"));
+            }
+            run(args)
+        };
+        assert!(check(false).is_err(), "nothing disassembled yet");
+        assert!(!dir.path().join("sample").exists());
+        run(vec![
+            sv("xml-disassembler"),
+            sv("disassemble"),
+            xml_path.to_string_lossy().to_string(),
+            sv("--unique-id-elements=name"),
+        ])
+        .unwrap();
+        check(false).unwrap();
+        check(true).unwrap();
+    }
+
+    #[test]
+    fn run_verify_fails_only_when_round_trip_differs() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml_path = dir.path().join("sample.xml");
+        // No declaration: the rebuilt file gains one, so only the byte comparison differs.
+        let xml = "<Root>\n    <child>\n        <name>one</name>\n    </child>\n    <child>\n        <name>two</name>\n    </child>\n</Root>\n";
+        std::fs::write(&xml_path, xml).unwrap();
+        let verify = |extra: &[&str]| {
+            let mut args = vec![
+                sv("xml-disassembler"),
+                sv("verify"),
+                xml_path.to_string_lossy().to_string(),
+            ];
+            args.extend(extra.iter().map(|s| sv(s)));
+            run(args)
+        };
+        verify(&[]).unwrap();
+        assert!(verify(&["--bytes"]).is_err());
+        assert_eq!(std::fs::read_to_string(&xml_path).unwrap(), xml);
+        assert!(!dir.path().join("sample").exists());
+        assert!(run(vec![sv("xml-disassembler"), sv("verify")]).is_err());
+    }
+
+    #[test]
+    fn run_disassemble_with_invalid_multi_level_spec_warns_and_continues() {
         let dir = tempfile::tempdir().unwrap();
         let xml_path = dir.path().join("sample.xml");
         let xml =
@@ -548,12 +1471,11 @@ mod tests {
             xml_path.to_string_lossy().to_string(),
             sv("--multi-level=bad-spec"),
         ])
-        .await
         .unwrap();
     }
 
-    #[tokio::test]
-    async fn run_reassemble_on_existing_directory_succeeds() {
+    #[test]
+    fn run_reassemble_on_existing_directory_succeeds() {
         // Disassemble then reassemble via the CLI to cover the success path end-to-end.
         let dir = tempfile::tempdir().unwrap();
         let xml_path = dir.path().join("reasm.xml");
@@ -565,7 +1487,6 @@ mod tests {
             sv("disassemble"),
             xml_path.to_string_lossy().to_string(),
         ])
-        .await
         .unwrap();
         let disassembled_dir = dir.path().join("reasm");
         assert!(disassembled_dir.exists());
@@ -574,12 +1495,11 @@ mod tests {
             sv("reassemble"),
             disassembled_dir.to_string_lossy().to_string(),
         ])
-        .await
         .unwrap();
     }
 
-    #[tokio::test]
-    async fn run_disassemble_with_grouped_by_tag_split_tags_runs() {
+    #[test]
+    fn run_disassemble_with_grouped_by_tag_split_tags_runs() {
         let dir = tempfile::tempdir().unwrap();
         let xml_path = dir.path().join("perms.xml");
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -596,7 +1516,6 @@ mod tests {
             sv("-p"),
             sv("objectPermissions:split:object"),
         ])
-        .await
         .unwrap();
     }
 }
@@ -0,0 +1,215 @@
+//! Errors of a disassembly or reassembly, including the reasons a file or directory is left out.
+
+use crate::cancel::Cancelled;
+use crate::limits::LimitError;
+use crate::options::OptionsError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Why a disassembly or reassembly failed, or why one of its files or directories was skipped.
+///
+/// The variants up to [`Error::NoRootToMerge`] are skips ([`Error::is_skip`]): a source file
+/// (disassembly) or directory (reassembly) that could not be processed. Each one is skipped and
+/// listed in the run's [`crate::RunReport`]; with the `strict` option it fails the run instead.
+/// The other variants always fail the run.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// The source file could not be read.
+    Unreadable { path: PathBuf, reason: String },
+    /// The source file is not well-formed XML.
+    InvalidXml { path: PathBuf },
+    /// The path does not have one of the configured XML extensions.
+    NotXml { path: PathBuf },
+    /// The document has no root element.
+    NoRoot { path: PathBuf },
+    /// The root element has no child elements.
+    EmptyRoot { path: PathBuf },
+    /// The document only has leaf elements, so there is nothing to split out.
+    LeafOnly { path: PathBuf },
+    /// The path is not a directory (reassembly), or neither a file nor a directory
+    /// (disassembly).
+    NotADirectory { path: PathBuf },
+    /// The directory only contains disassembled directories; reassemble it recursively.
+    NestedDisassemblies { path: PathBuf },
+    /// No part file under the directory could be parsed.
+    NoParts { path: PathBuf },
+    /// The parsed parts have no root element to merge into.
+    NoRootToMerge { path: PathBuf },
+    /// Reading or writing a file failed.
+    Io(Arc<io::Error>),
+    /// A source file exceeded one of its [`crate::Limits`].
+    Limit(LimitError),
+    /// The run was stopped through its [`crate::CancellationToken`].
+    Cancelled,
+    /// The options do not fit the input (e.g. two directories would be reassembled into the
+    /// same file).
+    Options(OptionsError),
+    /// Several of `total` concurrent tasks failed, each listed with its label.
+    Tasks {
+        total: usize,
+        failures: Vec<(String, Error)>,
+    },
+}
+
+impl Error {
+    /// Whether the file or directory is skipped rather than failing the run (outside strict
+    /// mode).
+    pub fn is_skip(&self) -> bool {
+        self.path().is_some()
+    }
+
+    /// The file or directory a skip is about; `None` for the errors that are not skips.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Unreadable { path, .. }
+            | Error::InvalidXml { path }
+            | Error::NotXml { path }
+            | Error::NoRoot { path }
+            | Error::EmptyRoot { path }
+            | Error::LeafOnly { path }
+            | Error::NotADirectory { path }
+            | Error::NestedDisassemblies { path }
+            | Error::NoParts { path }
+            | Error::NoRootToMerge { path } => Some(path),
+            Error::Io(_)
+            | Error::Limit(_)
+            | Error::Cancelled
+            | Error::Options(_)
+            | Error::Tasks { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unreadable { path, reason } => {
+                write!(f, "Could not read {}: {}", path.display(), reason)
+            }
+            Error::InvalidXml { path } => {
+                write!(f, "{} is not well-formed XML", path.display())
+            }
+            Error::NotXml { path } => write!(
+                f,
+                "The file path provided is not an XML file: {}",
+                path.display()
+            ),
+            Error::NoRoot { path } => {
+                write!(f, "The XML file {} has no root element", path.display())
+            }
+            Error::EmptyRoot { path } => write!(
+                f,
+                "The XML file {} has no elements under its root. This file will not be disassembled.",
+                path.display()
+            ),
+            Error::LeafOnly { path } => write!(
+                f,
+                "The XML file {} only has leaf elements. This file will not be disassembled.",
+                path.display()
+            ),
+            Error::NotADirectory { path } => write!(
+                f,
+                "The provided path is not a directory: {}",
+                path.display()
+            ),
+            Error::NestedDisassemblies { path } => write!(
+                f,
+                "{} contains disassembled directories but is not one itself; pass --recursive to reassemble each of them.",
+                path.display()
+            ),
+            Error::NoParts { path } => write!(
+                f,
+                "No files under {} were parsed successfully. A reassembled XML file was not created.",
+                path.display()
+            ),
+            Error::NoRootToMerge { path } => write!(
+                f,
+                "No usable root element found while merging files under {}. A reassembled XML file was not created.",
+                path.display()
+            ),
+            Error::Io(err) => err.fmt(f),
+            Error::Limit(err) => err.fmt(f),
+            Error::Cancelled => Cancelled.fmt(f),
+            Error::Options(err) => err.fmt(f),
+            Error::Tasks { total, failures } => {
+                write!(f, "{} of {} tasks failed:", failures.len(), total)?;
+                for (label, err) in failures {
+                    write!(f, "\n  {}: {}", label, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err.as_ref()),
+            Error::Limit(err) => Some(err),
+            Error::Options(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(Arc::new(err))
+    }
+}
+
+impl From<LimitError> for Error {
+    fn from(err: LimitError) -> Self {
+        Error::Limit(err)
+    }
+}
+
+impl From<Cancelled> for Error {
+    fn from(_: Cancelled) -> Self {
+        Error::Cancelled
+    }
+}
+
+impl From<OptionsError> for Error {
+    fn from(err: OptionsError) -> Self {
+        Error::Options(err)
+    }
+}
+
+/// Treat a skip ([`Error::is_skip`]) as success; for nested levels of a multi-level run, where a
+/// part that cannot be split further is simply kept as it is.
+pub(crate) fn allow_skip(result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(err) if err.is_skip() => {
+            log::debug!("{}", err);
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_skip_keeps_other_errors() {
+        let skip = Error::LeafOnly {
+            path: PathBuf::from("a.xml"),
+        };
+        assert!(allow_skip(Err(skip)).is_ok());
+        let disk_full = io::Error::new(io::ErrorKind::StorageFull, "disk full");
+        assert!(allow_skip(Err(disk_full.into())).is_err());
+        assert!(allow_skip(Err(Cancelled.into())).is_err());
+        assert_eq!(
+            Error::NoParts {
+                path: PathBuf::from("dir")
+            }
+            .path(),
+            Some(Path::new("dir"))
+        );
+    }
+}
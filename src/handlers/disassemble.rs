@@ -1,29 +1,172 @@
 //! Disassemble XML file handler.
 
 use crate::builders::build_disassembled_files_unified;
+use crate::cancel::CancellationToken;
+use crate::error::{allow_skip, Error};
 use crate::multi_level::{
     capture_xmlns_from_root, path_segment_from_file_pattern, save_multi_level_config,
     strip_root_and_build_xml,
 };
-use crate::parsers::parse_xml;
-use crate::types::{BuildDisassembledFilesOptions, DecomposeRule, MultiLevelRule};
-use crate::utils::normalize_path_unix;
+use crate::observer::{Event, Observer};
+use crate::options::{DisassembleOptions, OptionsError, Strategy};
+use crate::parsers::parse_xml_from_str;
+use crate::report::RunReport;
+use crate::sink::{OutputSink, PlannedChange};
+use crate::storage::{LocalStorage, Storage};
+use crate::types::{BuildDisassembledFilesOptions, MultiLevelRule, SourceMarker};
+use crate::utils::{
+    canonicalize_partial, collect_task_errors, is_disassembly_dir, is_plain_relative,
+    normalize_path_unix, purge_generated, read_manifest, relative_path, TaskQueue, HASH_INDEX_FILE,
+    MANIFEST_FILE,
+};
 use ignore::gitignore::GitignoreBuilder;
-use std::path::Path;
-use tokio::fs;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Read the `.hashes.json` of a disassembly directory, if present.
+fn read_hash_index(storage: &dyn Storage, dir: &Path) -> Option<HashMap<String, String>> {
+    let bytes = storage.read(&dir.join(HASH_INDEX_FILE)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
 
 pub struct DisassembleXmlFileHandler {
     ign: Option<ignore::gitignore::Gitignore>,
+    /// Where source files are read and output is written.
+    storage: Arc<dyn Storage>,
+    observer: Option<Arc<dyn Observer>>,
+    cancellation: CancellationToken,
+    /// Records every write and deletion of the current run (and skips them in dry-run mode).
+    sink: OutputSink,
+    /// Outcome of each source file of the last `disassemble` call.
+    report: Mutex<RunReport>,
+    /// Directory the current `disassemble` call started from; relative paths below it are
+    /// mirrored under the output directory.
+    input_root: PathBuf,
 }
 
 impl DisassembleXmlFileHandler {
     pub fn new() -> Self {
-        Self { ign: None }
+        Self {
+            ign: None,
+            storage: Arc::new(LocalStorage),
+            observer: None,
+            cancellation: CancellationToken::default(),
+            sink: OutputSink::new(false),
+            report: Mutex::new(RunReport::default()),
+            input_root: PathBuf::new(),
+        }
+    }
+
+    /// Read sources and write output through `storage` (e.g. a [`crate::MemoryStorage`])
+    /// instead of the local file system. `.gitignore` files are only honoured on local storage.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// The storage sources are read from and output is written to.
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Send an [`Event`] to `observer` for every file started, part written, multi-level rule
+    /// applied, and file disassembled, skipped or failed.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Stop runs once `token` is cancelled: the run fails with [`crate::Cancelled`] before its
+    /// next file or part and is rolled back, so the tree is left as it was.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// A fresh sink for a run with `options`.
+    fn new_sink(&self, options: &DisassembleOptions) -> OutputSink {
+        OutputSink::new(options.dry_run)
+            .with_hash_index(options.hash_index)
+            .with_storage(Arc::clone(&self.storage))
+            .with_observer(self.observer.clone())
+            .with_cancellation(self.cancellation.clone())
+    }
+
+    /// Changes made (or, in dry-run mode, planned) by the last `disassemble` call, sorted by path.
+    /// [`crate::ChangeCounts::from_changes`] turns them into created/updated/unchanged counts.
+    pub fn take_planned_changes(&self) -> Vec<PlannedChange> {
+        self.sink.take_changes()
+    }
+
+    /// Processed, ignored, skipped and failed source files of the last `disassemble` call. When
+    /// the call failed its changes were rolled back, so nothing is listed as processed: files that
+    /// had disassembled are listed as failed too.
+    pub fn take_report(&self) -> RunReport {
+        std::mem::take(&mut *self.report.lock().unwrap())
+    }
+
+    /// (source file, disassembly directory) pairs of the last `disassemble` call.
+    pub fn take_planned_outputs(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.sink.take_outputs()
+    }
+
+    /// Short output directory name: the file name up to its first dot (`Admin`).
+    fn short_dir_name(file_name: &str) -> &str {
+        file_name.split('.').next().unwrap_or(file_name)
+    }
+
+    /// Output directory name keeping the metadata-type suffix: the file name without `.xml`
+    /// and a trailing `-meta` (`Admin.permissionset-meta.xml` -> `Admin.permissionset`).
+    fn typed_dir_name(file_name: &str) -> &str {
+        let stem = if file_name.to_lowercase().ends_with(".xml") {
+            &file_name[..file_name.len() - 4]
+        } else {
+            file_name
+        };
+        stem.strip_suffix("-meta").unwrap_or(stem)
+    }
+
+    /// Short directory names claimed by more than one source file in `dir`.
+    fn colliding_dir_names(&self, dir: &Path, options: &DisassembleOptions) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut colliding = HashSet::new();
+        let Ok(entries) = self.storage.list(dir) else {
+            return colliding;
+        };
+        for path in entries {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !(self.storage.is_file(&path) && options.has_source_extension(&name)) {
+                continue;
+            }
+            let short = Self::short_dir_name(&name).to_string();
+            if !seen.insert(short.clone()) {
+                colliding.insert(short);
+            }
+        }
+        colliding
+    }
+
+    /// Parent directory for the disassembly of a file located in `dir_path`.
+    fn output_parent(&self, dir_path: &str, options: &DisassembleOptions) -> PathBuf {
+        match &options.output_dir {
+            Some(out) => {
+                let dir = self.storage.canonicalize(Path::new(dir_path));
+                let relative = dir.strip_prefix(&self.input_root).unwrap_or(Path::new(""));
+                out.join(relative)
+            }
+            None => PathBuf::from(dir_path),
+        }
     }
 
-    async fn load_ignore_rules(&mut self, ignore_path: &str) {
+    fn load_ignore_rules(&mut self, ignore_path: &str) {
         let path = Path::new(ignore_path);
-        let content = match fs::read_to_string(path).await {
+        let content = match self.storage.read_to_string(path) {
             Ok(c) => c,
             Err(_) => return,
         };
@@ -40,8 +183,97 @@ impl DisassembleXmlFileHandler {
         path.replace('\\', "/")
     }
 
-    fn is_xml_file(file_path: &str) -> bool {
-        file_path.to_lowercase().ends_with(".xml")
+    /// Walk `dir` recursively (honouring `.gitignore` and skipping hidden entries and earlier
+    /// disassembly output) and return the files to disassemble in sorted order.
+    fn collect_source_files(
+        &self,
+        dir: &Path,
+        options: &DisassembleOptions,
+    ) -> Result<Vec<PathBuf>, Error> {
+        // The builder already checked the globs.
+        let invalid = |err: ignore::Error| Error::Options(OptionsError::Invalid(err.to_string()));
+        let mut overrides = OverrideBuilder::new(dir);
+        for glob in &options.include {
+            overrides.add(glob).map_err(invalid)?;
+        }
+        for glob in &options.exclude {
+            overrides.add(&format!("!{}", glob)).map_err(invalid)?;
+        }
+        let overrides = overrides.build().map_err(invalid)?;
+        if !self.storage.is_local() {
+            return Ok(self.walk_storage(dir, &overrides, options));
+        }
+        let storage = Arc::clone(&self.storage);
+        let walker = WalkBuilder::new(dir)
+            .require_git(false)
+            .overrides(overrides)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !(is_dir && entry.depth() > 0 && is_disassembly_dir(storage.as_ref(), entry.path()))
+            })
+            .build();
+
+        let mut files = Vec::new();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    log::warn!("Skipping unreadable entry under {}: {}", dir.display(), err);
+                    continue;
+                }
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy();
+            if options.include.is_empty() && !options.has_source_extension(&name) {
+                continue;
+            }
+            files.push(entry.into_path());
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// [`Self::collect_source_files`] on storage other than the local file system: the same
+    /// filters apart from `.gitignore` files.
+    fn walk_storage(
+        &self,
+        dir: &Path,
+        overrides: &ignore::overrides::Override,
+        options: &DisassembleOptions,
+    ) -> Vec<PathBuf> {
+        let storage = self.storage.as_ref();
+        let mut files = Vec::new();
+        let mut stack = vec![dir.to_path_buf()];
+        while let Some(current) = stack.pop() {
+            let entries = match storage.list(&current) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    log::warn!("Skipping unreadable entry under {}: {}", dir.display(), err);
+                    continue;
+                }
+            };
+            for path in entries {
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let is_dir = storage.is_dir(&path);
+                if name.starts_with('.') || overrides.matched(&path, is_dir).is_ignore() {
+                    continue;
+                }
+                if is_dir {
+                    if !is_disassembly_dir(storage, &path) {
+                        stack.push(path);
+                    }
+                } else if !options.include.is_empty() || options.has_source_extension(&name) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        files
     }
 
     fn is_ignored(&self, path: &str) -> bool {
@@ -51,135 +283,105 @@ impl DisassembleXmlFileHandler {
             .unwrap_or(false)
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub async fn disassemble(
+    /// Disassemble the XML file, or every XML file below the directory, at `file_path`. Files
+    /// that cannot be disassembled are skipped (or, with [`DisassembleOptions::strict`], fail the
+    /// run); [`Self::take_report`] lists them.
+    pub fn disassemble(
         &mut self,
         file_path: &str,
-        unique_id_elements: Option<&str>,
-        strategy: Option<&str>,
-        pre_purge: bool,
-        post_purge: bool,
-        ignore_path: &str,
-        format: &str,
-        multi_level_rule: Option<&MultiLevelRule>,
-        decompose_rules: Option<&[DecomposeRule]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let strategy = strategy.unwrap_or("unique-id");
-        let strategy = if ["unique-id", "grouped-by-tag"].contains(&strategy) {
-            strategy
-        } else {
-            log::warn!(
-                "Unsupported strategy \"{}\", defaulting to \"unique-id\".",
-                strategy
-            );
-            "unique-id"
-        };
-
-        self.load_ignore_rules(ignore_path).await;
+        options: &DisassembleOptions,
+    ) -> Result<(), Error> {
+        self.load_ignore_rules(&options.ignore_path);
+        self.sink = self.new_sink(options);
+
+        // Every changed file is staged and only moved into place (and the sources post-purged)
+        // once every file disassembled; a failure leaves the tree untouched.
+        self.sink.begin();
+        let mut report = RunReport::default();
+        let result = self.disassemble_path(file_path, options, &mut report);
+        let result = self.sink.finish(result);
+        if result.is_err() {
+            report.roll_back();
+        }
+        report.sort();
+        *self.report.lock().unwrap() = report;
+        result
+    }
 
+    fn disassemble_path(
+        &mut self,
+        file_path: &str,
+        options: &DisassembleOptions,
+        report: &mut RunReport,
+    ) -> Result<(), Error> {
         let path = Path::new(file_path);
-        let meta = fs::metadata(path).await?;
+        let meta = self.storage.metadata(path)?;
         let cwd = std::env::current_dir().unwrap_or_else(|_| Path::new(".").to_path_buf());
         let relative_path = path.strip_prefix(&cwd).unwrap_or(path).to_string_lossy();
         let relative_path = Self::posix_path(&relative_path);
 
+        let input_dir = if meta.is_file() {
+            path.parent().unwrap_or(Path::new("."))
+        } else {
+            path
+        };
+        self.input_root = self.storage.canonicalize(input_dir);
+
         if meta.is_file() {
-            self.handle_file(
-                file_path,
-                &relative_path,
-                unique_id_elements,
-                strategy,
-                pre_purge,
-                post_purge,
-                format,
-                multi_level_rule,
-                decompose_rules,
-            )
-            .await?;
+            self.handle_file(file_path, &relative_path, options, report)
+        } else if meta.is_dir() {
+            self.handle_directory(file_path, options, report)
         } else {
-            // Anything that isn't a regular file is treated as a directory; fs::metadata on
-            // the caller already errored out if the path didn't exist.
-            self.handle_directory(
-                file_path,
-                unique_id_elements,
-                strategy,
-                pre_purge,
-                post_purge,
-                format,
-                multi_level_rule,
-                decompose_rules,
-            )
-            .await?;
+            let skip = Error::NotADirectory {
+                path: PathBuf::from(file_path),
+            };
+            report.record(file_path, Err(skip), options.strict, self.sink.observer())
         }
-
-        Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn handle_file(
+    fn handle_file(
         &self,
         file_path: &str,
         relative_path: &str,
-        unique_id_elements: Option<&str>,
-        strategy: &str,
-        pre_purge: bool,
-        post_purge: bool,
-        format: &str,
-        multi_level_rule: Option<&MultiLevelRule>,
-        decompose_rules: Option<&[DecomposeRule]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let resolved = Path::new(file_path)
-            .canonicalize()
-            .unwrap_or_else(|_| Path::new(file_path).to_path_buf());
+        options: &DisassembleOptions,
+        report: &mut RunReport,
+    ) -> Result<(), Error> {
+        let resolved = self.storage.canonicalize(Path::new(file_path));
         let resolved_str = normalize_path_unix(&resolved.to_string_lossy());
 
-        if !Self::is_xml_file(&resolved_str) {
-            log::error!(
-                "The file path provided is not an XML file: {}",
-                resolved_str
-            );
-            return Ok(());
+        if !options.has_source_extension(&resolved_str) {
+            let skip = Error::NotXml {
+                path: resolved.clone(),
+            };
+            return report.record(resolved, Err(skip), options.strict, self.sink.observer());
         }
 
         if self.is_ignored(relative_path) {
             log::warn!("File ignored by ignore rules: {}", resolved_str);
+            report.ignored.push(resolved);
             return Ok(());
         }
 
         let dir_path = resolved.parent().unwrap_or(Path::new("."));
         let dir_path_str = normalize_path_unix(&dir_path.to_string_lossy());
-        self.process_file(
-            &dir_path_str,
-            strategy,
-            &resolved_str,
-            unique_id_elements,
-            pre_purge,
-            post_purge,
-            format,
-            multi_level_rule,
-            decompose_rules,
-        )
-        .await
+        let collisions = self.colliding_dir_names(dir_path, options);
+        let result = self.process_file(&dir_path_str, &collisions, &resolved_str, options);
+        report.record(resolved_str, result, options.strict, self.sink.observer())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn handle_directory(
+    fn handle_directory(
         &self,
         dir_path: &str,
-        unique_id_elements: Option<&str>,
-        strategy: &str,
-        pre_purge: bool,
-        post_purge: bool,
-        format: &str,
-        multi_level_rule: Option<&MultiLevelRule>,
-        decompose_rules: Option<&[DecomposeRule]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        options: &DisassembleOptions,
+        report: &mut RunReport,
+    ) -> Result<(), Error> {
         let dir_path = normalize_path_unix(dir_path);
-        let mut entries = fs::read_dir(&dir_path).await?;
+        let files = self.collect_source_files(Path::new(&dir_path), options)?;
         let cwd = std::env::current_dir().unwrap_or_else(|_| Path::new(".").to_path_buf());
+        let mut collisions_by_dir: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+        let mut targets = Vec::new();
 
-        while let Some(entry) = entries.next_entry().await? {
-            let sub_path = entry.path();
+        for sub_path in files {
             let sub_file_path = sub_path.to_string_lossy();
             let relative_sub = sub_path
                 .strip_prefix(&cwd)
@@ -187,208 +389,427 @@ impl DisassembleXmlFileHandler {
                 .to_string_lossy();
             let relative_sub = Self::posix_path(&relative_sub);
 
-            if !(sub_path.is_file() && Self::is_xml_file(&sub_file_path)) {
-                continue;
-            }
             if self.is_ignored(&relative_sub) {
                 log::warn!("File ignored by ignore rules: {}", sub_file_path);
+                report.ignored.push(sub_path.clone());
                 continue;
             }
-            let sub_file_path_norm = normalize_path_unix(&sub_file_path);
-            self.process_file(
-                &dir_path,
-                strategy,
-                &sub_file_path_norm,
-                unique_id_elements,
-                pre_purge,
-                post_purge,
-                format,
-                multi_level_rule,
-                decompose_rules,
-            )
-            .await?;
+            let parent = sub_path
+                .parent()
+                .unwrap_or(Path::new(&dir_path))
+                .to_path_buf();
+            if !collisions_by_dir.contains_key(&parent) {
+                let collisions = self.colliding_dir_names(&parent, options);
+                collisions_by_dir.insert(parent.clone(), collisions);
+            }
+            targets.push((parent, normalize_path_unix(&sub_file_path)));
         }
-        Ok(())
+
+        // Files are independent (each has its own output directory), so they run concurrently;
+        // every failure is reported, not just the first one.
+        let queue = TaskQueue::new(options.jobs);
+        let collisions_by_dir = &collisions_by_dir;
+        let results = queue.run_all(targets.iter().map(|(parent, file)| {
+            move || {
+                let parent_norm = normalize_path_unix(&parent.to_string_lossy());
+                let result =
+                    self.process_file(&parent_norm, &collisions_by_dir[parent], file, options);
+                (file.clone(), result)
+            }
+        }));
+        let results = results
+            .into_iter()
+            .map(|(file, result)| {
+                let result = report.record(&file, result, options.strict, self.sink.observer());
+                (file, result)
+            })
+            .collect();
+        collect_task_errors(results)
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn process_file(
+    fn process_file(
         &self,
         dir_path: &str,
-        strategy: &str,
+        collisions: &HashSet<String>,
         file_path: &str,
-        unique_id_elements: Option<&str>,
-        pre_purge: bool,
-        post_purge: bool,
-        format: &str,
-        multi_level_rule: Option<&MultiLevelRule>,
-        decompose_rules: Option<&[DecomposeRule]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        options: &DisassembleOptions,
+    ) -> Result<(), Error> {
+        self.sink.check_cancelled()?;
         log::debug!("Parsing file to disassemble: {}", file_path);
+        self.sink.notify(|| Event::FileStarted {
+            path: PathBuf::from(file_path),
+        });
+        let multi_level_rule = options.multi_level.as_ref();
+        let decompose_rules =
+            (!options.decompose_rules.is_empty()).then_some(options.decompose_rules.as_slice());
 
         let file_name = Path::new(file_path)
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("output");
-        let base_name = file_name.split('.').next().unwrap_or(file_name);
-        let output_path = Path::new(dir_path).join(base_name);
+        let source_name = Path::new(file_path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or(file_name);
+        let short_name = Self::short_dir_name(file_name);
+        let typed_name = Self::typed_dir_name(source_name);
+        let collides = collisions.contains(short_name);
+        let use_typed = (options.type_suffix_dirs || collides) && typed_name != short_name;
+        if collides && use_typed {
+            log::warn!(
+                "Several files in {} would disassemble into {}/; using {}/ for {}",
+                dir_path,
+                short_name,
+                typed_name,
+                source_name
+            );
+        } else if collides {
+            log::warn!(
+                "{} has no type suffix to tell it apart from other files named {}; its output may be overwritten",
+                file_path,
+                short_name
+            );
+        }
+        let dir_name = if use_typed { typed_name } else { short_name };
+        let output_path = self.output_parent(dir_path, options).join(dir_name);
+
+        let sink = &self.sink;
+        sink.record_output(Path::new(file_path), &output_path);
+        if options.pre_purge {
+            // Only what an earlier disassembly generated; never a user folder of the same name.
+            purge_generated(sink, &output_path)?;
+        }
+        // Files already there; the multi-level manifest tells them apart from new output.
+        let existing = match multi_level_rule {
+            Some(_) => sink.files_under(&output_path),
+            None => Vec::new(),
+        };
+        let hash_index_path = output_path.join(HASH_INDEX_FILE);
+        let use_hash_index = options.hash_index && multi_level_rule.is_none();
+        if use_hash_index {
+            if let Some(hashes) = read_hash_index(sink.storage(), &output_path) {
+                sink.load_hashes(&output_path, &hashes);
+            }
+        } else if sink.is_file(&hash_index_path) {
+            // Would go stale once parts are written without it.
+            sink.remove_file(&hash_index_path)?;
+        }
 
-        if pre_purge && output_path.exists() {
-            fs::remove_dir_all(&output_path).await.ok();
+        if options.bare_parts && multi_level_rule.is_some() {
+            log::warn!(
+                "Bare part files are not supported with multi-level rules; writing wrapped parts for {}",
+                file_path
+            );
+        }
+        if !options.sidecars.is_empty() && multi_level_rule.is_some() {
+            log::warn!(
+                "Sidecar rules are not supported with multi-level rules; keeping content inline for {}",
+                file_path
+            );
+        }
+        if options.sync && multi_level_rule.is_some() {
+            log::warn!(
+                "Sync is not supported with multi-level rules; no files are removed for {}",
+                file_path
+            );
         }
 
         build_disassembled_files_unified(BuildDisassembledFilesOptions {
             file_path,
             disassembled_path: output_path.to_str().unwrap_or("."),
             base_name: file_name,
-            post_purge,
-            format,
-            unique_id_elements,
-            strategy,
+            post_purge: options.post_purge,
+            format: options.format,
+            unique_id_elements: options.unique_id_elements.as_deref(),
+            strategy: options.strategy,
             decompose_rules,
-        })
-        .await?;
+            leaf_split: options.leaf_split.as_ref(),
+            bare: options.bare_parts && multi_level_rule.is_none(),
+            sidecars: (!options.sidecars.is_empty() && multi_level_rule.is_none())
+                .then_some(options.sidecars.as_slice()),
+            jobs: options.jobs,
+            sink: Some(sink),
+            limits: Some(&options.limits),
+            transforms: &options.transforms,
+        })?;
+
+        if sink.has_writes_under(&output_path) {
+            let storage = self.storage.as_ref();
+            let source = storage.canonicalize(Path::new(file_path));
+            let output = canonicalize_partial(storage, &output_path);
+            let unix = |p: PathBuf| normalize_path_unix(&p.to_string_lossy());
+            let marker = SourceMarker {
+                file_name: source_name.to_string(),
+                source_path: relative_path(&self.input_root, &source).map(unix),
+                input_root: relative_path(&output, &self.input_root).map(unix),
+            };
+            let json = serde_json::to_string(&marker).unwrap_or_default();
+            sink.write(&output_path.join(".source.json"), json.as_bytes(), false)?;
+            if multi_level_rule.is_none() {
+                self.sync_manifest(&output_path, options.sync)?;
+            }
+            if use_hash_index {
+                let hashes: std::collections::BTreeMap<String, String> = sink
+                    .hashes_under(&output_path)
+                    .into_iter()
+                    .map(|(p, hash)| (normalize_path_unix(&p.to_string_lossy()), hash))
+                    .filter(|(p, _)| p != HASH_INDEX_FILE)
+                    .collect();
+                let json = serde_json::to_string(&hashes).unwrap_or_default();
+                sink.write(&hash_index_path, json.as_bytes(), false)?;
+            }
+        }
 
         if let Some(rule) = multi_level_rule {
-            self.recursively_disassemble_multi_level(&output_path, rule, format)
-                .await?;
+            self.recursively_disassemble_multi_level(&output_path, rule, options)?;
+            self.write_multi_level_manifest(&output_path, &existing)?;
+            sink.notify(|| Event::MultiLevelRuleApplied {
+                path: PathBuf::from(file_path),
+                rule: rule.clone(),
+            });
+        }
+
+        sink.notify(|| Event::FileDisassembled {
+            path: PathBuf::from(file_path),
+            output: output_path.clone(),
+        });
+        Ok(())
+    }
+
+    /// Write the manifest of a multi-level disassembly: every file now in the directory except
+    /// those that were there before the run (`existing`) without being generated by it or listed
+    /// in the previous manifest. Unlike [`Self::sync_manifest`] it lists the directory, since the
+    /// second level deletes the first-level parts it splits.
+    fn write_multi_level_manifest(
+        &self,
+        output_path: &Path,
+        existing: &[String],
+    ) -> Result<(), Error> {
+        let mut owned: HashSet<String> = read_manifest(self.storage.as_ref(), output_path)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        owned.extend(
+            self.sink
+                .written_under(output_path)
+                .iter()
+                .filter_map(|p| p.strip_prefix(output_path).ok())
+                .map(|p| normalize_path_unix(&p.to_string_lossy())),
+        );
+        let foreign: HashSet<&String> = existing.iter().filter(|f| !owned.contains(*f)).collect();
+        let generated: Vec<String> = self
+            .sink
+            .files_under(output_path)
+            .into_iter()
+            .filter(|f| f != MANIFEST_FILE && !foreign.contains(f))
+            .collect();
+        let json = serde_json::to_string(&generated).unwrap_or_else(|_| "[]".to_string());
+        self.sink
+            .write(&output_path.join(MANIFEST_FILE), json.as_bytes(), false)?;
+        Ok(())
+    }
+
+    /// Write the `.manifest.json` of `output_path` listing the files this run generated there.
+    /// Previously generated files this run did not produce are deleted in sync mode, and kept in
+    /// the manifest otherwise (so a later sync still removes them).
+    fn sync_manifest(&self, output_path: &Path, sync: bool) -> Result<(), Error> {
+        let mut generated: Vec<String> = self
+            .sink
+            .written_under(output_path)
+            .iter()
+            .filter_map(|p| p.strip_prefix(output_path).ok())
+            .map(|p| normalize_path_unix(&p.to_string_lossy()))
+            .filter(|p| p != MANIFEST_FILE)
+            .collect();
+        let previous = read_manifest(self.storage.as_ref(), output_path);
+        if sync && previous.is_none() {
+            log::warn!(
+                "No {} in {}; nothing is removed until a disassembly has recorded one",
+                MANIFEST_FILE,
+                output_path.display()
+            );
         }
 
+        let current: HashSet<String> = generated.iter().cloned().collect();
+        for orphan in previous.iter().flatten().filter(|p| !current.contains(*p)) {
+            // Only plain relative paths; never follow a manifest outside the directory.
+            if !is_plain_relative(orphan) {
+                continue;
+            }
+            let path = output_path.join(orphan);
+            if !self.sink.is_file(&path) {
+                continue;
+            }
+            if !sync {
+                generated.push(orphan.clone());
+                continue;
+            }
+            self.sink.remove_file(&path)?;
+            self.sink.prune_empty_parents(&path, output_path, false);
+        }
+        generated.sort();
+
+        let json = serde_json::to_string(&generated).unwrap_or_else(|_| "[]".to_string());
+        self.sink
+            .write(&output_path.join(MANIFEST_FILE), json.as_bytes(), false)?;
         Ok(())
     }
 
-    /// Recursively walk the disassembly output; for XML files matching the rule's file_pattern,
-    /// strip the root and re-disassemble with the rule's unique_id_elements.
-    async fn recursively_disassemble_multi_level(
+    /// For the XML parts the first level wrote under `dir_path` that match the rule's
+    /// file_pattern, strip the root and re-disassemble with the rule's unique_id_elements. The
+    /// second level goes through the run's sink like the first.
+    fn recursively_disassemble_multi_level(
         &self,
         dir_path: &Path,
         rule: &MultiLevelRule,
-        format: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut config = crate::multi_level::load_multi_level_config(dir_path)
-            .await
+        options: &DisassembleOptions,
+    ) -> Result<(), Error> {
+        let sink = &self.sink;
+        let storage = self.storage.as_ref();
+        // A config removed by the pre-purge no longer counts.
+        let mut config = sink
+            .is_file(&dir_path.join(".multi_level.json"))
+            .then(|| crate::multi_level::load_multi_level_config(storage, dir_path))
+            .flatten()
             .unwrap_or_default();
 
-        let mut stack = vec![dir_path.to_path_buf()];
-        while let Some(current) = stack.pop() {
-            let mut entries = Vec::new();
-            let mut read_dir = fs::read_dir(&current).await?;
-            while let Some(entry) = read_dir.next_entry().await? {
-                entries.push(entry);
+        for path in sink.written_under(dir_path) {
+            let path_str = path.to_string_lossy().to_string();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !name.ends_with(".xml")
+                || (!name.contains(&rule.file_pattern) && !path_str.contains(&rule.file_pattern))
+            {
+                continue;
             }
 
-            for entry in entries {
-                let path = entry.path();
-                let path_str = path.to_string_lossy().to_string();
-
-                if path.is_dir() {
-                    stack.push(path);
-                    continue;
-                }
-                // Anything not a directory is processed as a regular file below.
-                {
-                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                    let path_str_check = path.to_string_lossy();
-                    if !name.ends_with(".xml")
-                        || (!name.contains(&rule.file_pattern)
-                            && !path_str_check.contains(&rule.file_pattern))
-                    {
-                        continue;
-                    }
+            let Ok(content) = sink.read_to_string(&path) else {
+                continue;
+            };
+            let parsed = match parse_xml_from_str(&content, &path_str) {
+                Some(p) => p,
+                None => continue,
+            };
+            let has_element_to_strip = parsed
+                .as_object()
+                .and_then(|o| {
+                    let root_key = o.keys().find(|k| *k != "?xml")?;
+                    let root_val = o.get(root_key)?.as_object()?;
+                    Some(
+                        root_key == &rule.root_to_strip
+                            || root_val.contains_key(&rule.root_to_strip),
+                    )
+                })
+                .unwrap_or(false);
+            if !has_element_to_strip {
+                continue;
+            }
 
-                    let parsed = match parse_xml(&path_str).await {
-                        Some(p) => p,
-                        None => continue,
-                    };
-                    let has_element_to_strip = parsed
+            let wrap_xmlns = capture_xmlns_from_root(&parsed).unwrap_or_default();
+
+            let stripped_xml = match strip_root_and_build_xml(&parsed, &rule.root_to_strip) {
+                Some(xml) => xml,
+                None => continue,
+            };
+
+            sink.write(&path, stripped_xml.as_bytes(), false)?;
+
+            let file_stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output");
+            let output_dir_name = file_stem.split('.').next().unwrap_or(file_stem);
+            let parent = path.parent().unwrap_or(dir_path);
+            let second_level_output = parent.join(output_dir_name);
+
+            // A part that cannot be split further (e.g. only leaves) stays as it is.
+            allow_skip(build_disassembled_files_unified(
+                BuildDisassembledFilesOptions {
+                    file_path: &path_str,
+                    disassembled_path: second_level_output.to_str().unwrap_or("."),
+                    base_name: output_dir_name,
+                    post_purge: true,
+                    format: options.format,
+                    unique_id_elements: Some(&rule.unique_id_elements),
+                    strategy: Strategy::UniqueId,
+                    decompose_rules: None,
+                    leaf_split: None,
+                    bare: false,
+                    sidecars: None,
+                    jobs: options.jobs,
+                    sink: Some(sink),
+                    limits: Some(&options.limits),
+                    // The first level already ran the transforms.
+                    transforms: &[],
+                },
+            ))?;
+
+            match config.rules.first_mut() {
+                None => {
+                    let wrap_root = parsed
                         .as_object()
-                        .and_then(|o| {
-                            let root_key = o.keys().find(|k| *k != "?xml")?;
-                            let root_val = o.get(root_key)?.as_object()?;
-                            Some(
-                                root_key == &rule.root_to_strip
-                                    || root_val.contains_key(&rule.root_to_strip),
-                            )
-                        })
-                        .unwrap_or(false);
-                    if !has_element_to_strip {
-                        continue;
-                    }
-
-                    let wrap_xmlns = capture_xmlns_from_root(&parsed).unwrap_or_default();
-
-                    let stripped_xml = match strip_root_and_build_xml(&parsed, &rule.root_to_strip)
-                    {
-                        Some(xml) => xml,
-                        None => continue,
+                        .and_then(|o| o.keys().find(|k| *k != "?xml").cloned())
+                        .unwrap_or_else(|| rule.wrap_root_element.clone());
+                    let path_segment = if rule.path_segment.is_empty() {
+                        path_segment_from_file_pattern(&rule.file_pattern)
+                    } else {
+                        rule.path_segment.clone()
                     };
-
-                    fs::write(&path, stripped_xml).await?;
-
-                    let file_stem = path
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("output");
-                    let output_dir_name = file_stem.split('.').next().unwrap_or(file_stem);
-                    let parent = path.parent().unwrap_or(dir_path);
-                    let second_level_output = parent.join(output_dir_name);
-
-                    build_disassembled_files_unified(BuildDisassembledFilesOptions {
-                        file_path: &path_str,
-                        disassembled_path: second_level_output.to_str().unwrap_or("."),
-                        base_name: output_dir_name,
-                        post_purge: true,
-                        format,
-                        unique_id_elements: Some(&rule.unique_id_elements),
-                        strategy: "unique-id",
-                        decompose_rules: None,
-                    })
-                    .await?;
-
-                    match config.rules.first_mut() {
-                        None => {
-                            let wrap_root = parsed
-                                .as_object()
-                                .and_then(|o| o.keys().find(|k| *k != "?xml").cloned())
-                                .unwrap_or_else(|| rule.wrap_root_element.clone());
-                            let path_segment = if rule.path_segment.is_empty() {
-                                path_segment_from_file_pattern(&rule.file_pattern)
-                            } else {
-                                rule.path_segment.clone()
-                            };
-                            let stored_xmlns = if rule.wrap_xmlns.is_empty() {
-                                wrap_xmlns
-                            } else {
-                                rule.wrap_xmlns.clone()
-                            };
-                            config.rules.push(MultiLevelRule {
-                                file_pattern: rule.file_pattern.clone(),
-                                root_to_strip: rule.root_to_strip.clone(),
-                                unique_id_elements: rule.unique_id_elements.clone(),
-                                path_segment,
-                                // Persist document root (e.g. LoyaltyProgramSetup) so reassembly uses it
-                                // as root with xmlns; path_segment is the inner wrapper in each file.
-                                wrap_root_element: wrap_root,
-                                wrap_xmlns: stored_xmlns,
-                            });
-                        }
-                        Some(r) if r.wrap_xmlns.is_empty() => {
-                            r.wrap_xmlns = wrap_xmlns;
-                        }
-                        Some(_) => {}
-                    }
+                    let stored_xmlns = if rule.wrap_xmlns.is_empty() {
+                        wrap_xmlns
+                    } else {
+                        rule.wrap_xmlns.clone()
+                    };
+                    config.rules.push(MultiLevelRule {
+                        file_pattern: rule.file_pattern.clone(),
+                        root_to_strip: rule.root_to_strip.clone(),
+                        unique_id_elements: rule.unique_id_elements.clone(),
+                        path_segment,
+                        // Persist document root (e.g. LoyaltyProgramSetup) so reassembly uses it
+                        // as root with xmlns; path_segment is the inner wrapper in each file.
+                        wrap_root_element: wrap_root,
+                        wrap_xmlns: stored_xmlns,
+                    });
+                }
+                Some(r) if r.wrap_xmlns.is_empty() => {
+                    r.wrap_xmlns = wrap_xmlns;
                 }
+                Some(_) => {}
             }
         }
 
         if !config.rules.is_empty() {
-            save_multi_level_config(dir_path, &config).await?;
+            save_multi_level_config(Some(sink), dir_path, &config)?;
         }
 
         Ok(())
     }
 }
 
+#[cfg(feature = "async")]
+impl DisassembleXmlFileHandler {
+    /// [`Self::disassemble`] on tokio's blocking thread pool, for async callers. The handler moves
+    /// to the pool for the run and is put back afterwards; if the run panics or the future is
+    /// dropped first, it is left as [`Self::new`]. Dropping the future does not stop the run; use
+    /// [`Self::with_cancellation`] for that.
+    pub async fn disassemble_async(
+        &mut self,
+        file_path: &str,
+        options: &DisassembleOptions,
+    ) -> Result<(), Error> {
+        let mut handler = std::mem::take(self);
+        let file_path = file_path.to_string();
+        let options = options.clone();
+        let (handler, result) = tokio::task::spawn_blocking(move || {
+            let result = handler.disassemble(&file_path, &options);
+            (handler, result)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        *self = handler;
+        result
+    }
+}
+
 impl Default for DisassembleXmlFileHandler {
     fn default() -> Self {
         Self::new()
@@ -406,10 +827,32 @@ mod tests {
     }
 
     #[test]
-    fn is_xml_file_matches_case_insensitively() {
-        assert!(DisassembleXmlFileHandler::is_xml_file("foo.xml"));
-        assert!(DisassembleXmlFileHandler::is_xml_file("BAR.XML"));
-        assert!(!DisassembleXmlFileHandler::is_xml_file("foo.txt"));
+    fn has_source_extension_matches_case_insensitively() {
+        let options = DisassembleOptions::default();
+        assert!(options.has_source_extension("foo.xml"));
+        assert!(options.has_source_extension("BAR.XML"));
+        assert!(!options.has_source_extension("foo.txt"));
+        assert!(!options.has_source_extension("schema.xsd"));
+
+        let options = DisassembleOptions::builder()
+            .extensions(vec![".xsd".to_string(), "Config".to_string()])
+            .build()
+            .unwrap();
+        assert!(options.has_source_extension("schema.xsd"));
+        assert!(options.has_source_extension("app.config"));
+        assert!(!options.has_source_extension("foo.xml"));
+    }
+
+    #[test]
+    fn dir_names_short_and_typed() {
+        type H = DisassembleXmlFileHandler;
+        assert_eq!(H::short_dir_name("Admin.permissionset-meta"), "Admin");
+        assert_eq!(
+            H::typed_dir_name("Admin.permissionset-meta.xml"),
+            "Admin.permissionset"
+        );
+        assert_eq!(H::typed_dir_name("Admin.profile-meta.XML"), "Admin.profile");
+        assert_eq!(H::typed_dir_name("notes.xml"), "notes");
     }
 
     #[test]
@@ -420,22 +863,20 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn load_ignore_rules_noop_when_path_missing() {
+    #[test]
+    fn load_ignore_rules_noop_when_path_missing() {
         let mut handler = DisassembleXmlFileHandler::new();
-        handler
-            .load_ignore_rules("/definitely/does/not/exist/.ignore")
-            .await;
+        handler.load_ignore_rules("/definitely/does/not/exist/.ignore");
         assert!(handler.ign.is_none());
     }
 
-    #[tokio::test]
-    async fn load_ignore_rules_builds_matcher() {
+    #[test]
+    fn load_ignore_rules_builds_matcher() {
         let temp = tempfile::tempdir().unwrap();
         let path = temp.path().join(".ignore");
-        tokio::fs::write(&path, "*.xml\n").await.unwrap();
+        std::fs::write(&path, "*.xml\n").unwrap();
         let mut handler = DisassembleXmlFileHandler::new();
-        handler.load_ignore_rules(path.to_str().unwrap()).await;
+        handler.load_ignore_rules(path.to_str().unwrap());
         assert!(handler.ign.is_some());
         assert!(handler.is_ignored("file.xml"));
         assert!(!handler.is_ignored("file.txt"));
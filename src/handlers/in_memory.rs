@@ -0,0 +1,97 @@
+//! Disassemble and reassemble documents held in memory, without touching the file system.
+
+use crate::error::Error;
+use crate::handlers::{DisassembleXmlFileHandler, ReassembleXmlFileHandler};
+use crate::options::{DisassembleOptions, ReassembleOptions};
+use crate::report::RunReport;
+use crate::storage::{MemoryStorage, Storage};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Name the document gets in memory; its disassembly directory is `document/`.
+const DOCUMENT: &str = "document.xml";
+const DISASSEMBLY_DIR: &str = "document";
+
+/// The first skipped file or directory of a run, as an error.
+fn skip_error(report: RunReport) -> Result<(), Error> {
+    match report.skipped.into_iter().next() {
+        Some((_, skip)) => Err(skip),
+        None => Ok(()),
+    }
+}
+
+/// Disassemble the XML document `xml` in memory. Returns every generated file (parts and the
+/// hidden marker files reassembly needs) keyed by its path relative to the disassembly
+/// directory. A document that cannot be disassembled is an [`crate::Error`]; the ignore file,
+/// purging, the output directory and dry-run mode do not apply.
+pub fn disassemble_str(
+    xml: &str,
+    options: &DisassembleOptions,
+) -> Result<BTreeMap<PathBuf, String>, Error> {
+    let storage = Arc::new(MemoryStorage::new());
+    storage.insert(DOCUMENT, xml)?;
+    let options = DisassembleOptions {
+        pre_purge: false,
+        post_purge: false,
+        output_dir: None,
+        dry_run: false,
+        ..options.clone()
+    };
+    let mut handler = DisassembleXmlFileHandler::new().with_storage(storage.clone());
+    handler.disassemble(DOCUMENT, &options)?;
+    skip_error(handler.take_report())?;
+
+    storage
+        .files_under(DISASSEMBLY_DIR)
+        .into_iter()
+        .map(|(path, bytes)| match String::from_utf8(bytes) {
+            Ok(text) => Ok((path, text)),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} is not UTF-8 text", path.display()),
+            )
+            .into()),
+        })
+        .collect()
+}
+
+/// Reassemble the files of a disassembly directory (as returned by [`disassemble_str`]: paths
+/// relative to the directory) in memory and return the XML document. A set of files that cannot
+/// be reassembled is an [`crate::Error`]. The output path, recursion and dry-run mode do not apply.
+pub fn reassemble_from_map(
+    files: &BTreeMap<PathBuf, String>,
+    options: &ReassembleOptions,
+) -> Result<String, Error> {
+    let storage = Arc::new(MemoryStorage::new());
+    storage.create_dir_all(Path::new(DISASSEMBLY_DIR))?;
+    for (path, contents) in files {
+        storage.insert(Path::new(DISASSEMBLY_DIR).join(path), contents.as_str())?;
+    }
+    let options = ReassembleOptions {
+        output: Some(PathBuf::from(DOCUMENT)),
+        output_dir: None,
+        dry_run: false,
+        recursive: false,
+        ..options.clone()
+    };
+    let mut handler = ReassembleXmlFileHandler::new().with_storage(storage.clone());
+    handler.reassemble(DISASSEMBLY_DIR, &options)?;
+    skip_error(handler.take_report())?;
+    Ok(storage.read_to_string(Path::new(DOCUMENT))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn leaf_only_document_is_an_error() {
+        let err =
+            disassemble_str("<Root><a>1</a></Root>", &DisassembleOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::LeafOnly { .. }));
+        let err = reassemble_from_map(&BTreeMap::new(), &ReassembleOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::NoParts { .. }));
+    }
+}
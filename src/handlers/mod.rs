@@ -1,5 +1,7 @@
 mod disassemble;
+mod in_memory;
 mod reassemble;
 
 pub use disassemble::DisassembleXmlFileHandler;
+pub use in_memory::{disassemble_str, reassemble_from_map};
 pub use reassemble::ReassembleXmlFileHandler;
@@ -1,22 +1,105 @@
 //! Reassemble XML from disassembled directory.
 
 use crate::builders::{build_xml_string, merge_xml_elements, reorder_root_keys};
+use crate::cancel::CancellationToken;
+use crate::error::{allow_skip, Error};
 use crate::multi_level::{ensure_segment_files_structure, load_multi_level_config};
-use crate::parsers::parse_to_xml_object;
-use crate::types::XmlElement;
-use crate::utils::normalize_path_unix;
+use crate::observer::{Event, Observer};
+use crate::options::{OptionsError, ReassembleOptions};
+use crate::parsers::{parse_bare_part_to_xml_object, parse_to_xml_object};
+use crate::report::RunReport;
+use crate::sidecar::{inline_sidecars, is_sidecar_file, sidecar_references};
+use crate::sink::{self, OutputSink, PlannedChange};
+use crate::storage::{LocalStorage, Storage};
+use crate::transform::{apply_transforms, ElementTransform};
+use crate::types::{NestedItemMarker, RootWrapper, SourceMarker, XmlElement};
+use crate::utils::{
+    collect_task_errors, is_disassembly_dir, normalize_path_unix, purge_generated, TaskQueue,
+};
 use serde_json::Value;
-use std::future::Future;
-use std::path::Path;
-use std::pin::Pin;
-use tokio::fs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 /// Read a `.key_order.json` file (if present) and parse it as a list of root key names.
-async fn read_key_order(path: &Path) -> Option<Vec<String>> {
-    let bytes = fs::read(path).await.ok()?;
+fn read_key_order(storage: &dyn Storage, path: &Path) -> Option<Vec<String>> {
+    let bytes = storage.read(path).ok()?;
     serde_json::from_slice::<Vec<String>>(&bytes).ok()
 }
 
+/// Read the `.nested_item.json` marker of a decomposed split item directory, if present.
+fn read_nested_item_marker(storage: &dyn Storage, dir: &Path) -> Option<NestedItemMarker> {
+    let bytes = storage.read(&dir.join(".nested_item.json")).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Read the `.source.json` marker recording the file a directory was disassembled from, if present.
+fn read_source_marker(storage: &dyn Storage, dir: &Path) -> Option<SourceMarker> {
+    let bytes = storage.read(&dir.join(".source.json")).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Original file name up to its first dot, which names the disassembly directory.
+fn short_name(marker: &SourceMarker) -> &str {
+    marker.file_name.split('.').next().unwrap_or_default()
+}
+
+/// Disassembled directories under `root` (or `root` itself), in sorted order. Hidden directories
+/// are skipped and the search does not descend into a disassembled directory.
+fn find_disassembly_dirs(storage: &dyn Storage, root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut found = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if is_disassembly_dir(storage, &dir) {
+            found.push(dir);
+            continue;
+        }
+        for path in storage.list(&dir)? {
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if !hidden && storage.is_dir(&path) {
+                stack.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Whether any direct subdirectory of `dir` is a disassembled directory.
+fn has_disassembly_subdir(storage: &dyn Storage, dir: &Path) -> bool {
+    let Ok(entries) = storage.list(dir) else {
+        return false;
+    };
+    entries
+        .iter()
+        .any(|path| storage.is_dir(path) && is_disassembly_dir(storage, path))
+}
+
+/// Read the `.root.json` wrapper of a disassembly written with bare part files, if present.
+fn read_root_wrapper(storage: &dyn Storage, dir: &Path) -> Option<RootWrapper> {
+    let bytes = storage.read(&dir.join(".root.json")).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Put the stored root attributes and declaration back on a merged bare-part document.
+fn apply_root_wrapper(merged: XmlElement, wrapper: &RootWrapper) -> XmlElement {
+    let mut children = merged
+        .get(&wrapper.name)
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let mut root = wrapper.attributes.clone();
+    root.append(&mut children);
+    let mut top = serde_json::Map::new();
+    if let Some(decl) = wrapper.declaration.clone() {
+        top.insert("?xml".to_string(), decl);
+    }
+    top.insert(wrapper.name.clone(), Value::Object(root));
+    Value::Object(top)
+}
+
 /// Remove @xmlns from an object so the reassembled segment wrapper (e.g. programProcesses) has no xmlns.
 fn strip_xmlns_from_value(v: Value) -> Value {
     match v {
@@ -27,103 +110,258 @@ fn strip_xmlns_from_value(v: Value) -> Value {
     }
 }
 
-type ProcessDirFuture<'a> = Pin<
-    Box<
-        dyn Future<Output = Result<Vec<XmlElement>, Box<dyn std::error::Error + Send + Sync>>>
-            + Send
-            + 'a,
-    >,
->;
-
-pub struct ReassembleXmlFileHandler;
+pub struct ReassembleXmlFileHandler {
+    /// Where parts are read and the reassembled files written.
+    storage: Arc<dyn Storage>,
+    observer: Option<Arc<dyn Observer>>,
+    cancellation: CancellationToken,
+    sink: OutputSink,
+    /// Outcome of each directory of the last `reassemble` call.
+    report: Mutex<RunReport>,
+}
 
 impl ReassembleXmlFileHandler {
     pub fn new() -> Self {
-        Self
+        Self {
+            storage: Arc::new(LocalStorage),
+            observer: None,
+            cancellation: CancellationToken::default(),
+            sink: OutputSink::new(false),
+            report: Mutex::new(RunReport::default()),
+        }
+    }
+
+    /// Read parts and write the reassembled files through `storage` (e.g. a
+    /// [`crate::MemoryStorage`]) instead of the local file system.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Send an [`Event`] to `observer` for every directory started, and reassembled, skipped or
+    /// failed.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Stop runs once `token` is cancelled: the run fails with [`crate::Cancelled`] before its
+    /// next directory or directory of parts and is rolled back, so the tree is left as it was.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// A fresh sink for a run with `options`.
+    fn new_sink(&self, options: &ReassembleOptions) -> OutputSink {
+        OutputSink::new(options.dry_run)
+            .with_storage(Arc::clone(&self.storage))
+            .with_observer(self.observer.clone())
+            .with_cancellation(self.cancellation.clone())
+    }
+
+    /// Changes made (or, in dry-run mode, planned) by the last `reassemble` call, sorted by path.
+    pub fn take_planned_changes(&self) -> Vec<PlannedChange> {
+        self.sink.take_changes()
+    }
+
+    /// Reassembled, skipped and failed directories of the last `reassemble` call. When the call
+    /// failed its changes were rolled back, so nothing is listed as processed: directories that
+    /// had reassembled are listed as failed too.
+    pub fn take_report(&self) -> RunReport {
+        std::mem::take(&mut *self.report.lock().unwrap())
+    }
+
+    /// Reassemble the directory at `file_path`. Without an extension in `options`, the original
+    /// file name recorded in `.source.json` is used when present, otherwise `{directory}.xml`.
+    /// Directories that cannot be reassembled are skipped (or, with
+    /// [`ReassembleOptions::strict`], fail the run); [`Self::take_report`] lists them.
+    pub fn reassemble(
+        &mut self,
+        file_path: &str,
+        options: &ReassembleOptions,
+    ) -> Result<(), Error> {
+        self.sink = self.new_sink(options);
+        // Outputs are staged and only moved into place (and the directories post-purged) once
+        // every directory reassembled; a failure leaves the tree untouched.
+        self.sink.begin();
+        let mut report = RunReport::default();
+        let result = self.reassemble_path(file_path, options, &mut report);
+        let result = self.sink.finish(result);
+        if result.is_err() {
+            report.roll_back();
+        }
+        report.sort();
+        *self.report.lock().unwrap() = report;
+        result
     }
 
-    pub async fn reassemble(
+    fn reassemble_path(
         &self,
         file_path: &str,
-        file_extension: Option<&str>,
-        post_purge: bool,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        options: &ReassembleOptions,
+        report: &mut RunReport,
+    ) -> Result<(), Error> {
         let file_path = normalize_path_unix(file_path);
-        if !self.validate_directory(&file_path).await? {
-            return Ok(());
+        if !self.storage.metadata(Path::new(&file_path))?.is_dir() {
+            let skip = Error::NotADirectory {
+                path: PathBuf::from(&file_path),
+            };
+            return report.record(&file_path, Err(skip), options.strict, self.sink.observer());
         }
+        let root = Path::new(&file_path);
 
+        if options.recursive {
+            let dirs = find_disassembly_dirs(self.storage.as_ref(), root)?;
+            if dirs.is_empty() {
+                log::warn!("No disassembled directories found under {}", file_path);
+            }
+            self.check_distinct_outputs(&dirs, root, options)?;
+            let queue = TaskQueue::new(options.jobs);
+            let results = queue.run_all(dirs.iter().map(|dir| {
+                move || {
+                    let relative = dir
+                        .parent()
+                        .and_then(|p| p.strip_prefix(root).ok())
+                        .unwrap_or(Path::new(""));
+                    let dir_str = normalize_path_unix(&dir.to_string_lossy());
+                    let result = self.reassemble_dir(&dir_str, options, relative);
+                    (dir_str, result)
+                }
+            }));
+            let results = results
+                .into_iter()
+                .map(|(dir, result)| {
+                    let result = report.record(&dir, result, options.strict, self.sink.observer());
+                    (dir, result)
+                })
+                .collect();
+            return collect_task_errors(results);
+        }
+
+        let storage = self.storage.as_ref();
+        let result = if !is_disassembly_dir(storage, root) && has_disassembly_subdir(storage, root)
+        {
+            Err(Error::NestedDisassemblies {
+                path: root.to_path_buf(),
+            })
+        } else {
+            self.reassemble_dir(&file_path, options, Path::new(""))
+        };
+        report.record(&file_path, result, options.strict, self.sink.observer())
+    }
+
+    /// Reassemble one disassembled directory; `relative` is its parent relative to the root
+    /// passed to `reassemble` (mirrored under the output directory).
+    fn reassemble_dir(
+        &self,
+        file_path: &str,
+        options: &ReassembleOptions,
+        relative: &Path,
+    ) -> Result<(), Error> {
+        self.sink.check_cancelled()?;
+        let file_path = file_path.to_string();
         let path = Path::new(&file_path);
-        let config = load_multi_level_config(path).await;
+        self.sink.notify(|| Event::FileStarted {
+            path: path.to_path_buf(),
+        });
+        let config = load_multi_level_config(self.storage.as_ref(), path);
+        // Multi-level reassembly rewrites the directory while merging it, so it works on a
+        // scratch copy (also in dry-run mode); the directory itself is only changed by the purge
+        // below.
+        let work_path = if config.is_some() {
+            self.sink.scratch_copy(path)?
+        } else {
+            path.to_path_buf()
+        };
+        let work_dir = normalize_path_unix(&work_path.to_string_lossy());
         if let Some(ref config) = config {
             for rule in &config.rules {
-                let segment_path = path.join(&rule.path_segment);
-                self.reassemble_multi_level_segment(&segment_path, rule)
-                    .await?;
+                let segment_path = work_path.join(&rule.path_segment);
+                self.reassemble_multi_level_segment(&segment_path, rule, options.jobs)?;
             }
         }
 
         let base_segment = config.as_ref().and_then(|c| {
             c.rules.first().map(|r| {
                 (
-                    file_path.clone(),
+                    work_dir.clone(),
                     r.path_segment.clone(),
                     true, // extract_inner: segment files have document_root > segment > content
                 )
             })
         });
         // When multi-level reassembly is done, purge the entire disassembled directory
-        let post_purge_final = post_purge || config.is_some();
-        self.reassemble_plain(&file_path, file_extension, post_purge_final, base_segment)
-            .await
+        let post_purge_final = options.post_purge || config.is_some();
+        let output_path = self.top_level_output_path(&file_path, options, relative);
+        self.reassemble_plain(
+            &work_dir,
+            &output_path,
+            false,
+            base_segment,
+            &options.transforms,
+            options.jobs,
+        )?;
+        if post_purge_final {
+            purge_generated(&self.sink, path)?;
+        }
+        self.sink.notify(|| Event::FileReassembled {
+            path: path.to_path_buf(),
+            output: PathBuf::from(output_path),
+        });
+        Ok(())
     }
 
     /// Reassemble a single multi-level segment directory: walk each process dir, reassemble
     /// nested segments, reassemble the process, then ensure the wrapper structure.
-    async fn reassemble_multi_level_segment(
+    fn reassemble_multi_level_segment(
         &self,
         segment_path: &Path,
         rule: &crate::types::MultiLevelRule,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if !segment_path.is_dir() {
+        jobs: usize,
+    ) -> Result<(), Error> {
+        let storage = self.storage.as_ref();
+        if !storage.is_dir(segment_path) {
             return Ok(());
         }
-        let mut entries = Vec::new();
-        let mut read_dir = fs::read_dir(segment_path).await?;
-        while let Some(entry) = read_dir.next_entry().await? {
-            entries.push(entry);
-        }
-        entries.sort_by_key(|e| e.file_name());
-        for entry in entries {
-            let process_path = entry.path();
-            if !process_path.is_dir() {
+        let mut entries = storage.list(segment_path)?;
+        entries.sort();
+        for process_path in entries {
+            if !storage.is_dir(&process_path) {
                 continue;
             }
             let process_path_str = normalize_path_unix(&process_path.to_string_lossy());
-            let mut sub_entries = Vec::new();
-            let mut sub_read = fs::read_dir(&process_path).await?;
-            while let Some(e) = sub_read.next_entry().await? {
-                sub_entries.push(e);
-            }
-            sub_entries.sort_by_key(|e| e.file_name());
-            for sub_entry in sub_entries {
-                let sub_path = sub_entry.path();
-                if sub_path.is_dir() {
+            let mut sub_entries = storage.list(&process_path)?;
+            sub_entries.sort();
+            for sub_path in sub_entries {
+                if storage.is_dir(&sub_path) {
                     let sub_path_str = normalize_path_unix(&sub_path.to_string_lossy());
-                    self.reassemble_plain(&sub_path_str, Some("xml"), true, None)
-                        .await?;
+                    allow_skip(self.reassemble_plain(
+                        &sub_path_str,
+                        &self.get_output_path(&sub_path_str, Some("xml")),
+                        true,
+                        None,
+                        &[],
+                        jobs,
+                    ))?;
                 }
             }
-            self.reassemble_plain(&process_path_str, Some("xml"), true, None)
-                .await?;
+            allow_skip(self.reassemble_plain(
+                &process_path_str,
+                &self.get_output_path(&process_path_str, Some("xml")),
+                true,
+                None,
+                &[],
+                jobs,
+            ))?;
         }
         ensure_segment_files_structure(
+            storage,
             segment_path,
             &rule.wrap_root_element,
             &rule.path_segment,
             &rule.wrap_xmlns,
-        )
-        .await?;
+        )?;
         Ok(())
     }
 
@@ -131,139 +369,262 @@ impl ReassembleXmlFileHandler {
     /// When base_segment is Some((base_path, segment_name, extract_inner)), processing that base path
     /// treats the segment subdir as one key whose value is an array; when extract_inner is true,
     /// each file's root has document_root > segment > content and we use content (not whole root).
-    async fn reassemble_plain(
+    /// The merged document is written to `output_path`. `post_purge` removes the whole directory
+    /// (only used for the intermediate levels of a multi-level reassembly). `transforms` run over
+    /// the merged document, and up to `jobs` parts are parsed at a time. A directory without a
+    /// usable part is an [`Error`].
+    fn reassemble_plain(
         &self,
         file_path: &str,
-        file_extension: Option<&str>,
+        output_path: &str,
         post_purge: bool,
         base_segment: Option<(String, String, bool)>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        transforms: &[Arc<dyn ElementTransform>],
+        jobs: usize,
+    ) -> Result<(), Error> {
         let file_path = normalize_path_unix(file_path);
         log::debug!("Parsing directory to reassemble: {}", file_path);
-        let parsed_objects = self
-            .process_files_in_directory(file_path.to_string(), base_segment)
-            .await?;
+        let root_wrapper = read_root_wrapper(self.storage.as_ref(), Path::new(&file_path));
+        let bare_root = root_wrapper.as_ref().map(|w| w.name.clone());
+        let parsed_objects =
+            self.process_files_in_directory(file_path.to_string(), base_segment, bare_root, jobs)?;
 
         if parsed_objects.is_empty() {
-            log::error!(
-                "No files under {} were parsed successfully. A reassembled XML file was not created.",
-                file_path
-            );
-            return Ok(());
+            return Err(Error::NoParts {
+                path: PathBuf::from(&file_path),
+            });
         }
 
         // merge_xml_elements only returns None when every parsed element is empty or
         // declaration-only (no usable root). Treat that the same as "nothing parsed"
         // rather than emitting an `<root></root>` stub.
         let Some(mut merged) = merge_xml_elements(&parsed_objects) else {
-            log::error!(
-                "No usable root element found while merging files under {}. A reassembled XML file was not created.",
-                file_path
-            );
-            return Ok(());
+            return Err(Error::NoRootToMerge {
+                path: PathBuf::from(&file_path),
+            });
         };
 
         // Apply stored key order so reassembled XML matches original document order.
         let key_order_path = Path::new(&file_path).join(".key_order.json");
-        if let Some(reordered) = read_key_order(&key_order_path)
-            .await
+        if let Some(reordered) = read_key_order(self.storage.as_ref(), &key_order_path)
             .and_then(|order| reorder_root_keys(&merged, &order))
         {
             merged = reordered;
         }
+        if let Some(wrapper) = &root_wrapper {
+            merged = apply_root_wrapper(merged, wrapper);
+        }
+        apply_transforms(&mut merged, transforms);
 
         let final_xml = build_xml_string(&merged);
-        let output_path = self.get_output_path(&file_path, file_extension);
-
-        fs::write(&output_path, final_xml).await?;
+        if let Some(parent) = Path::new(output_path).parent() {
+            sink::create_dir_all(Some(&self.sink), parent)?;
+        }
+        // Diffs are only shown by dry runs.
+        self.sink.write(
+            Path::new(output_path),
+            final_xml.as_bytes(),
+            self.sink.is_dry_run(),
+        )?;
 
         if post_purge {
-            fs::remove_dir_all(file_path).await.ok();
+            self.sink.remove_dir_all(Path::new(&file_path))?;
         }
 
         Ok(())
     }
 
-    fn process_files_in_directory<'a>(
-        &'a self,
+    fn process_files_in_directory(
+        &self,
         dir_path: String,
         base_segment: Option<(String, String, bool)>,
-    ) -> ProcessDirFuture<'a> {
-        Box::pin(async move {
-            let mut parsed = Vec::new();
-            let mut entries = Vec::new();
-            let mut read_dir = fs::read_dir(&dir_path).await?;
-            while let Some(entry) = read_dir.next_entry().await? {
-                entries.push(entry);
-            }
-            // Sort by full filename for deterministic cross-platform ordering
-            entries.sort_by(|a, b| {
-                let a_name = a.file_name().to_string_lossy().to_string();
-                let b_name = b.file_name().to_string_lossy().to_string();
-                a_name.cmp(&b_name)
-            });
+        bare_root: Option<String>,
+        jobs: usize,
+    ) -> Result<Vec<XmlElement>, Error> {
+        self.sink.check_cancelled()?;
+        let storage = self.storage.as_ref();
+        let mut parsed = Vec::new();
+        let mut entries = storage.list(Path::new(&dir_path))?;
+        // Sort by full filename for deterministic cross-platform ordering
+        entries.sort_by(|a, b| {
+            let a_name = a.file_name().unwrap_or_default().to_string_lossy();
+            let b_name = b.file_name().unwrap_or_default().to_string_lossy();
+            a_name.cmp(&b_name)
+        });
 
-            let is_base = base_segment
-                .as_ref()
-                .map(|(base, _, _)| dir_path == *base)
-                .unwrap_or(false);
-            let segment_name = base_segment.as_ref().map(|(_, name, _)| name.as_str());
-            let extract_inner = base_segment.as_ref().map(|(_, _, e)| *e).unwrap_or(false);
-
-            for entry in entries {
-                let path = entry.path();
-                let file_path = normalize_path_unix(&path.to_string_lossy()).to_string();
-
-                if path.is_file() {
-                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                    if !name.starts_with('.') && self.is_parsable_file(name) {
-                        if let Some(parsed_obj) = parse_to_xml_object(&file_path).await {
-                            parsed.push(parsed_obj);
-                        }
+        let is_base = base_segment
+            .as_ref()
+            .map(|(base, _, _)| dir_path == *base)
+            .unwrap_or(false);
+        let segment_name = base_segment.as_ref().map(|(_, name, _)| name.as_str());
+        let extract_inner = base_segment.as_ref().map(|(_, _, e)| *e).unwrap_or(false);
+
+        // Parse the part files of this directory concurrently; results keep entry order.
+        let file_name = |path: &PathBuf| -> String {
+            path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let file_names: Vec<String> = entries
+            .iter()
+            .filter(|path| storage.is_file(path))
+            .map(file_name)
+            .collect();
+        let siblings: Vec<&str> = file_names.iter().map(String::as_str).collect();
+        let part_files: Vec<(usize, String)> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| {
+                let name = file_name(path);
+                storage.is_file(path)
+                    && !name.starts_with('.')
+                    && self.is_parsable_file(&name)
+                    && !is_sidecar_file(&name, &siblings)
+            })
+            .map(|(idx, path)| (idx, normalize_path_unix(&path.to_string_lossy())))
+            .collect();
+        let queue = TaskQueue::new(jobs);
+        let bare_root_ref = bare_root.as_deref();
+        let dir_ref = Path::new(&dir_path);
+        let parsed_parts = queue.run_all(part_files.iter().map(|(_, file_path)| {
+            move || {
+                let parsed_obj = match bare_root_ref {
+                    Some(root) => parse_bare_part_to_xml_object(storage, file_path, root),
+                    None => parse_to_xml_object(storage, file_path),
+                };
+                match parsed_obj {
+                    Some(mut parsed_obj) => {
+                        let references = sidecar_references(&parsed_obj);
+                        inline_sidecars(storage, &mut parsed_obj, dir_ref);
+                        (Some(parsed_obj), references)
                     }
-                } else {
-                    // Anything not a regular file is treated as a directory; symlinks and
-                    // other exotic entries simply recurse via read_dir below.
-                    let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                    if is_base && segment_name == Some(dir_name) {
-                        let segment_element = self
-                            .collect_segment_as_array(
-                                &file_path,
-                                segment_name.unwrap(),
-                                extract_inner,
-                            )
-                            .await?;
-                        if let Some(el) = segment_element {
-                            parsed.push(el);
-                        }
-                    } else {
-                        let sub_parsed = self
-                            .process_files_in_directory(file_path, base_segment.clone())
-                            .await?;
-                        parsed.extend(sub_parsed);
+                    None => (None, Vec::new()),
+                }
+            }
+        }));
+        // A file another part references is that part's sidecar, whatever its name.
+        let referenced: std::collections::HashSet<String> = parsed_parts
+            .iter()
+            .flat_map(|(_, references)| references.iter().cloned())
+            .collect();
+        let mut parts_by_entry: HashMap<usize, XmlElement> = part_files
+            .iter()
+            .map(|(idx, _)| *idx)
+            .zip(parsed_parts)
+            .filter(|(idx, _)| !referenced.contains(&file_name(&entries[*idx])))
+            .filter_map(|(idx, (parsed_obj, _))| parsed_obj.map(|p| (idx, p)))
+            .collect();
+
+        for (idx, path) in entries.iter().enumerate() {
+            let file_path = normalize_path_unix(&path.to_string_lossy()).to_string();
+
+            if storage.is_file(path) {
+                if let Some(parsed_obj) = parts_by_entry.remove(&idx) {
+                    parsed.push(parsed_obj);
+                }
+            } else {
+                // Anything not a regular file is treated as a directory; symlinks and
+                // other exotic entries simply recurse via read_dir below.
+                let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if let Some(marker) = read_nested_item_marker(storage, path) {
+                    if let Some(el) =
+                        self.collect_nested_item(file_path, marker, bare_root.clone(), jobs)?
+                    {
+                        parsed.push(el);
                     }
+                } else if is_base && segment_name == Some(dir_name) {
+                    let segment_element = self.collect_segment_as_array(
+                        &file_path,
+                        segment_name.unwrap(),
+                        extract_inner,
+                    )?;
+                    if let Some(el) = segment_element {
+                        parsed.push(el);
+                    }
+                } else {
+                    let sub_parsed = self.process_files_in_directory(
+                        file_path,
+                        base_segment.clone(),
+                        bare_root.clone(),
+                        jobs,
+                    )?;
+                    parsed.extend(sub_parsed);
                 }
             }
+        }
 
-            Ok(parsed)
-        })
+        Ok(parsed)
+    }
+
+    /// Merge the directory of a split item that child decompose rules broke up (marked by
+    /// `.nested_item.json`) back into one element: every part is root > tags... > partial item,
+    /// so unwrap the tags, merge the partial items, restore their key order and wrap again.
+    fn collect_nested_item(
+        &self,
+        item_dir: String,
+        marker: NestedItemMarker,
+        bare_root: Option<String>,
+        jobs: usize,
+    ) -> Result<Option<XmlElement>, Error> {
+        let parts = self.process_files_in_directory(item_dir, None, bare_root, jobs)?;
+        let mut unwrapped = Vec::new();
+        for part in &parts {
+            let Some(obj) = part.as_object() else {
+                continue;
+            };
+            let Some(root_key) = obj.keys().find(|k| *k != "?xml") else {
+                continue;
+            };
+            let mut inner = obj.get(root_key);
+            for tag in &marker.tags {
+                inner = inner.and_then(|v| v.get(tag));
+            }
+            let Some(inner) = inner.filter(|v| v.is_object()) else {
+                continue;
+            };
+            let mut el = serde_json::Map::new();
+            if let Some(decl) = obj.get("?xml") {
+                el.insert("?xml".to_string(), decl.clone());
+            }
+            el.insert(root_key.clone(), inner.clone());
+            unwrapped.push(Value::Object(el));
+        }
+        let Some(merged) = merge_xml_elements(&unwrapped) else {
+            return Ok(None);
+        };
+        let merged = reorder_root_keys(&merged, &marker.key_order).unwrap_or(merged);
+        let obj = merged.as_object().cloned().unwrap_or_default();
+        let Some(root_key) = obj.keys().find(|k| *k != "?xml").cloned() else {
+            return Ok(None);
+        };
+        let mut item = obj.get(&root_key).cloned().unwrap_or(Value::Null);
+        for tag in marker.tags.iter().rev() {
+            let mut wrapped = serde_json::Map::new();
+            wrapped.insert(tag.clone(), item);
+            item = Value::Object(wrapped);
+        }
+        let mut top = serde_json::Map::new();
+        if let Some(decl) = obj.get("?xml") {
+            top.insert("?xml".to_string(), decl.clone());
+        }
+        top.insert(root_key, item);
+        Ok(Some(Value::Object(top)))
     }
 
     /// Collect all .xml files in a directory, parse each, and build one element with
     /// root_key and single key segment_name whose value is array of each file's content.
     /// When extract_inner is true, each file has root > segment_name > content; we push that content.
-    async fn collect_segment_as_array(
+    fn collect_segment_as_array(
         &self,
         segment_dir: &str,
         segment_name: &str,
         extract_inner: bool,
-    ) -> Result<Option<XmlElement>, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Option<XmlElement>, Error> {
+        let storage = self.storage.as_ref();
         let mut xml_files = Vec::new();
-        let mut read_dir = fs::read_dir(segment_dir).await?;
-        while let Some(entry) = read_dir.next_entry().await? {
-            let path = entry.path();
+        for path in storage.list(Path::new(segment_dir))? {
             let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-            if path.is_file() && !name.starts_with('.') && self.is_parsable_file(name) {
+            if storage.is_file(&path) && !name.starts_with('.') && self.is_parsable_file(name) {
                 xml_files.push(normalize_path_unix(&path.to_string_lossy()));
             }
         }
@@ -274,7 +635,7 @@ impl ReassembleXmlFileHandler {
         for file_path in &xml_files {
             // parse_to_xml_object always yields a JSON object on success; treat any other
             // shape (including parse failure) as a skip without branching explicitly.
-            let Some(parsed) = parse_to_xml_object(file_path).await else {
+            let Some(parsed) = parse_to_xml_object(storage, file_path) else {
                 continue;
             };
             let obj_owned = parsed.as_object().cloned().unwrap_or_default();
@@ -331,19 +692,90 @@ impl ReassembleXmlFileHandler {
             || lower.ends_with(".yml")
     }
 
-    async fn validate_directory(
+    /// Output path for a directory passed to (or found by) `reassemble`, honouring
+    /// [`ReassembleOptions::output`] and [`ReassembleOptions::output_dir`]. Otherwise a recursive
+    /// run writes each file to the location its marker records, and a single directory is
+    /// reassembled next to itself. With a `.source.json` marker the original file name is
+    /// rebuilt: as is without an extension, and as `Admin.{extension}` for `Admin/`. A directory
+    /// named with the type suffix keeps it: `Admin.permissionset/` becomes
+    /// `Admin.permissionset-meta.xml` for an extension the original name already ends in,
+    /// otherwise `Admin.permissionset-meta.{extension}`.
+    fn top_level_output_path(
         &self,
-        path: &str,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-        let meta = fs::metadata(path).await?;
-        if !meta.is_dir() {
-            log::error!(
-                "The provided path to reassemble is not a directory: {}",
-                path
-            );
-            return Ok(false);
+        dir_path: &str,
+        options: &ReassembleOptions,
+        relative: &Path,
+    ) -> String {
+        if let Some(output) = &options.output {
+            return output.to_string_lossy().to_string();
         }
-        Ok(true)
+        let extension = options.extension();
+        let path = Path::new(dir_path);
+        let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let marker = read_source_marker(self.storage.as_ref(), path).filter(|m| {
+            // Ignore the marker when the directory was renamed after disassembly.
+            let short = short_name(m);
+            dir_name == short || dir_name.starts_with(&format!("{}.", short))
+        });
+        // A recursive run puts each file back where it was disassembled from.
+        let source_dir = marker
+            .as_ref()
+            .filter(|_| options.recursive)
+            .and_then(|m| m.source_location(path))
+            .and_then(|location| location.parent().map(Path::to_path_buf));
+        let default = match (marker, extension) {
+            (Some(marker), None) => path
+                .with_file_name(&marker.file_name)
+                .to_string_lossy()
+                .to_string(),
+            (Some(marker), Some(extension)) if dir_name == short_name(&marker) => self
+                .get_output_path(
+                    &path.with_file_name(dir_name).to_string_lossy(),
+                    Some(extension),
+                ),
+            // `Admin.permissionset/` and `Admin.profile/` must not both become `Admin.{extension}`.
+            (Some(marker), Some(extension)) => {
+                let name = if marker.file_name.ends_with(&format!(".{}", extension)) {
+                    PathBuf::from(&marker.file_name)
+                } else {
+                    Path::new(&marker.file_name).with_extension(extension)
+                };
+                path.with_file_name(name).to_string_lossy().to_string()
+            }
+            (None, _) => self.get_output_path(dir_path, extension),
+        };
+        let name = Path::new(&default).file_name().unwrap_or_default();
+        match (&options.output_dir, source_dir) {
+            (Some(dir), _) => dir.join(relative).join(name).to_string_lossy().to_string(),
+            (None, Some(source_dir)) => source_dir.join(name).to_string_lossy().to_string(),
+            (None, None) => default,
+        }
+    }
+
+    /// Fail before anything is written when two of `dirs` would reassemble into the same file.
+    fn check_distinct_outputs(
+        &self,
+        dirs: &[PathBuf],
+        root: &Path,
+        options: &ReassembleOptions,
+    ) -> Result<(), Error> {
+        let mut outputs: HashMap<String, &PathBuf> = HashMap::new();
+        for dir in dirs {
+            let relative = dir
+                .parent()
+                .and_then(|p| p.strip_prefix(root).ok())
+                .unwrap_or(Path::new(""));
+            let output = self.top_level_output_path(&dir.to_string_lossy(), options, relative);
+            if let Some(other) = outputs.insert(output.clone(), dir) {
+                return Err(Error::Options(OptionsError::Invalid(format!(
+                    "{} and {} would both be reassembled into {}",
+                    other.display(),
+                    dir.display(),
+                    output
+                ))));
+            }
+        }
+        Ok(())
     }
 
     fn get_output_path(&self, dir_path: &str, extension: Option<&str>) -> String {
@@ -361,6 +793,31 @@ impl ReassembleXmlFileHandler {
     }
 }
 
+#[cfg(feature = "async")]
+impl ReassembleXmlFileHandler {
+    /// [`Self::reassemble`] on tokio's blocking thread pool, for async callers. The handler moves
+    /// to the pool for the run and is put back afterwards; if the run panics or the future is
+    /// dropped first, it is left as [`Self::new`]. Dropping the future does not stop the run; use
+    /// [`Self::with_cancellation`] for that.
+    pub async fn reassemble_async(
+        &mut self,
+        file_path: &str,
+        options: &ReassembleOptions,
+    ) -> Result<(), Error> {
+        let mut handler = std::mem::take(self);
+        let file_path = file_path.to_string();
+        let options = options.clone();
+        let (handler, result) = tokio::task::spawn_blocking(move || {
+            let result = handler.reassemble(&file_path, &options);
+            (handler, result)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        *self = handler;
+        result
+    }
+}
+
 impl Default for ReassembleXmlFileHandler {
     fn default() -> Self {
         Self::new()
@@ -421,12 +878,12 @@ mod tests {
         assert_eq!(h.get_output_path("only", Some("json")), "only.json");
     }
 
-    #[tokio::test]
-    async fn reassemble_multi_level_segment_noop_when_not_dir() {
+    #[test]
+    fn reassemble_multi_level_segment_noop_when_not_dir() {
         let h = ReassembleXmlFileHandler::new();
         let tmp = tempfile::tempdir().unwrap();
         let file = tmp.path().join("not_a_dir.txt");
-        tokio::fs::write(&file, "hi").await.unwrap();
+        std::fs::write(&file, "hi").unwrap();
         let rule = crate::types::MultiLevelRule {
             file_pattern: String::new(),
             root_to_strip: String::new(),
@@ -435,21 +892,17 @@ mod tests {
             wrap_root_element: "Root".to_string(),
             wrap_xmlns: String::new(),
         };
-        h.reassemble_multi_level_segment(&file, &rule)
-            .await
-            .unwrap();
+        h.reassemble_multi_level_segment(&file, &rule, 1).unwrap();
     }
 
-    #[tokio::test]
-    async fn reassemble_multi_level_segment_skips_files_in_segment_root() {
+    #[test]
+    fn reassemble_multi_level_segment_skips_files_in_segment_root() {
         let h = ReassembleXmlFileHandler::new();
         let tmp = tempfile::tempdir().unwrap();
         let segment = tmp.path().join("segment");
-        tokio::fs::create_dir(&segment).await.unwrap();
+        std::fs::create_dir(&segment).unwrap();
         // A bare file inside the segment dir should be skipped (not a subdir).
-        tokio::fs::write(segment.join("stray.txt"), "x")
-            .await
-            .unwrap();
+        std::fs::write(segment.join("stray.txt"), "x").unwrap();
         let rule = crate::types::MultiLevelRule {
             file_pattern: String::new(),
             root_to_strip: String::new(),
@@ -458,55 +911,43 @@ mod tests {
             wrap_root_element: "Root".to_string(),
             wrap_xmlns: "http://example.com".to_string(),
         };
-        h.reassemble_multi_level_segment(&segment, &rule)
-            .await
+        h.reassemble_multi_level_segment(&segment, &rule, 1)
             .unwrap();
     }
 
-    #[tokio::test]
-    async fn collect_segment_as_array_returns_none_for_empty_dir() {
+    #[test]
+    fn collect_segment_as_array_returns_none_for_empty_dir() {
         let h = ReassembleXmlFileHandler::new();
         let tmp = tempfile::tempdir().unwrap();
         let out = h
             .collect_segment_as_array(tmp.path().to_str().unwrap(), "seg", true)
-            .await
             .unwrap();
         assert!(out.is_none());
     }
 
-    #[tokio::test]
-    async fn collect_segment_as_array_skips_unparseable_and_empty_roots() {
+    #[test]
+    fn collect_segment_as_array_skips_unparseable_and_empty_roots() {
         let h = ReassembleXmlFileHandler::new();
         let tmp = tempfile::tempdir().unwrap();
         // Unparseable XML
-        tokio::fs::write(tmp.path().join("bad.xml"), "<<")
-            .await
-            .unwrap();
+        std::fs::write(tmp.path().join("bad.xml"), "<<").unwrap();
         // Valid XML but only declaration and no root after parse
-        tokio::fs::write(tmp.path().join("only-decl.xml"), "")
-            .await
-            .unwrap();
+        std::fs::write(tmp.path().join("only-decl.xml"), "").unwrap();
         // Hidden file is skipped
-        tokio::fs::write(tmp.path().join(".hidden.xml"), "<r/>")
-            .await
-            .unwrap();
+        std::fs::write(tmp.path().join(".hidden.xml"), "<r/>").unwrap();
         let out = h
             .collect_segment_as_array(tmp.path().to_str().unwrap(), "seg", false)
-            .await
             .unwrap();
         assert!(out.is_none());
     }
 
-    #[tokio::test]
-    async fn collect_segment_as_array_without_extract_inner_wraps_root() {
+    #[test]
+    fn collect_segment_as_array_without_extract_inner_wraps_root() {
         let h = ReassembleXmlFileHandler::new();
         let tmp = tempfile::tempdir().unwrap();
-        tokio::fs::write(tmp.path().join("a.xml"), r#"<Root><child>1</child></Root>"#)
-            .await
-            .unwrap();
+        std::fs::write(tmp.path().join("a.xml"), r#"<Root><child>1</child></Root>"#).unwrap();
         let out = h
             .collect_segment_as_array(tmp.path().to_str().unwrap(), "seg", false)
-            .await
             .unwrap()
             .unwrap();
         let obj = out.as_object().unwrap();
@@ -1,21 +1,51 @@
 //! XML Disassembler - Disassemble large XML files into smaller files and reassemble the original XML.
 
 pub mod builders;
+pub mod cancel;
+pub mod check;
 pub mod cli;
 pub mod constants;
+pub mod error;
 pub mod handlers;
+pub mod limits;
 pub mod multi_level;
+pub mod observer;
+pub mod options;
 pub mod parsers;
+pub mod report;
+pub mod sidecar;
+pub mod sink;
+pub mod storage;
+pub mod transform;
 pub mod transformers;
 pub mod types;
 pub mod utils;
+pub mod verify;
 
 pub use builders::build_xml_string;
-pub use handlers::{DisassembleXmlFileHandler, ReassembleXmlFileHandler};
+pub use cancel::{CancellationToken, Cancelled};
+pub use check::{check_disassembly, CheckReport};
+pub use error::Error;
+pub use handlers::{
+    disassemble_str, reassemble_from_map, DisassembleXmlFileHandler, ReassembleXmlFileHandler,
+};
+pub use limits::{LimitError, Limits};
 pub use multi_level::{
     load_multi_level_config, path_segment_from_file_pattern, save_multi_level_config,
     strip_root_and_build_xml,
 };
+pub use observer::{Event, Observer};
+pub use options::{
+    DecomposeMode, DisassembleOptions, DisassembleOptionsBuilder, Format, OptionsError,
+    ReassembleOptions, ReassembleOptionsBuilder, Strategy,
+};
 pub use parsers::parse_xml;
+pub use report::RunReport;
+pub use sink::{ChangeCounts, ChangeKind, OutputSink, PlannedChange};
+pub use storage::{LocalStorage, MemoryStorage, Storage};
+pub use transform::{ElementTransform, RuleAction, TransformAction, TransformRule};
 pub use transformers::{transform_to_json, transform_to_json5, transform_to_yaml};
-pub use types::{DecomposeRule, MultiLevelConfig, MultiLevelRule, XmlElement};
+pub use types::{
+    DecomposeRule, LeafSplitRule, MultiLevelConfig, MultiLevelRule, SidecarRule, XmlElement,
+};
+pub use verify::{verify_round_trip, CompareMode, VerifyOptions, VerifyReport};
@@ -0,0 +1,185 @@
+//! Resource limits guarding disassembly of untrusted input.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Deepest element nesting accepted by default (the root element is at depth 1).
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+/// Largest source file accepted by default, in bytes (256 MiB).
+pub const DEFAULT_MAX_DOCUMENT_SIZE: u64 = 256 * 1024 * 1024;
+/// Most part files one source file may produce by default.
+pub const DEFAULT_MAX_PARTS: usize = 100_000;
+/// Longest file or directory name (in bytes) a part may get by default; most file systems
+/// reject longer names.
+pub const DEFAULT_MAX_FILENAME_LENGTH: usize = 255;
+
+/// Limits applied to each source file of a disassembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_document_size: u64,
+    pub max_parts: usize,
+    pub max_filename_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_document_size: DEFAULT_MAX_DOCUMENT_SIZE,
+            max_parts: DEFAULT_MAX_PARTS,
+            max_filename_length: DEFAULT_MAX_FILENAME_LENGTH,
+        }
+    }
+}
+
+impl Limits {
+    /// Error unless a document of `size` bytes is within `max_document_size`.
+    pub fn check_document_size(&self, file: &str, size: u64) -> Result<(), LimitError> {
+        if size > self.max_document_size {
+            return Err(LimitError::DocumentSize {
+                file: file.to_string(),
+                size,
+                max: self.max_document_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A source file exceeded one of its [`Limits`]; nothing of the run is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    Depth {
+        file: String,
+        max: usize,
+    },
+    DocumentSize {
+        file: String,
+        size: u64,
+        max: u64,
+    },
+    Parts {
+        file: String,
+        max: usize,
+    },
+    FilenameLength {
+        file: String,
+        name: String,
+        max: usize,
+    },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::Depth { file, max } => write!(
+                f,
+                "{} nests elements deeper than the limit of {} (max depth)",
+                file, max
+            ),
+            LimitError::DocumentSize { file, size, max } => write!(
+                f,
+                "{} is {} bytes, more than the limit of {} bytes (max document size)",
+                file, size, max
+            ),
+            LimitError::Parts { file, max } => write!(
+                f,
+                "{} would produce more than {} part files (max parts)",
+                file, max
+            ),
+            LimitError::FilenameLength { file, name, max } => write!(
+                f,
+                "{} would produce the name \"{}\", longer than the limit of {} bytes (max filename length)",
+                file, name, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+/// Counts the part files written for one source file against `max_parts`, and checks the
+/// length of every name in their paths; shared by the concurrent writers of that file.
+#[derive(Debug)]
+pub struct PartBudget {
+    file: String,
+    max_parts: usize,
+    max_filename_length: usize,
+    used: AtomicUsize,
+}
+
+impl PartBudget {
+    pub fn new(file: &str, limits: &Limits) -> Self {
+        Self {
+            file: file.to_string(),
+            max_parts: limits.max_parts,
+            max_filename_length: limits.max_filename_length,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Claim one part written at `relative` (below the disassembly directory).
+    pub fn take(&self, relative: &Path) -> Result<(), LimitError> {
+        for component in relative.components() {
+            let name = component.as_os_str().to_string_lossy();
+            if name.len() > self.max_filename_length {
+                return Err(LimitError::FilenameLength {
+                    file: self.file.clone(),
+                    name: name.into_owned(),
+                    max: self.max_filename_length,
+                });
+            }
+        }
+        if self.used.fetch_add(1, Ordering::Relaxed) >= self.max_parts {
+            return Err(LimitError::Parts {
+                file: self.file.clone(),
+                max: self.max_parts,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_size_is_checked_against_the_limit() {
+        let limits = Limits {
+            max_document_size: 10,
+            ..Limits::default()
+        };
+        assert!(limits.check_document_size("a.xml", 10).is_ok());
+        let err = limits.check_document_size("a.xml", 11).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a.xml is 11 bytes, more than the limit of 10 bytes (max document size)"
+        );
+    }
+
+    #[test]
+    fn part_budget_counts_parts_and_checks_names() {
+        let limits = Limits {
+            max_parts: 2,
+            max_filename_length: 8,
+            ..Limits::default()
+        };
+        let budget = PartBudget::new("a.xml", &limits);
+        assert!(budget.take(Path::new("dir/a.xml")).is_ok());
+        assert!(matches!(
+            budget.take(Path::new("too-long-dir/a.xml")),
+            Err(LimitError::FilenameLength { ref name, .. }) if name == "too-long-dir"
+        ));
+        assert!(budget.take(Path::new("b.xml")).is_ok());
+        assert_eq!(
+            budget.take(Path::new("c.xml")),
+            Err(LimitError::Parts {
+                file: "a.xml".to_string(),
+                max: 2
+            })
+        );
+    }
+}
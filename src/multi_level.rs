@@ -3,6 +3,9 @@
 use serde_json::{Map, Value};
 
 use crate::builders::build_xml_string;
+use crate::error::Error;
+use crate::sink::OutputSink;
+use crate::storage::Storage;
 use crate::types::{MultiLevelConfig, XmlElement};
 
 /// Strip the given element and build a new XML string.
@@ -70,46 +73,47 @@ pub fn path_segment_from_file_pattern(file_pattern: &str) -> String {
 }
 
 /// Load multi-level config from a directory (reads .multi_level.json).
-pub async fn load_multi_level_config(dir_path: &std::path::Path) -> Option<MultiLevelConfig> {
+pub fn load_multi_level_config(
+    storage: &dyn Storage,
+    dir_path: &std::path::Path,
+) -> Option<MultiLevelConfig> {
     let path = dir_path.join(".multi_level.json");
-    let content = tokio::fs::read_to_string(&path).await.ok()?;
+    let content = storage.read_to_string(&path).ok()?;
     serde_json::from_str(&content).ok()
 }
 
-/// Persist multi-level config to a directory.
-pub async fn save_multi_level_config(
+/// Persist multi-level config to a directory through `sink`, if any; an unchanged config is
+/// left untouched.
+pub fn save_multi_level_config(
+    sink: Option<&OutputSink>,
     dir_path: &std::path::Path,
     config: &MultiLevelConfig,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), Error> {
     let path = dir_path.join(".multi_level.json");
-    let content = serde_json::to_string_pretty(config)?;
-    tokio::fs::write(path, content).await?;
+    let content = serde_json::to_string_pretty(config).map_err(std::io::Error::from)?;
+    crate::sink::write_file(sink, &path, content)?;
     Ok(())
 }
 
 /// Ensure all XML files in a segment directory have structure:
 /// document_root (with xmlns) > inner_wrapper (no xmlns) > content.
 /// Used after inner-level reassembly for multi-level (e.g. LoyaltyProgramSetup > programProcesses).
-pub async fn ensure_segment_files_structure(
+pub fn ensure_segment_files_structure(
+    storage: &dyn Storage,
     dir_path: &std::path::Path,
     document_root: &str,
     inner_wrapper: &str,
     xmlns: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), Error> {
     use crate::parsers::parse_xml_from_str;
     use serde_json::Map;
 
-    let mut entries = Vec::new();
-    let mut read_dir = tokio::fs::read_dir(dir_path).await?;
-    while let Some(entry) = read_dir.next_entry().await? {
-        entries.push(entry);
-    }
+    let mut entries = storage.list(dir_path)?;
     // Sort for deterministic cross-platform ordering
-    entries.sort_by_key(|e| e.file_name());
+    entries.sort();
 
-    for entry in entries {
-        let path = entry.path();
-        if !path.is_file() {
+    for path in entries {
+        if !storage.is_file(&path) {
             continue;
         }
         let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
@@ -119,7 +123,7 @@ pub async fn ensure_segment_files_structure(
         let path_str = path.to_string_lossy();
         // Read errors on a file the walker just reported as present are essentially impossible
         // (concurrent deletion); treat the content as empty so downstream lookups skip naturally.
-        let content = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+        let content = storage.read_to_string(&path).unwrap_or_default();
         let Some(parsed) = parse_xml_from_str(&content, &path_str) else {
             continue;
         };
@@ -198,7 +202,7 @@ pub async fn ensure_segment_files_structure(
         );
         let wrapped = serde_json::Value::Object(top);
         let xml_string = build_xml_string(&wrapped);
-        tokio::fs::write(&path, xml_string).await?;
+        crate::sink::replace_file(storage, &path, xml_string)?;
     }
     Ok(())
 }
@@ -206,6 +210,7 @@ pub async fn ensure_segment_files_structure(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::LocalStorage;
     use serde_json::json;
 
     #[test]
@@ -268,8 +273,8 @@ mod tests {
         assert!(capture_xmlns_from_root(&parsed).is_none());
     }
 
-    #[tokio::test]
-    async fn save_and_load_multi_level_config() {
+    #[test]
+    fn save_and_load_multi_level_config() {
         let dir = tempfile::tempdir().unwrap();
         let config = MultiLevelConfig {
             rules: vec![crate::types::MultiLevelRule {
@@ -281,99 +286,88 @@ mod tests {
                 wrap_xmlns: "http://example.com".to_string(),
             }],
         };
-        save_multi_level_config(dir.path(), &config).await.unwrap();
-        let loaded = load_multi_level_config(dir.path()).await.unwrap();
+        save_multi_level_config(None, dir.path(), &config).unwrap();
+        let loaded = load_multi_level_config(&LocalStorage, dir.path()).unwrap();
         assert_eq!(loaded.rules.len(), 1);
         assert_eq!(loaded.rules[0].path_segment, "test");
     }
 
-    #[tokio::test]
-    async fn load_multi_level_config_missing_file_returns_none() {
+    #[test]
+    fn load_multi_level_config_missing_file_returns_none() {
         let dir = tempfile::tempdir().unwrap();
-        assert!(load_multi_level_config(dir.path()).await.is_none());
+        assert!(load_multi_level_config(&LocalStorage, dir.path()).is_none());
     }
 
-    #[tokio::test]
-    async fn ensure_segment_files_structure_adds_xmlns_and_rewrites() {
+    #[test]
+    fn ensure_segment_files_structure_adds_xmlns_and_rewrites() {
         let dir = tempfile::tempdir().unwrap();
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
 <Root>
   <programProcesses><x>1</x></programProcesses>
 </Root>"#;
         let path = dir.path().join("segment.xml");
-        tokio::fs::write(&path, xml).await.unwrap();
+        std::fs::write(&path, xml).unwrap();
         ensure_segment_files_structure(
+            &LocalStorage,
             dir.path(),
             "Root",
             "programProcesses",
             "http://example.com",
         )
-        .await
         .unwrap();
-        let out = tokio::fs::read_to_string(&path).await.unwrap();
+        let out = std::fs::read_to_string(&path).unwrap();
         assert!(out.contains("http://example.com"));
         assert!(out.contains("<programProcesses>"));
         assert!(out.contains("<x>1</x>"));
     }
 
-    #[tokio::test]
-    async fn ensure_segment_files_structure_skips_already_correct_files() {
+    #[test]
+    fn ensure_segment_files_structure_skips_already_correct_files() {
         // Root wraps inner_wrapper and has xmlns; inner has no xmlns -> no rewrite.
         let dir = tempfile::tempdir().unwrap();
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
 <Root xmlns="http://example.com"><programProcesses><x>1</x></programProcesses></Root>"#;
         let path = dir.path().join("ok.xml");
-        tokio::fs::write(&path, xml).await.unwrap();
-        let before = tokio::fs::metadata(&path).await.unwrap().modified().ok();
+        std::fs::write(&path, xml).unwrap();
+        let before = std::fs::metadata(&path).unwrap().modified().ok();
         ensure_segment_files_structure(
+            &LocalStorage,
             dir.path(),
             "Root",
             "programProcesses",
             "http://example.com",
         )
-        .await
         .unwrap();
-        let after = tokio::fs::metadata(&path).await.unwrap().modified().ok();
+        let after = std::fs::metadata(&path).unwrap().modified().ok();
         assert_eq!(before, after, "already-correct files must be left as-is");
     }
 
-    #[tokio::test]
-    async fn ensure_segment_files_structure_skips_non_xml_and_subdirs() {
+    #[test]
+    fn ensure_segment_files_structure_skips_non_xml_and_subdirs() {
         let dir = tempfile::tempdir().unwrap();
-        tokio::fs::create_dir(dir.path().join("nested"))
-            .await
-            .unwrap();
-        tokio::fs::write(dir.path().join("notes.txt"), "hello")
-            .await
-            .unwrap();
-        tokio::fs::write(dir.path().join("broken.xml"), "<<not xml>")
-            .await
-            .unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join("broken.xml"), "<<not xml>").unwrap();
         // No XML payload that matches; should succeed without writing anything.
         ensure_segment_files_structure(
+            &LocalStorage,
             dir.path(),
             "Root",
             "programProcesses",
             "http://example.com",
         )
-        .await
         .unwrap();
         // broken.xml remains unchanged
-        let raw = tokio::fs::read_to_string(dir.path().join("broken.xml"))
-            .await
-            .unwrap();
+        let raw = std::fs::read_to_string(dir.path().join("broken.xml")).unwrap();
         assert_eq!(raw, "<<not xml>");
     }
 
-    #[tokio::test]
-    async fn ensure_segment_files_structure_skips_xml_missing_root() {
+    #[test]
+    fn ensure_segment_files_structure_skips_xml_missing_root() {
         // Only a declaration, no root element (empty document)
         let dir = tempfile::tempdir().unwrap();
-        tokio::fs::write(dir.path().join("empty.xml"), "")
-            .await
-            .unwrap();
-        ensure_segment_files_structure(dir.path(), "Root", "programProcesses", "")
-            .await
+        std::fs::write(dir.path().join("empty.xml"), "").unwrap();
+        ensure_segment_files_structure(&LocalStorage, dir.path(), "Root", "programProcesses", "")
             .unwrap();
     }
 }
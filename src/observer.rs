@@ -0,0 +1,42 @@
+//! Progress events of a disassembly or reassembly, for hosts that show progress, build their own
+//! reports or collect metrics.
+
+use crate::error::Error;
+use crate::types::MultiLevelRule;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Something that happened during a run. Events are sent as the work happens, so a run that
+/// fails afterwards (and is rolled back) may already have reported parts as written.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A source file (disassembly) or disassembled directory (reassembly) is being processed.
+    FileStarted { path: PathBuf },
+    /// A part file was written (or, in a dry run, planned). `tag` is the element the part holds
+    /// (the root element for the file of leaf elements) and `id` the name the part is filed
+    /// under, if any; `bytes` is the size of its content.
+    PartWritten {
+        path: PathBuf,
+        tag: String,
+        id: Option<String>,
+        bytes: usize,
+    },
+    /// A source file was disassembled into `output`.
+    FileDisassembled { path: PathBuf, output: PathBuf },
+    /// A multi-level rule split the parts of `path` further. The second-level parts are not
+    /// reported one by one.
+    MultiLevelRuleApplied { path: PathBuf, rule: MultiLevelRule },
+    /// A disassembled directory was reassembled (or, in a dry run, planned) into `output`.
+    FileReassembled { path: PathBuf, output: PathBuf },
+    /// A file or directory was skipped (outside strict mode).
+    Skipped { path: PathBuf, reason: Error },
+    /// A file or directory failed the run.
+    Failed { path: PathBuf, error: String },
+}
+
+/// Receives the [`Event`]s of the runs of a handler it is passed to (`with_observer`). Files may
+/// be processed concurrently (`jobs` in the options), so events can arrive from several threads
+/// at once.
+pub trait Observer: fmt::Debug + Send + Sync {
+    fn on_event(&self, event: &Event);
+}
@@ -0,0 +1,898 @@
+//! Typed options for [`crate::DisassembleXmlFileHandler::disassemble`] and
+//! [`crate::ReassembleXmlFileHandler::reassemble`].
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::limits::Limits;
+use crate::transform::ElementTransform;
+use crate::types::{DecomposeRule, LeafSplitRule, MultiLevelRule, SidecarRule};
+
+/// How nested elements are written (see the README's "Disassembly strategies").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// One file per nested element, named after its unique-id elements (or a hash).
+    #[default]
+    UniqueId,
+    /// One file per tag holding every element with that tag.
+    GroupedByTag,
+}
+
+impl Strategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::UniqueId => "unique-id",
+            Strategy::GroupedByTag => "grouped-by-tag",
+        }
+    }
+}
+
+/// File format of the disassembled parts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+    #[default]
+    Xml,
+    Json,
+    Json5,
+    Yaml,
+}
+
+impl Format {
+    /// The name, which is also the extension of the part files.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Xml => "xml",
+            Format::Json => "json",
+            Format::Json5 => "json5",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// How a [`DecomposeRule`] writes the elements of its tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DecomposeMode {
+    /// One file per element, named from the rule's field.
+    #[default]
+    Split,
+    /// One file per value of the rule's field, holding every element with that value.
+    Group,
+}
+
+impl DecomposeMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DecomposeMode::Split => "split",
+            DecomposeMode::Group => "group",
+        }
+    }
+}
+
+/// The variant named `value`, or an error listing the names of `variants`.
+fn parse_name<T: Copy>(
+    option: &'static str,
+    value: &str,
+    variants: &[T],
+    name: fn(T) -> &'static str,
+) -> Result<T, OptionsError> {
+    variants
+        .iter()
+        .copied()
+        .find(|v| name(*v) == value)
+        .ok_or_else(|| OptionsError::UnknownValue {
+            option,
+            value: value.to_string(),
+            expected: variants.iter().map(|v| name(*v)).collect(),
+        })
+}
+
+impl FromStr for Strategy {
+    type Err = OptionsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_name(
+            "strategy",
+            value,
+            &[Strategy::UniqueId, Strategy::GroupedByTag],
+            Strategy::as_str,
+        )
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Format {
+    type Err = OptionsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_name(
+            "format",
+            value,
+            &[Format::Xml, Format::Json, Format::Json5, Format::Yaml],
+            Format::as_str,
+        )
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DecomposeMode {
+    type Err = OptionsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_name(
+            "decompose mode",
+            value,
+            &[DecomposeMode::Split, DecomposeMode::Group],
+            DecomposeMode::as_str,
+        )
+    }
+}
+
+impl fmt::Display for DecomposeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An option value that is unknown or does not fit the other options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionsError {
+    UnknownValue {
+        option: &'static str,
+        value: String,
+        expected: Vec<&'static str>,
+    },
+    Invalid(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::UnknownValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "unknown {} \"{}\" (expected one of: {})",
+                option,
+                value,
+                expected.join(", ")
+            ),
+            OptionsError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+/// Arguments of a disassembly, built with [`DisassembleOptions::builder`]. The default
+/// disassembles `.xml` files with the unique-id strategy into XML parts next to each source,
+/// one file at a time, without purging anything.
+#[derive(Debug, Clone)]
+pub struct DisassembleOptions {
+    pub(crate) unique_id_elements: Option<String>,
+    pub(crate) strategy: Strategy,
+    pub(crate) pre_purge: bool,
+    pub(crate) post_purge: bool,
+    pub(crate) ignore_path: String,
+    pub(crate) format: Format,
+    pub(crate) multi_level: Option<MultiLevelRule>,
+    pub(crate) decompose_rules: Vec<DecomposeRule>,
+    pub(crate) strict: bool,
+    pub(crate) transforms: Vec<Arc<dyn ElementTransform>>,
+    pub(crate) leaf_split: Option<LeafSplitRule>,
+    pub(crate) bare_parts: bool,
+    pub(crate) sidecars: Vec<SidecarRule>,
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) type_suffix_dirs: bool,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    /// Lower-case file extensions (without the dot) picked up when disassembling a directory.
+    pub(crate) extensions: Vec<String>,
+    pub(crate) jobs: usize,
+    pub(crate) dry_run: bool,
+    pub(crate) sync: bool,
+    pub(crate) hash_index: bool,
+    pub(crate) limits: Limits,
+}
+
+impl Default for DisassembleOptions {
+    fn default() -> Self {
+        Self {
+            unique_id_elements: None,
+            strategy: Strategy::default(),
+            pre_purge: false,
+            post_purge: false,
+            ignore_path: ".xmldisassemblerignore".to_string(),
+            format: Format::default(),
+            multi_level: None,
+            decompose_rules: Vec::new(),
+            strict: false,
+            transforms: Vec::new(),
+            leaf_split: None,
+            bare_parts: false,
+            sidecars: Vec::new(),
+            output_dir: None,
+            type_suffix_dirs: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            extensions: vec!["xml".to_string()],
+            jobs: 1,
+            dry_run: false,
+            sync: false,
+            hash_index: false,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl DisassembleOptions {
+    pub fn builder() -> DisassembleOptionsBuilder {
+        DisassembleOptionsBuilder::default()
+    }
+
+    pub fn unique_id_elements(&self) -> Option<&str> {
+        self.unique_id_elements.as_deref()
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn pre_purge(&self) -> bool {
+        self.pre_purge
+    }
+
+    pub fn post_purge(&self) -> bool {
+        self.post_purge
+    }
+
+    pub fn ignore_path(&self) -> &str {
+        &self.ignore_path
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn multi_level(&self) -> Option<&MultiLevelRule> {
+        self.multi_level.as_ref()
+    }
+
+    pub fn decompose_rules(&self) -> &[DecomposeRule] {
+        &self.decompose_rules
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn transforms(&self) -> &[Arc<dyn ElementTransform>] {
+        &self.transforms
+    }
+
+    pub fn leaf_split(&self) -> Option<&LeafSplitRule> {
+        self.leaf_split.as_ref()
+    }
+
+    pub fn bare_parts(&self) -> bool {
+        self.bare_parts
+    }
+
+    pub fn sidecars(&self) -> &[SidecarRule] {
+        &self.sidecars
+    }
+
+    pub fn output_dir(&self) -> Option<&Path> {
+        self.output_dir.as_deref()
+    }
+
+    pub fn type_suffix_dirs(&self) -> bool {
+        self.type_suffix_dirs
+    }
+
+    pub fn include(&self) -> &[String] {
+        &self.include
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        &self.exclude
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn sync(&self) -> bool {
+        self.sync
+    }
+
+    pub fn hash_index(&self) -> bool {
+        self.hash_index
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Whether the file has one of the configured extensions (`.xml` by default).
+    pub(crate) fn has_source_extension(&self, file_path: &str) -> bool {
+        let lower = file_path.to_lowercase();
+        self.extensions
+            .iter()
+            .any(|ext| lower.ends_with(&format!(".{}", ext)))
+    }
+}
+
+/// Builder for [`DisassembleOptions`]; [`Self::build`] validates the combination.
+#[derive(Debug, Clone, Default)]
+pub struct DisassembleOptionsBuilder {
+    options: DisassembleOptions,
+}
+
+impl DisassembleOptionsBuilder {
+    /// Comma-separated element names whose values name the part files of nested elements.
+    pub fn unique_id_elements(mut self, elements: impl Into<String>) -> Self {
+        self.options.unique_id_elements = Some(elements.into());
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.options.strategy = strategy;
+        self
+    }
+
+    /// Remove the files an earlier disassembly generated before writing.
+    pub fn pre_purge(mut self, enabled: bool) -> Self {
+        self.options.pre_purge = enabled;
+        self
+    }
+
+    /// Delete the source files once the run succeeded.
+    pub fn post_purge(mut self, enabled: bool) -> Self {
+        self.options.post_purge = enabled;
+        self
+    }
+
+    /// Ignore file (gitignore syntax) read before disassembling a directory.
+    pub fn ignore_path(mut self, path: impl Into<String>) -> Self {
+        self.options.ignore_path = path.into();
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.options.format = format;
+        self
+    }
+
+    /// Further disassemble the files matching `rule` (see the README's "Multi-level
+    /// disassembly").
+    pub fn multi_level(mut self, rule: MultiLevelRule) -> Self {
+        self.options.multi_level = Some(rule);
+        self
+    }
+
+    /// Fail the run on a file that would otherwise be skipped (see [`crate::Error`]).
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.options.strict = enabled;
+        self
+    }
+
+    /// Split or group nested tags; needs [`Strategy::GroupedByTag`].
+    pub fn decompose_rules(mut self, rules: Vec<DecomposeRule>) -> Self {
+        self.options.decompose_rules = rules;
+        self
+    }
+
+    /// Run `transform` over each source file after it is parsed, before it is split into parts.
+    /// Transforms run in the order they were added.
+    pub fn transform(mut self, transform: Arc<dyn ElementTransform>) -> Self {
+        self.options.transforms.push(transform);
+        self
+    }
+
+    /// Move leaf tags matching `rule` out of the base file into one file per tag.
+    /// Leaf-only documents can be disassembled when at least one tag matches.
+    pub fn leaf_split(mut self, rule: LeafSplitRule) -> Self {
+        self.options.leaf_split = (!rule.is_empty()).then_some(rule);
+        self
+    }
+
+    /// Write part files without the XML declaration and root element; the root name, attributes
+    /// and declaration are stored once in `.root.json` and restored on reassembly.
+    /// Not combined with multi-level rules, which re-parse the parts' root element.
+    pub fn bare_parts(mut self, enabled: bool) -> Self {
+        self.options.bare_parts = enabled;
+        self
+    }
+
+    /// Extract the text or CDATA content of matching tags into raw sidecar files next to each
+    /// part; reassembly inlines them again. Not combined with multi-level rules.
+    pub fn sidecars(mut self, rules: Vec<SidecarRule>) -> Self {
+        self.options.sidecars = rules;
+        self
+    }
+
+    /// Write disassembled directories under `dir` instead of next to the source files. When a
+    /// directory is disassembled, each file's path relative to it is mirrored under `dir`.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Always name disassembly directories after the file with its metadata-type suffix
+    /// (`Admin.permissionset/` instead of `Admin/`). Without this, the suffix is kept only for
+    /// files whose short names collide with another XML file in the same directory.
+    pub fn type_suffix_dirs(mut self, enabled: bool) -> Self {
+        self.options.type_suffix_dirs = enabled;
+        self
+    }
+
+    /// Only disassemble files matching these globs (gitignore syntax, relative to the directory
+    /// being disassembled, e.g. `**/*.permissionset-meta.xml`). When set, the globs replace the
+    /// extension filter.
+    pub fn include(mut self, globs: Vec<String>) -> Self {
+        self.options.include = globs;
+        self
+    }
+
+    /// Skip files and directories matching these globs when disassembling a directory.
+    pub fn exclude(mut self, globs: Vec<String>) -> Self {
+        self.options.exclude = globs;
+        self
+    }
+
+    /// File extensions treated as XML (default `xml`), e.g. `xsd`, `wsdl`, `resx`, `csproj`,
+    /// `config`. A leading dot is ignored; an empty list keeps the default.
+    pub fn extensions(mut self, extensions: Vec<String>) -> Self {
+        let extensions: Vec<String> = extensions
+            .iter()
+            .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+        if !extensions.is_empty() {
+            self.options.extensions = extensions;
+        }
+        self
+    }
+
+    /// Disassemble up to `jobs` files of a directory at a time, each writing up to `jobs` part
+    /// files at a time (default 1; at least 1). The output does not depend on the number of
+    /// jobs.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = jobs;
+        self
+    }
+
+    /// Run the whole pipeline without touching the file system; the files that would be
+    /// created, overwritten or deleted are available from
+    /// [`crate::DisassembleXmlFileHandler::take_planned_changes`]. Multi-level rules are skipped
+    /// in dry-run mode.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.options.dry_run = enabled;
+        self
+    }
+
+    /// Keep each disassembly directory in sync with its source: after writing the new parts,
+    /// delete the files listed in the directory's `.manifest.json` (written by every
+    /// disassembly) that this run did not produce. Other files are left alone. Not combined with
+    /// multi-level rules.
+    pub fn sync(mut self, enabled: bool) -> Self {
+        self.options.sync = enabled;
+        self
+    }
+
+    /// Store a `.hashes.json` of content hashes in each disassembly directory and use it on the
+    /// next run to skip unchanged parts without reading them. Parts whose content did not change
+    /// are never rewritten either way; the index only saves the comparison, and it trusts that
+    /// generated files are not edited by hand. Not combined with multi-level rules.
+    pub fn hash_index(mut self, enabled: bool) -> Self {
+        self.options.hash_index = enabled;
+        self
+    }
+
+    /// Limits each source file must stay within (nesting depth, size, number of parts and name
+    /// length). A file exceeding one fails the run with a [`crate::LimitError`], and nothing of
+    /// the run is kept. [`Limits::default`] applies otherwise.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.options.limits = limits;
+        self
+    }
+
+    pub fn build(self) -> Result<DisassembleOptions, OptionsError> {
+        let options = self.options;
+        if options.jobs == 0 {
+            return Err(OptionsError::Invalid(
+                "the number of jobs must be at least 1".to_string(),
+            ));
+        }
+        let limits = options.limits;
+        if limits.max_depth == 0
+            || limits.max_document_size == 0
+            || limits.max_parts == 0
+            || limits.max_filename_length == 0
+        {
+            return Err(OptionsError::Invalid(
+                "limits must be greater than zero".to_string(),
+            ));
+        }
+        if let Some(rule) = options
+            .sidecars
+            .iter()
+            .find(|r| r.tag.is_empty() || r.extension.is_empty())
+        {
+            return Err(OptionsError::Invalid(format!(
+                "sidecar rule for \"{}\" needs a tag and an extension",
+                rule.tag
+            )));
+        }
+        let mut globs = ignore::overrides::OverrideBuilder::new(".");
+        for glob in options.include.iter().chain(&options.exclude) {
+            if let Err(err) = globs.add(glob) {
+                return Err(OptionsError::Invalid(format!(
+                    "invalid glob \"{}\": {}",
+                    glob, err
+                )));
+            }
+        }
+        if let Some(elements) = &options.unique_id_elements {
+            if elements.split(',').any(|e| e.trim().is_empty()) {
+                return Err(OptionsError::Invalid(format!(
+                    "unique-id elements \"{}\" contain an empty name",
+                    elements
+                )));
+            }
+        }
+        if options.ignore_path.is_empty() {
+            return Err(OptionsError::Invalid(
+                "the ignore path must not be empty".to_string(),
+            ));
+        }
+        if !options.decompose_rules.is_empty() && options.strategy != Strategy::GroupedByTag {
+            return Err(OptionsError::Invalid(format!(
+                "decompose rules need the {} strategy, not {}",
+                Strategy::GroupedByTag,
+                options.strategy
+            )));
+        }
+        validate_decompose_rules(&options.decompose_rules)?;
+        if let Some(rule) = &options.multi_level {
+            if rule.file_pattern.is_empty()
+                || rule.root_to_strip.is_empty()
+                || rule.unique_id_elements.is_empty()
+            {
+                return Err(OptionsError::Invalid(
+                    "a multi-level rule needs a file pattern, a root to strip and unique-id elements"
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn validate_decompose_rules(rules: &[DecomposeRule]) -> Result<(), OptionsError> {
+    for rule in rules {
+        if rule.tag.is_empty() || rule.field.is_empty() {
+            return Err(OptionsError::Invalid(format!(
+                "decompose rule for \"{}\" needs a tag and a field",
+                rule.tag
+            )));
+        }
+        validate_decompose_rules(&rule.children)?;
+    }
+    Ok(())
+}
+
+/// Arguments of a reassembly, built with [`ReassembleOptions::builder`]. The default names the
+/// output after the original file (or `.xml`), writes it next to the directory and keeps the
+/// disassembled directory.
+#[derive(Debug, Clone)]
+pub struct ReassembleOptions {
+    pub(crate) extension: Option<String>,
+    pub(crate) post_purge: bool,
+    pub(crate) strict: bool,
+    pub(crate) transforms: Vec<Arc<dyn ElementTransform>>,
+    pub(crate) jobs: usize,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) dry_run: bool,
+    pub(crate) recursive: bool,
+}
+
+impl Default for ReassembleOptions {
+    fn default() -> Self {
+        Self {
+            extension: None,
+            post_purge: false,
+            strict: false,
+            transforms: Vec::new(),
+            jobs: 1,
+            output: None,
+            output_dir: None,
+            dry_run: false,
+            recursive: false,
+        }
+    }
+}
+
+impl ReassembleOptions {
+    pub fn builder() -> ReassembleOptionsBuilder {
+        ReassembleOptionsBuilder::default()
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    pub fn post_purge(&self) -> bool {
+        self.post_purge
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn transforms(&self) -> &[Arc<dyn ElementTransform>] {
+        &self.transforms
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    pub fn output_dir(&self) -> Option<&Path> {
+        self.output_dir.as_deref()
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+}
+
+/// Builder for [`ReassembleOptions`]; [`Self::build`] validates the values.
+#[derive(Debug, Clone, Default)]
+pub struct ReassembleOptionsBuilder {
+    options: ReassembleOptions,
+}
+
+impl ReassembleOptionsBuilder {
+    /// Extension of the reassembled file (e.g. `permissionset-meta.xml`), used instead of the
+    /// original file name.
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.options.extension = Some(extension.into());
+        self
+    }
+
+    /// Delete the generated files of each directory once it was reassembled.
+    pub fn post_purge(mut self, enabled: bool) -> Self {
+        self.options.post_purge = enabled;
+        self
+    }
+
+    /// Fail the run on a directory that would otherwise be skipped (see [`crate::Error`]).
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.options.strict = enabled;
+        self
+    }
+
+    /// Run `transform` over each merged document before it is written. Transforms run in the
+    /// order they were added.
+    pub fn transform(mut self, transform: Arc<dyn ElementTransform>) -> Self {
+        self.options.transforms.push(transform);
+        self
+    }
+
+    /// Parse up to `jobs` part files of a directory at a time and, when recursive, reassemble up
+    /// to `jobs` directories at a time (default 1; at least 1). The output does not depend on
+    /// the number of jobs.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = jobs;
+        self
+    }
+
+    /// Write the reassembled file to `path` instead of next to the disassembled directory. Not
+    /// combined with [`Self::output_dir`] or [`Self::recursive`].
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.output = Some(path.into());
+        self
+    }
+
+    /// Write the reassembled file into `dir` instead of next to the disassembled directory. When
+    /// recursive, each directory's path relative to the root is mirrored under `dir`.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Run the whole pipeline without writing; the files that would be created, overwritten or
+    /// deleted are available from [`crate::ReassembleXmlFileHandler::take_planned_changes`].
+    /// Multi-level directories are skipped in dry-run mode.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.options.dry_run = enabled;
+        self
+    }
+
+    /// Treat the path as a root and reassemble every disassembled directory below it (found by
+    /// the marker files a disassembly writes), each one to the location its `.source.json`
+    /// records, also when it was disassembled with an output directory (next to itself for
+    /// markers without a location).
+    pub fn recursive(mut self, enabled: bool) -> Self {
+        self.options.recursive = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<ReassembleOptions, OptionsError> {
+        if self.options.jobs == 0 {
+            return Err(OptionsError::Invalid(
+                "the number of jobs must be at least 1".to_string(),
+            ));
+        }
+        if self.options.output.is_some() && self.options.output_dir.is_some() {
+            return Err(OptionsError::Invalid(
+                "an output file and an output directory cannot be combined".to_string(),
+            ));
+        }
+        if self.options.output.is_some() && self.options.recursive {
+            return Err(OptionsError::Invalid(
+                "a recursive reassembly writes several files; use an output directory instead of an output file"
+                    .to_string(),
+            ));
+        }
+        if let Some(extension) = &self.options.extension {
+            if extension.is_empty() || extension.contains(['/', '\\']) {
+                return Err(OptionsError::Invalid(format!(
+                    "\"{}\" is not a file extension",
+                    extension
+                )));
+            }
+        }
+        Ok(self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enums_parse_their_names_and_reject_others() {
+        assert_eq!(
+            "grouped-by-tag".parse::<Strategy>(),
+            Ok(Strategy::GroupedByTag)
+        );
+        assert_eq!("json5".parse::<Format>(), Ok(Format::Json5));
+        assert_eq!("group".parse::<DecomposeMode>(), Ok(DecomposeMode::Group));
+        let err = "by-tag".parse::<Strategy>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown strategy \"by-tag\" (expected one of: unique-id, grouped-by-tag)"
+        );
+        assert!("toml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn disassemble_options_are_validated_when_built() {
+        let options = DisassembleOptions::builder().build().unwrap();
+        assert_eq!(options.strategy(), Strategy::UniqueId);
+        assert_eq!(options.format(), Format::Xml);
+        assert_eq!(options.ignore_path(), ".xmldisassemblerignore");
+
+        let rule = DecomposeRule {
+            tag: "objectPermissions".to_string(),
+            path_segment: "objectPermissions".to_string(),
+            mode: DecomposeMode::Split,
+            field: "object".to_string(),
+            ..Default::default()
+        };
+        assert!(DisassembleOptions::builder()
+            .decompose_rules(vec![rule.clone()])
+            .build()
+            .is_err());
+        assert!(DisassembleOptions::builder()
+            .strategy(Strategy::GroupedByTag)
+            .decompose_rules(vec![rule.clone()])
+            .build()
+            .is_ok());
+        let no_field = DecomposeRule {
+            field: String::new(),
+            ..rule
+        };
+        assert!(DisassembleOptions::builder()
+            .strategy(Strategy::GroupedByTag)
+            .decompose_rules(vec![no_field])
+            .build()
+            .is_err());
+        assert!(DisassembleOptions::builder()
+            .unique_id_elements("name,,id")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn run_settings_are_validated_when_built() {
+        let options = DisassembleOptions::builder().build().unwrap();
+        assert_eq!(
+            (options.jobs(), options.extensions()),
+            (1, &["xml".to_string()][..])
+        );
+        assert!(DisassembleOptions::builder().jobs(0).build().is_err());
+        assert!(DisassembleOptions::builder()
+            .limits(Limits {
+                max_parts: 0,
+                ..Limits::default()
+            })
+            .build()
+            .is_err());
+        assert!(DisassembleOptions::builder()
+            .sidecars(vec![SidecarRule {
+                tag: "actionSchema".to_string(),
+                extension: String::new(),
+                min_len: 0,
+            }])
+            .build()
+            .is_err());
+        assert!(DisassembleOptions::builder()
+            .exclude(vec!["legacy/[".to_string()])
+            .build()
+            .is_err());
+
+        assert_eq!(ReassembleOptions::default().jobs(), 1);
+        assert!(ReassembleOptions::builder().jobs(0).build().is_err());
+        assert!(ReassembleOptions::builder()
+            .output("out.xml")
+            .output_dir("dist")
+            .build()
+            .is_err());
+        assert!(ReassembleOptions::builder()
+            .output("out.xml")
+            .recursive(true)
+            .build()
+            .is_err());
+        assert!(ReassembleOptions::builder()
+            .output_dir("dist")
+            .recursive(true)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn reassemble_extension_must_not_be_a_path() {
+        assert!(ReassembleOptions::builder()
+            .extension("permissionset-meta.xml")
+            .build()
+            .is_ok());
+        assert!(ReassembleOptions::builder()
+            .extension("../x")
+            .build()
+            .is_err());
+    }
+}
@@ -1,6 +1,8 @@
 //! Parse element during disassembly - unified strategy handling.
 
 use crate::builders::build_disassembled_file;
+use crate::error::Error;
+use crate::options::Strategy;
 use crate::types::{UnifiedParseResult, XmlElementArrayMap, XmlElementParams};
 use serde_json::{Map, Value};
 
@@ -14,7 +16,10 @@ fn is_nested_object(element: &Value) -> bool {
         .unwrap_or(false)
 }
 
-pub async fn parse_element_unified(params: XmlElementParams<'_>) -> UnifiedParseResult {
+/// Classify one element: nested elements are written as part files (unique-id) or returned as
+/// groups (grouped-by-tag); leaves are returned for the base file. Fails when a part cannot be
+/// written.
+pub fn parse_element_unified(params: XmlElementParams<'_>) -> Result<UnifiedParseResult, Error> {
     let XmlElementParams {
         element,
         disassembled_path,
@@ -27,6 +32,10 @@ pub async fn parse_element_unified(params: XmlElementParams<'_>) -> UnifiedParse
         format,
         xml_declaration,
         strategy,
+        bare,
+        sidecars,
+        sink,
+        parts,
         leaf_content: _,
     } = params;
 
@@ -35,17 +44,17 @@ pub async fn parse_element_unified(params: XmlElementParams<'_>) -> UnifiedParse
     let is_nested = is_array || is_nested_obj;
 
     if is_nested {
-        if strategy == "grouped-by-tag" {
+        if strategy == Strategy::GroupedByTag {
             let mut nested = XmlElementArrayMap::new();
             nested.insert(key.to_string(), vec![element.clone()]);
-            return UnifiedParseResult {
+            return Ok(UnifiedParseResult {
                 leaf_content: Value::Object(Map::new()),
                 leaf_count,
                 has_nested_elements: true,
                 nested_groups: Some(nested),
-            };
+            });
         } else {
-            let _ = build_disassembled_file(crate::types::BuildDisassembledFileOptions {
+            build_disassembled_file(crate::types::BuildDisassembledFileOptions {
                 content: element.clone(),
                 disassembled_path,
                 output_file_name: None,
@@ -57,23 +66,26 @@ pub async fn parse_element_unified(params: XmlElementParams<'_>) -> UnifiedParse
                 format,
                 xml_declaration: xml_declaration.clone(),
                 unique_id_elements,
-            })
-            .await;
-            return UnifiedParseResult {
+                bare,
+                sidecars,
+                sink,
+                parts,
+            })?;
+            return Ok(UnifiedParseResult {
                 leaf_content: Value::Object(Map::new()),
                 leaf_count,
                 has_nested_elements: true,
                 nested_groups: None,
-            };
+            });
         }
     }
 
     let mut leaf_content = Map::new();
     leaf_content.insert(key.to_string(), Value::Array(vec![element]));
-    UnifiedParseResult {
+    Ok(UnifiedParseResult {
         leaf_content: Value::Object(leaf_content),
         leaf_count: leaf_count + 1,
         has_nested_elements,
         nested_groups: None,
-    }
+    })
 }
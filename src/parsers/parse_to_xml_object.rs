@@ -1,12 +1,13 @@
 //! Parse file to XmlElement - supports XML, YAML, JSON.
 
 use crate::parsers::{extract_xml_declaration_from_raw, parse_xml_from_str};
+use crate::storage::Storage;
 use crate::types::XmlElement;
-use tokio::fs;
+use std::path::Path;
 
-pub async fn parse_to_xml_object(file_path: &str) -> Option<XmlElement> {
+pub fn parse_to_xml_object(storage: &dyn Storage, file_path: &str) -> Option<XmlElement> {
     if file_path.to_lowercase().ends_with(".xml") {
-        let content = fs::read_to_string(file_path).await.ok()?;
+        let content = storage.read_to_string(Path::new(file_path)).ok()?;
         let mut parsed = parse_xml_from_str(&content, file_path)?;
         // The declaration is not preserved by the parser - extract from raw XML.
         // `parse_xml_from_str` always yields an object, so `as_object_mut` is safe.
@@ -19,7 +20,7 @@ pub async fn parse_to_xml_object(file_path: &str) -> Option<XmlElement> {
         return Some(parsed);
     }
 
-    let content = fs::read_to_string(file_path).await.ok()?;
+    let content = storage.read_to_string(Path::new(file_path)).ok()?;
 
     if file_path.to_lowercase().ends_with(".yaml") || file_path.to_lowercase().ends_with(".yml") {
         return serde_yaml::from_str(&content).ok();
@@ -36,64 +37,83 @@ pub async fn parse_to_xml_object(file_path: &str) -> Option<XmlElement> {
     None
 }
 
+/// Parse a bare part file (written without declaration and root wrapper) and wrap its content
+/// in `root_name` so it merges like a regular part. XML parts may hold several sibling elements.
+pub fn parse_bare_part_to_xml_object(
+    storage: &dyn Storage,
+    file_path: &str,
+    root_name: &str,
+) -> Option<XmlElement> {
+    if file_path.to_lowercase().ends_with(".xml") {
+        let content = storage.read_to_string(Path::new(file_path)).ok()?;
+        let wrapped = format!("<{}>\n{}\n</{}>", root_name, content, root_name);
+        return parse_xml_from_str(&wrapped, file_path);
+    }
+    let body = parse_to_xml_object(storage, file_path)?;
+    let mut root = serde_json::Map::new();
+    root.insert(root_name.to_string(), body);
+    Some(serde_json::Value::Object(root))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::{LocalStorage, MemoryStorage};
 
-    #[tokio::test]
-    async fn parse_to_xml_object_json() {
+    #[test]
+    fn parse_to_xml_object_json() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("test.json");
         std::fs::write(&path, r#"{"root":{"a":1}}"#).unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_some());
         let obj = out.unwrap();
         assert!(obj.get("root").is_some());
     }
 
-    #[tokio::test]
-    async fn parse_to_xml_object_yaml() {
+    #[test]
+    fn parse_to_xml_object_yaml() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("test.yaml");
         std::fs::write(&path, "root:\n  a: 1\n").unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_some());
     }
 
-    #[tokio::test]
-    async fn parse_to_xml_object_yml_extension() {
+    #[test]
+    fn parse_to_xml_object_yml_extension() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("test.yml");
         std::fs::write(&path, "root: {}").unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_some());
     }
 
-    #[tokio::test]
-    async fn parse_to_xml_object_json5() {
+    #[test]
+    fn parse_to_xml_object_json5() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("test.json5");
         std::fs::write(&path, "{ root: { a: 1 } }").unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_some());
     }
 
-    #[tokio::test]
-    async fn parse_to_xml_object_unsupported_returns_none() {
+    #[test]
+    fn parse_to_xml_object_unsupported_returns_none() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("test.txt");
         std::fs::write(&path, "not xml").unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_none());
     }
 
-    #[tokio::test]
-    async fn parse_to_xml_object_xml_with_declaration_and_xmlns() {
+    #[test]
+    fn parse_to_xml_object_xml_with_declaration_and_xmlns() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("meta.xml");
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?><root xmlns="http://example.com"><a>1</a></root>"#;
         std::fs::write(&path, xml).unwrap();
-        let out = parse_to_xml_object(path.to_str().unwrap()).await;
+        let out = parse_to_xml_object(&LocalStorage, path.to_str().unwrap());
         assert!(out.is_some());
         let obj = out.unwrap();
         assert!(obj.get("?xml").is_some());
@@ -103,4 +123,34 @@ mod tests {
             Some("http://example.com")
         );
     }
+
+    #[test]
+    fn parse_bare_part_wraps_siblings_in_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml_path = dir.path().join("tabs.xml");
+        std::fs::write(&xml_path, "<tabs>a</tabs>\n<tabs>b</tabs>").unwrap();
+        let out = parse_bare_part_to_xml_object(&LocalStorage, xml_path.to_str().unwrap(), "App")
+            .unwrap();
+        assert_eq!(
+            out["App"]["tabs"],
+            serde_json::json!([{ "#text": "a" }, { "#text": "b" }])
+        );
+
+        let json_path = dir.path().join("tabs.json");
+        std::fs::write(&json_path, r#"{"tabs":["a"]}"#).unwrap();
+        let out = parse_bare_part_to_xml_object(&LocalStorage, json_path.to_str().unwrap(), "App")
+            .unwrap();
+        assert_eq!(out["App"]["tabs"], serde_json::json!(["a"]));
+    }
+
+    #[test]
+    fn parse_to_xml_object_reads_through_storage() {
+        let storage = MemoryStorage::new();
+        storage
+            .insert("parts/a.json", r#"{"root":{"a":1}}"#)
+            .unwrap();
+        let out = parse_to_xml_object(&storage, "parts/a.json").unwrap();
+        assert_eq!(out["root"]["a"], 1);
+        assert!(parse_to_xml_object(&LocalStorage, "parts/a.json").is_none());
+    }
 }
@@ -140,6 +140,12 @@ mod tests {
         assert_eq!(id.len(), 8);
     }
 
+    #[test]
+    fn finds_attribute_field() {
+        let el = json!({ "@id": "item-7", "name": { "#text": "Seven" } });
+        assert_eq!(parse_unique_id_element(&el, Some("@id")), "item-7");
+    }
+
     #[test]
     fn finds_name_from_text_object() {
         // XML parser stores leaf elements as { "#text": "value" }
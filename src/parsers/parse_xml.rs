@@ -1,15 +1,16 @@
 //! Parse XML file from path into XmlElement structure.
 
 use serde_json::Value;
-use tokio::fs;
+use std::fs;
 
-use crate::parsers::parse_xml_cdata;
+use crate::limits::{LimitError, Limits};
+use crate::parsers::parse_xml_cdata::{self, ParseError};
 use crate::parsers::strip_whitespace_text_nodes;
 use crate::types::XmlElement;
 
 /// Parses an XML file from a path.
-pub async fn parse_xml(file_path: &str) -> Option<XmlElement> {
-    let content = match fs::read_to_string(file_path).await {
+pub fn parse_xml(file_path: &str) -> Option<XmlElement> {
+    let content = match fs::read_to_string(file_path) {
         Ok(c) => c,
         Err(e) => {
             log::error!(
@@ -25,21 +26,41 @@ pub async fn parse_xml(file_path: &str) -> Option<XmlElement> {
 
 /// Parses XML from a string. The file_path is used for error logging only.
 /// Uses custom parser that preserves CDATA sections (output as #cdata key).
+/// Documents nested deeper than [`Limits::default`] allows are logged and not processed.
 pub fn parse_xml_from_str(content: &str, file_path: &str) -> Option<XmlElement> {
-    let parsed: Value = match parse_xml_cdata::parse_xml_with_cdata(content) {
+    parse_xml_from_str_with_limits(content, file_path, &Limits::default()).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        None
+    })
+}
+
+/// Like [`parse_xml_from_str`] (malformed XML is logged and yields `Ok(None)`), but nesting
+/// deeper than `limits.max_depth` is returned as an error.
+pub fn parse_xml_from_str_with_limits(
+    content: &str,
+    file_path: &str,
+    limits: &Limits,
+) -> Result<Option<XmlElement>, LimitError> {
+    let parsed: Value = match parse_xml_cdata::parse_xml_with_cdata(content, limits.max_depth) {
         Ok(v) => v,
-        Err(e) => {
+        Err(ParseError::TooDeep) => {
+            return Err(LimitError::Depth {
+                file: file_path.to_string(),
+                max: limits.max_depth,
+            })
+        }
+        Err(ParseError::Xml(e)) => {
             log::error!(
                 "{} was unable to be parsed and will not be processed. Confirm formatting and try again.",
                 file_path
             );
             log::debug!("Parse error: {}", e);
-            return None;
+            return Ok(None);
         }
     };
 
     let cleaned = strip_whitespace_text_nodes(&parsed);
-    Some(cleaned)
+    Ok(Some(cleaned))
 }
 
 /// Extract xmlns attribute from raw XML (quickxml_to_serde drops it).
@@ -127,9 +148,27 @@ mod tests {
         assert!(result.is_none());
     }
 
-    #[tokio::test]
-    async fn parse_xml_missing_file_returns_none() {
-        let result = parse_xml("/nonexistent/path/file.xml").await;
+    #[test]
+    fn parse_xml_from_str_with_limits_reports_depth() {
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let err =
+            parse_xml_from_str_with_limits("<a><b><c/></b></a>", "deep.xml", &limits).unwrap_err();
+        assert_eq!(
+            err,
+            LimitError::Depth {
+                file: "deep.xml".to_string(),
+                max: 2
+            }
+        );
+        assert!(parse_xml_from_str("<a><b><c/></b></a>", "deep.xml").is_some());
+    }
+
+    #[test]
+    fn parse_xml_missing_file_returns_none() {
+        let result = parse_xml("/nonexistent/path/file.xml");
         assert!(result.is_none());
     }
 }
@@ -137,9 +137,18 @@ fn flush_text_buffer(
     }
 }
 
+/// Why [`parse_xml_with_cdata`] rejected a document.
+#[derive(Debug)]
+pub enum ParseError {
+    Xml(quick_xml::Error),
+    /// Elements nest deeper than the requested maximum.
+    TooDeep,
+}
+
 /// Parse XML string to JSON Value, preserving CDATA as #cdata key.
 /// Produces the same structure as quickxml_to_serde but with #cdata for CDATA content.
-pub fn parse_xml_with_cdata(xml: &str) -> Result<Value, quick_xml::Error> {
+/// Elements nested deeper than `max_depth` (the root is at depth 1) are an error.
+pub fn parse_xml_with_cdata(xml: &str, max_depth: usize) -> Result<Value, ParseError> {
     let mut reader = Reader::from_str(xml);
     reader.config_mut().trim_text(false);
 
@@ -153,6 +162,9 @@ pub fn parse_xml_with_cdata(xml: &str) -> Result<Value, quick_xml::Error> {
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(e)) => {
+                if stack.len() >= max_depth {
+                    return Err(ParseError::TooDeep);
+                }
                 flush_text_buffer(&mut text_buffer, &mut stack, text_buffer_after_comment);
                 text_buffer_after_comment = false;
                 let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
@@ -182,6 +194,9 @@ pub fn parse_xml_with_cdata(xml: &str) -> Result<Value, quick_xml::Error> {
                 }
             }
             Ok(Event::Empty(e)) => {
+                if stack.len() >= max_depth {
+                    return Err(ParseError::TooDeep);
+                }
                 flush_text_buffer(&mut text_buffer, &mut stack, text_buffer_after_comment);
                 text_buffer_after_comment = false;
                 let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
@@ -226,7 +241,7 @@ pub fn parse_xml_with_cdata(xml: &str) -> Result<Value, quick_xml::Error> {
             }
             Ok(Event::Eof) => break,
             Ok(_) => {}
-            Err(e) => return Err(e),
+            Err(e) => return Err(ParseError::Xml(e)),
         }
         buf.clear();
     }
@@ -266,7 +281,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_simple_element() {
         let xml = r#"<root><a>hello</a></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         let a = root.get("a").and_then(|a| a.as_object()).unwrap();
         assert_eq!(a.get("#text").and_then(|t| t.as_str()), Some("hello"));
@@ -275,7 +290,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_preserves_cdata() {
         let xml = r#"<root><x><![CDATA[<escaped>]]></x></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         let x = root.get("x").and_then(|x| x.as_object()).unwrap();
         assert_eq!(x.get("#cdata").and_then(|c| c.as_str()), Some("<escaped>"));
@@ -284,7 +299,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_empty_element() {
         let xml = r#"<root><empty/></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         assert!(root.get("empty").is_some());
     }
@@ -292,7 +307,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_comment() {
         let xml = r#"<root><!-- comment --><a>1</a></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         assert!(root.get("#comment").or(root.get("a")).is_some());
     }
@@ -300,7 +315,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_attributes() {
         let xml = r#"<root id="x"><a>1</a></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         assert_eq!(root.get("@id").and_then(|v| v.as_str()), Some("x"));
     }
@@ -308,7 +323,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_multiple_children() {
         let xml = r#"<r><n>42</n><b>true</b></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         assert!(r.get("n").is_some());
         assert!(r.get("b").is_some());
@@ -317,7 +332,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_text_tail_after_comment() {
         let xml = r#"<r><!-- comment -->tail</r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         assert_eq!(
             r.get("#comment").and_then(|c| c.as_str()),
@@ -329,7 +344,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_empty_root_returns_empty_object() {
         let xml = r#"<root></root>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         assert!(root.is_empty());
     }
@@ -338,7 +353,7 @@ mod tests {
     fn parse_xml_with_cdata_mixed_content_appends_text() {
         // Two text nodes in same element (e.g. <a>hello</a><b/> then text "world" in same parent)
         let xml = r#"<r><a>hello<x/>world</a></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         let a = r.get("a").and_then(|a| a.as_object()).unwrap();
         // First text "hello", then after <x/>, text "world" appends to #text
@@ -350,7 +365,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_appends_multiple_cdata_sections() {
         let xml = r#"<r><x><![CDATA[a]]><![CDATA[b]]></x></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let x = v
             .get("r")
             .and_then(|r| r.get("x"))
@@ -361,14 +376,14 @@ mod tests {
 
     #[test]
     fn parse_xml_with_cdata_invalid_returns_err() {
-        let result = parse_xml_with_cdata("<<");
+        let result = parse_xml_with_cdata("<<", usize::MAX);
         assert!(result.is_err());
     }
 
     #[test]
     fn parse_xml_with_cdata_duplicate_sibling_elements_become_array() {
         let xml = r#"<r><item>a</item><item>b</item></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         let items = r.get("item").and_then(|i| i.as_array()).unwrap();
         assert_eq!(items.len(), 2);
@@ -379,7 +394,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_empty_element_with_attributes() {
         let xml = r#"<r><empty id="x"/></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let empty = v
             .get("r")
             .and_then(|r| r.get("empty"))
@@ -419,7 +434,7 @@ mod tests {
     fn parse_xml_with_cdata_duplicate_empty_siblings_become_array() {
         // Two empty elements with same name: second triggers remove+insert Array (Event::End path)
         let xml = r#"<r><a/><a/></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         let arr = r.get("a").and_then(|a| a.as_array()).unwrap();
         assert_eq!(arr.len(), 2);
@@ -441,7 +456,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_malformed_entity_between_tags_is_dropped() {
         // A bare `&;` unescapes to an empty string - second empty-check returns without insert.
-        let v = parse_xml_with_cdata(r#"<r>&;</r>"#).unwrap();
+        let v = parse_xml_with_cdata(r#"<r>&;</r>"#, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         // Text collapses to nothing - no #text key
         assert!(r.get("#text").is_none());
@@ -450,7 +465,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_empty_root_element() {
         // Self-closing root: Event::Empty at the top level (no parent on stack).
-        let v = parse_xml_with_cdata("<root/>").unwrap();
+        let v = parse_xml_with_cdata("<root/>", usize::MAX).unwrap();
         let root = v.get("root").and_then(|r| r.as_object()).unwrap();
         assert!(root.is_empty());
     }
@@ -458,7 +473,7 @@ mod tests {
     #[test]
     fn parse_xml_with_cdata_three_empty_siblings_extend_array() {
         // Third duplicate empty sibling extends the existing array.
-        let v = parse_xml_with_cdata("<r><a/><a/><a/></r>").unwrap();
+        let v = parse_xml_with_cdata("<r><a/><a/><a/></r>", usize::MAX).unwrap();
         let arr = v
             .get("r")
             .and_then(|r| r.get("a"))
@@ -471,7 +486,7 @@ mod tests {
     fn parse_xml_with_cdata_text_tail_appended_after_second_comment() {
         // Comment then text (#text-tail), then comment then text -> append to #text-tail
         let xml = r#"<r><!--c1-->t1<!--c2-->t2</r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         assert_eq!(r.get("#text-tail").and_then(|t| t.as_str()), Some("t1t2"));
     }
@@ -480,7 +495,7 @@ mod tests {
     fn parse_xml_with_cdata_empty_document_returns_empty_object() {
         // Eof with no root (e.g. empty or only whitespace) -> empty object
         let xml = r#""#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         assert!(v.as_object().unwrap().is_empty());
     }
 
@@ -488,7 +503,7 @@ mod tests {
     fn parse_xml_with_cdata_unescapes_entities_in_text() {
         // quick-xml 0.38+ emits entities as Event::GeneralRef; we resolve and append.
         let xml = r#"<r><expr>IF(x, &quot;created&quot;, &quot;updated&quot;)</expr></r>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let r = v.get("r").and_then(|r| r.as_object()).unwrap();
         let expr = r.get("expr").and_then(|e| e.as_object()).unwrap();
         let text = expr.get("#text").and_then(|t| t.as_str()).unwrap();
@@ -500,7 +515,7 @@ mod tests {
     fn parse_xml_with_cdata_preserves_space_after_comma_in_entities() {
         // Fixture format: comma space before second entity - must preserve for round-trip
         let xml = r#"<e>IF(a, &quot;x&quot;, &quot;y&quot;)</e>"#;
-        let v = parse_xml_with_cdata(xml).unwrap();
+        let v = parse_xml_with_cdata(xml, usize::MAX).unwrap();
         let e = v.get("e").and_then(|e| e.as_object()).unwrap();
         let text = e.get("#text").and_then(|t| t.as_str()).unwrap();
         assert_eq!(
@@ -508,4 +523,18 @@ mod tests {
             "space after comma must be preserved"
         );
     }
+
+    #[test]
+    fn parse_xml_with_cdata_rejects_elements_deeper_than_max_depth() {
+        let xml = "<a><b><c/></b></a>";
+        assert!(parse_xml_with_cdata(xml, 3).is_ok());
+        assert!(matches!(
+            parse_xml_with_cdata(xml, 2),
+            Err(ParseError::TooDeep)
+        ));
+        assert!(matches!(
+            parse_xml_with_cdata("<a><b><c>x</c></b></a>", 2),
+            Err(ParseError::TooDeep)
+        ));
+    }
 }
@@ -2,57 +2,126 @@
 
 use serde_json::{Map, Value};
 
+use crate::sidecar::SIDECAR_ATTR;
+
 fn is_empty_text_node(key: &str, value: &Value) -> bool {
     (key == "#text" || key == "#cdata" || key == "#text-tail")
         && value.as_str().map(|s| s.trim().is_empty()).unwrap_or(false)
 }
 
-fn clean_array(arr: &[Value]) -> Vec<Value> {
-    arr.iter()
-        .filter_map(|entry| {
-            let cleaned = strip_whitespace_text_nodes(entry);
-            match &cleaned {
-                Value::Object(m) if m.is_empty() => None,
-                _ => Some(cleaned),
-            }
-        })
-        .collect()
+/// A container being cleaned: its entries still to visit and the cleaned result so far.
+enum Frame<'a> {
+    Array(std::slice::Iter<'a, Value>, Vec<Value>),
+    Object {
+        entries: serde_json::map::Iter<'a>,
+        result: Map<String, Value>,
+        /// Key of the entry being cleaned below this frame.
+        key: Option<&'a String>,
+        // An element whose CDATA moved to a sidecar keeps the same formatting text as one with #cdata.
+        has_cdata: bool,
+        has_comment: bool,
+    },
 }
 
-fn clean_object(obj: &Map<String, Value>) -> Map<String, Value> {
-    let mut result = Map::new();
-    let has_cdata = obj.contains_key("#cdata");
-    let has_comment = obj.contains_key("#comment");
-    for (key, value) in obj {
-        // Preserve whitespace-only #text when element has #cdata (needed for round-trip)
-        // Preserve whitespace-only #text and #text-tail when element has #comment
-        if is_empty_text_node(key, value)
-            && !(key == "#text" && has_cdata)
-            && !(key == "#text" && has_comment)
-            && !(key == "#text-tail" && has_comment)
-        {
-            continue;
+impl<'a> Frame<'a> {
+    fn open(node: &'a Value) -> Option<Self> {
+        match node {
+            Value::Array(arr) => Some(Frame::Array(arr.iter(), Vec::with_capacity(arr.len()))),
+            Value::Object(obj) => Some(Frame::Object {
+                entries: obj.iter(),
+                result: Map::new(),
+                key: None,
+                has_cdata: obj.contains_key("#cdata") || obj.contains_key(SIDECAR_ATTR),
+                has_comment: obj.contains_key("#comment"),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The next entry to clean, skipping whitespace-only text nodes that are dropped.
+    fn next_child(&mut self) -> Option<&'a Value> {
+        match self {
+            Frame::Array(items, _) => items.next(),
+            Frame::Object {
+                entries,
+                key,
+                has_cdata,
+                has_comment,
+                ..
+            } => {
+                for (k, value) in entries.by_ref() {
+                    // Preserve whitespace-only #text when element has #cdata (needed for round-trip)
+                    // Preserve whitespace-only #text and #text-tail when element has #comment
+                    if is_empty_text_node(k, value)
+                        && !(k == "#text" && *has_cdata)
+                        && !(k == "#text" && *has_comment)
+                        && !(k == "#text-tail" && *has_comment)
+                    {
+                        continue;
+                    }
+                    *key = Some(k);
+                    return Some(value);
+                }
+                None
+            }
         }
-        let cleaned = strip_whitespace_text_nodes(value);
-        if !cleaned.is_null()
-            || key == "#text"
-            || key == "#cdata"
-            || key == "#comment"
-            || key == "#text-tail"
-        {
-            result.insert(key.clone(), cleaned);
+    }
+
+    /// Add the cleaned value of the entry returned by the last [`Self::next_child`].
+    fn accept(&mut self, cleaned: Value) {
+        match self {
+            Frame::Array(_, result) => {
+                if !matches!(&cleaned, Value::Object(m) if m.is_empty()) {
+                    result.push(cleaned);
+                }
+            }
+            Frame::Object { result, key, .. } => {
+                let Some(key) = key.take() else {
+                    return;
+                };
+                if !cleaned.is_null()
+                    || key == "#text"
+                    || key == "#cdata"
+                    || key == "#comment"
+                    || key == "#text-tail"
+                {
+                    result.insert(key.clone(), cleaned);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Frame::Array(_, result) => Value::Array(result),
+            Frame::Object { result, .. } => Value::Object(result),
         }
     }
-    result
 }
 
 /// Remove meaningless whitespace-only #text nodes from the XML structure.
+/// Walks the tree with an explicit stack, so deeply nested input cannot overflow the call stack.
 pub fn strip_whitespace_text_nodes(node: &Value) -> Value {
-    match node {
-        Value::Array(arr) => Value::Array(clean_array(arr)),
-        Value::Object(obj) => Value::Object(clean_object(obj)),
-        other => other.clone(),
+    let Some(root) = Frame::open(node) else {
+        return node.clone();
+    };
+    let mut stack = vec![root];
+    let mut cleaned = Value::Null;
+    while let Some(top) = stack.last_mut() {
+        if let Some(child) = top.next_child() {
+            match Frame::open(child) {
+                Some(frame) => stack.push(frame),
+                None => top.accept(child.clone()),
+            }
+            continue;
+        }
+        let done = stack.pop().map(Frame::finish).unwrap_or_default();
+        match stack.last_mut() {
+            Some(parent) => parent.accept(done),
+            None => cleaned = done,
+        }
     }
+    cleaned
 }
 
 #[cfg(test)]
@@ -121,4 +190,28 @@ mod tests {
         assert!(obj.get("#text-tail").map(|v| v.is_null()) == Some(true));
         assert_eq!(obj.get("a").and_then(|v| v.as_str()), Some("b"));
     }
+
+    #[test]
+    fn deeply_nested_input_does_not_overflow_the_stack() {
+        let mut node = json!({ "#text": " " });
+        for _ in 0..100_000 {
+            let mut obj = Map::new();
+            obj.insert("a".to_string(), Value::Array(vec![node]));
+            obj.insert("#text".to_string(), json!("\n  "));
+            node = Value::Object(obj);
+        }
+        let mut cleaned = strip_whitespace_text_nodes(&node);
+        // Taken apart level by level: dropping it whole would recurse as deep as it nests.
+        let mut depth = 0;
+        while let Value::Object(mut obj) = cleaned {
+            assert!(!obj.contains_key("#text"));
+            cleaned = match obj.remove("a") {
+                Some(Value::Array(mut arr)) => arr.pop().unwrap_or(Value::Null),
+                _ => Value::Null,
+            };
+            depth += 1;
+        }
+        assert_eq!(depth, 100_000);
+        std::mem::forget(node);
+    }
 }
@@ -0,0 +1,131 @@
+//! Per-run outcome of a disassembly or reassembly.
+
+use crate::error::Error;
+use crate::observer::{Event, Observer};
+use std::fmt;
+use std::path::PathBuf;
+
+/// What happened to each source file (disassembly) or directory (reassembly) of a run, from
+/// [`crate::DisassembleXmlFileHandler::take_report`] or
+/// [`crate::ReassembleXmlFileHandler::take_report`]. Every list is sorted by path.
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    /// Processed successfully.
+    pub processed: Vec<PathBuf>,
+    /// Left out by the ignore file.
+    pub ignored: Vec<PathBuf>,
+    /// Not processed, with the reason (only outside strict mode).
+    pub skipped: Vec<(PathBuf, Error)>,
+    /// Failed the run, with the error.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl RunReport {
+    /// Record the outcome of one file or directory. A skip ([`Error::is_skip`]) is listed as
+    /// skipped unless `strict`; any other error fails it. Skips and failures are also sent to
+    /// `observer`. Returns the error failing the run, if any.
+    pub(crate) fn record(
+        &mut self,
+        path: impl Into<PathBuf>,
+        result: Result<(), Error>,
+        strict: bool,
+        observer: Option<&dyn Observer>,
+    ) -> Result<(), Error> {
+        let path = path.into();
+        let notify = |event: Event| {
+            if let Some(observer) = observer {
+                observer.on_event(&event);
+            }
+        };
+        match result {
+            Ok(()) => {
+                self.processed.push(path);
+                Ok(())
+            }
+            Err(skip) if skip.is_skip() && !strict => {
+                log::error!("{}", skip);
+                notify(Event::Skipped {
+                    path: path.clone(),
+                    reason: skip.clone(),
+                });
+                self.skipped.push((path, skip));
+                Ok(())
+            }
+            Err(err) => {
+                notify(Event::Failed {
+                    path: path.clone(),
+                    error: err.to_string(),
+                });
+                self.failed.push((path, err.to_string()));
+                Err(err)
+            }
+        }
+    }
+
+    /// The run failed and was rolled back: nothing it processed was kept, so every processed
+    /// file or directory moves to `failed`.
+    pub(crate) fn roll_back(&mut self) {
+        for path in self.processed.drain(..) {
+            self.failed
+                .push((path, "rolled back because the run failed".to_string()));
+        }
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.processed.sort();
+        self.ignored.sort();
+        self.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        self.failed.sort();
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} processed, {} ignored, {} skipped, {} failed",
+            self.processed.len(),
+            self.ignored.len(),
+            self.skipped.len(),
+            self.failed.len()
+        )?;
+        for (path, reason) in &self.skipped {
+            writeln!(f, "  skipped {}: {}", path.display(), reason)?;
+        }
+        for (path, error) in &self.failed {
+            writeln!(f, "  failed {}: {}", path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_sorts_outcomes_by_strictness() {
+        let leaf_only = || Error::LeafOnly {
+            path: PathBuf::from("b.xml"),
+        };
+        let mut report = RunReport::default();
+        assert!(report.record("a.xml", Ok(()), false, None).is_ok());
+        assert!(report
+            .record("b.xml", Err(leaf_only()), false, None)
+            .is_ok());
+        assert!(report
+            .record("c.xml", Err(Error::Cancelled), false, None)
+            .is_err());
+        let err = report
+            .record("b.xml", Err(leaf_only()), true, None)
+            .unwrap_err();
+        assert!(matches!(err, Error::LeafOnly { .. }));
+
+        assert_eq!(report.processed, vec![PathBuf::from("a.xml")]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.failed.len(), 2);
+        assert!(report
+            .to_string()
+            .starts_with("1 processed, 0 ignored, 1 skipped, 2 failed\n"));
+    }
+}
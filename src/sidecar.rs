@@ -0,0 +1,378 @@
+//! Sidecar files: move the text or CDATA content of chosen tags (HTML, scripts, queries, base64
+//! binaries) into raw files next to the part, and inline it again on reassembly.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+use crate::storage::Storage;
+use crate::types::{SidecarRule, XmlElement};
+
+/// Attribute holding the sidecar file name on the element whose content was extracted.
+pub const SIDECAR_ATTR: &str = "@xd-sidecar";
+/// Attribute recording where the content came from: "cdata" or "text", suffixed with "-base64"
+/// when the sidecar holds decoded bytes.
+pub const SIDECAR_KIND_ATTR: &str = "@xd-sidecar-kind";
+/// Infix in every sidecar file name, so reassembly never parses a sidecar (e.g. a `.json` one) as a part.
+pub const SIDECAR_INFIX: &str = ".sidecar.";
+
+/// Whether `name` is named like a sidecar of one of the files in `siblings` (the other file names
+/// in its directory): `{stem}.{tag}[-n].sidecar.{ext}`, where `{stem}` is the stem of a sibling
+/// part. A part whose unique id merely contains `.sidecar.` is not one.
+pub fn is_sidecar_file(name: &str, siblings: &[&str]) -> bool {
+    let Some((prefix, extension)) = name.rsplit_once(SIDECAR_INFIX) else {
+        return false;
+    };
+    if extension.is_empty() || extension.contains('.') {
+        return false;
+    }
+    let Some((stem, tag)) = prefix.rsplit_once('.') else {
+        return false;
+    };
+    !tag.is_empty()
+        && siblings.iter().any(|sibling| {
+            *sibling != name
+                && Path::new(sibling).file_stem().and_then(|s| s.to_str()) == Some(stem)
+        })
+}
+
+/// Whether a sidecar reference is a bare file name (one normal path component), so it can only
+/// name a file next to the part.
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !name.contains(['/', '\\'])
+}
+
+/// Sidecar file names a parsed part refers to; references that are not bare file names are
+/// left out.
+pub fn sidecar_references(element: &XmlElement) -> Vec<String> {
+    let mut names = Vec::new();
+    collect_references(element, &mut names);
+    names.retain(|name| is_plain_file_name(name));
+    names
+}
+
+/// Replace the content of elements matching `rules` in `body` with sidecar references.
+/// Returns the sidecar files to write next to the part as (file name, bytes).
+/// Names are `{part_stem}.{tag}.sidecar.{ext}`, with `-2`, `-3`… after the tag for repeats.
+pub fn extract_sidecars(
+    body: &mut Map<String, Value>,
+    rules: &[SidecarRule],
+    part_stem: &str,
+) -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut counts = HashMap::new();
+    extract_in_map(body, rules, part_stem, &mut counts, &mut files);
+    files
+}
+
+fn extract_in_map(
+    map: &mut Map<String, Value>,
+    rules: &[SidecarRule],
+    part_stem: &str,
+    counts: &mut HashMap<String, usize>,
+    files: &mut Vec<(String, Vec<u8>)>,
+) {
+    for (key, value) in map.iter_mut() {
+        if key.starts_with('@') || key.starts_with('#') {
+            continue;
+        }
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    visit(key, item, rules, part_stem, counts, files);
+                }
+            }
+            other => visit(key, other, rules, part_stem, counts, files),
+        }
+    }
+}
+
+/// Extract `value` when a rule matches `tag`; otherwise search its children.
+fn visit(
+    tag: &str,
+    value: &mut Value,
+    rules: &[SidecarRule],
+    part_stem: &str,
+    counts: &mut HashMap<String, usize>,
+    files: &mut Vec<(String, Vec<u8>)>,
+) {
+    let rule = rules.iter().find(|r| r.tag == tag);
+    if let Some(file) = rule.and_then(|r| extract_one(tag, value, r, part_stem, counts)) {
+        files.push(file);
+        return;
+    }
+    if let Value::Object(child) = value {
+        extract_in_map(child, rules, part_stem, counts, files);
+    }
+}
+
+fn extract_one(
+    tag: &str,
+    value: &mut Value,
+    rule: &SidecarRule,
+    part_stem: &str,
+    counts: &mut HashMap<String, usize>,
+) -> Option<(String, Vec<u8>)> {
+    let (content_key, kind) = match &*value {
+        Value::Object(obj) if obj.contains_key("#cdata") => ("#cdata", "cdata"),
+        Value::Object(obj) if obj.contains_key("#text") => ("#text", "text"),
+        Value::String(_) => ("", "text"),
+        _ => return None,
+    };
+    let text = if content_key.is_empty() {
+        value.as_str()
+    } else {
+        value.get(content_key).and_then(|v| v.as_str())
+    }?
+    .to_string();
+    if text.is_empty() || text.len() < rule.min_len {
+        return None;
+    }
+
+    let (bytes, kind) = if rule.extension == "bin" {
+        let decoded = STANDARD.decode(text.as_bytes()).ok()?;
+        // Only extract when encoding the bytes again gives back exactly the original text.
+        if STANDARD.encode(&decoded) != text {
+            return None;
+        }
+        (decoded, format!("{}-base64", kind))
+    } else {
+        (text.into_bytes(), kind.to_string())
+    };
+
+    let count = counts.entry(tag.to_string()).or_insert(0);
+    *count += 1;
+    let name = if *count == 1 {
+        format!("{}.{}{}{}", part_stem, tag, SIDECAR_INFIX, rule.extension)
+    } else {
+        format!(
+            "{}.{}-{}{}{}",
+            part_stem, tag, count, SIDECAR_INFIX, rule.extension
+        )
+    };
+
+    let mut obj = match value.take() {
+        Value::Object(obj) => obj,
+        _ => Map::new(),
+    };
+    obj.shift_remove(content_key);
+    obj.insert(SIDECAR_ATTR.to_string(), Value::String(name.clone()));
+    obj.insert(SIDECAR_KIND_ATTR.to_string(), Value::String(kind));
+    *value = Value::Object(obj);
+    Some((name, bytes))
+}
+
+/// Replace sidecar references in a parsed part with the content of the files in `dir`.
+/// A missing sidecar is logged and its reference left in place. Parts are untrusted input: a
+/// reference that is not a bare file name (`../x`, an absolute path) is logged and never read.
+pub fn inline_sidecars(storage: &dyn Storage, element: &mut XmlElement, dir: &Path) {
+    let mut names = Vec::new();
+    collect_references(element, &mut names);
+    if names.is_empty() {
+        return;
+    }
+    let mut contents = HashMap::new();
+    for name in names {
+        if !is_plain_file_name(&name) {
+            log::error!(
+                "Sidecar reference \"{}\" in {} is not a plain file name; it is kept as is.",
+                name,
+                dir.display()
+            );
+            continue;
+        }
+        match storage.read(&dir.join(&name)) {
+            Ok(bytes) => {
+                contents.insert(name, bytes);
+            }
+            Err(_) => log::error!(
+                "Sidecar file {} is missing; its reference is kept as is.",
+                dir.join(&name).display()
+            ),
+        }
+    }
+    restore_references(element, &contents);
+}
+
+fn collect_references(value: &Value, names: &mut Vec<String>) {
+    match value {
+        Value::Object(obj) => {
+            if let Some(name) = obj.get(SIDECAR_ATTR).and_then(|v| v.as_str()) {
+                names.push(name.to_string());
+            }
+            for child in obj.values() {
+                collect_references(child, names);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_references(item, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn restore_references(value: &mut Value, contents: &HashMap<String, Vec<u8>>) {
+    match value {
+        Value::Object(obj) => {
+            let bytes = obj
+                .get(SIDECAR_ATTR)
+                .and_then(|v| v.as_str())
+                .and_then(|name| contents.get(name));
+            if let Some(bytes) = bytes {
+                let kind = obj
+                    .get(SIDECAR_KIND_ATTR)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("text")
+                    .to_string();
+                let (source, is_base64) = match kind.strip_suffix("-base64") {
+                    Some(source) => (source, true),
+                    None => (kind.as_str(), false),
+                };
+                let text = if is_base64 {
+                    STANDARD.encode(bytes)
+                } else {
+                    String::from_utf8_lossy(bytes).into_owned()
+                };
+                let key = if source == "cdata" { "#cdata" } else { "#text" };
+                obj.shift_remove(SIDECAR_ATTR);
+                obj.shift_remove(SIDECAR_KIND_ATTR);
+                obj.insert(key.to_string(), Value::String(text));
+            }
+            for child in obj.values_mut() {
+                restore_references(child, contents);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                restore_references(item, contents);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(tag: &str, extension: &str) -> SidecarRule {
+        SidecarRule {
+            tag: tag.to_string(),
+            extension: extension.to_string(),
+            min_len: 0,
+        }
+    }
+
+    #[test]
+    fn extract_sidecars_replaces_content_with_references() {
+        let mut body = json!({
+            "actions": [
+                { "schema": { "#cdata": "{ \"a\": 1 }" }, "name": "one" },
+                { "schema": { "#cdata": "{}" }, "name": "two" }
+            ],
+            "query": "SELECT Id FROM Account",
+            "logo": { "@type": "png", "#text": "aGVsbG8=" },
+            "wrapped": { "#text": "aGVs\nbG8=" }
+        });
+        let rules = [
+            rule("schema", "json"),
+            rule("query", "soql"),
+            rule("logo", "bin"),
+            rule("wrapped", "bin"),
+        ];
+        let files = extract_sidecars(body.as_object_mut().unwrap(), &rules, "App.meta");
+        let names: Vec<&str> = files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "App.meta.schema.sidecar.json",
+                "App.meta.schema-2.sidecar.json",
+                "App.meta.query.sidecar.soql",
+                "App.meta.logo.sidecar.bin",
+            ]
+        );
+        assert_eq!(files[3].1, b"hello");
+        assert_eq!(
+            body["actions"][1]["schema"],
+            json!({ "@xd-sidecar": "App.meta.schema-2.sidecar.json", "@xd-sidecar-kind": "cdata" })
+        );
+        assert_eq!(body["logo"]["@type"], "png");
+        assert_eq!(body["logo"]["@xd-sidecar-kind"], "text-base64");
+        // Base64 that does not re-encode to the same text stays inline.
+        assert_eq!(body["wrapped"]["#text"], "aGVs\nbG8=");
+    }
+
+    #[test]
+    fn extract_sidecars_respects_min_len() {
+        let mut body = json!({ "html": { "#cdata": "<p/>" } });
+        let rules = [SidecarRule {
+            min_len: 10,
+            ..rule("html", "html")
+        }];
+        assert!(extract_sidecars(body.as_object_mut().unwrap(), &rules, "p").is_empty());
+        assert_eq!(body["html"]["#cdata"], "<p/>");
+    }
+
+    #[test]
+    fn inline_sidecars_restores_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = json!({
+            "Root": {
+                "html": { "#cdata": "<b>hi</b>\n" },
+                "logo": { "#text": "aGVsbG8=" }
+            }
+        });
+        let mut element = original.clone();
+        let root = element.get_mut("Root").unwrap().as_object_mut().unwrap();
+        let files = extract_sidecars(root, &[rule("html", "html"), rule("logo", "bin")], "p");
+        for (name, bytes) in files {
+            std::fs::write(dir.path().join(name), bytes).unwrap();
+        }
+        inline_sidecars(&crate::storage::LocalStorage, &mut element, dir.path());
+        assert_eq!(element, original);
+    }
+
+    #[test]
+    fn inline_sidecars_never_reads_outside_the_part_directory() {
+        let storage = crate::storage::MemoryStorage::new();
+        storage.insert("secret", "top secret").unwrap();
+        storage.insert("parts/ok.sidecar.txt", "fine").unwrap();
+        let mut element = json!({
+            "Root": {
+                "up": { "@xd-sidecar": "../secret", "@xd-sidecar-kind": "text" },
+                "abs": { "@xd-sidecar": "/secret", "@xd-sidecar-kind": "text" },
+                "ok": { "@xd-sidecar": "ok.sidecar.txt", "@xd-sidecar-kind": "text" }
+            }
+        });
+        assert_eq!(sidecar_references(&element), vec!["ok.sidecar.txt"]);
+        inline_sidecars(&storage, &mut element, Path::new("parts"));
+        assert_eq!(element["Root"]["up"]["@xd-sidecar"], "../secret");
+        assert_eq!(element["Root"]["abs"]["@xd-sidecar"], "/secret");
+        assert_eq!(element["Root"]["ok"], json!({ "#text": "fine" }));
+    }
+
+    #[test]
+    fn is_sidecar_file_needs_the_writer_naming_and_a_part() {
+        let siblings = [
+            "App.meta.xml",
+            "App.meta.schema.sidecar.json",
+            "App.meta.schema-2.sidecar.json",
+            "x.sidecar.y.item-meta.xml",
+            "orphan.tag.sidecar.json",
+        ];
+        assert!(is_sidecar_file("App.meta.schema.sidecar.json", &siblings));
+        assert!(is_sidecar_file("App.meta.schema-2.sidecar.json", &siblings));
+        assert!(!is_sidecar_file("App.meta.xml", &siblings));
+        assert!(!is_sidecar_file("x.sidecar.y.item-meta.xml", &siblings));
+        assert!(!is_sidecar_file("orphan.tag.sidecar.json", &siblings));
+    }
+}
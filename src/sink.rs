@@ -0,0 +1,1172 @@
+//! Output sink: every file a disassembly or reassembly writes or deletes goes through an
+//! [`OutputSink`], which records the change (for manifests and reports) and, in dry-run mode,
+//! skips touching the file system.
+//!
+//! Writers take an `Option<&OutputSink>`; with `None` the helpers below just perform the
+//! operation on the local file system. A sink performs its operations on its [`Storage`].
+//!
+//! Handlers run each call as a transaction ([`OutputSink::begin`] .. [`OutputSink::finish`]):
+//! each changed file is written to a hidden sibling and renamed into place on commit, and
+//! deletions are deferred to the commit, so nothing the user sees changes until every write
+//! succeeded. Unchanged files are never touched. A failed run removes what it staged and leaves
+//! the tree as it was.
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::error::Error;
+use crate::observer::{Event, Observer};
+use crate::storage::{LocalStorage, Metadata, Storage};
+use crate::utils::relative_files;
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Counter making staged, backup and temporary names unique within the process.
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Hidden sibling of `path` named `.{name}.{label}-{pid}-{n}` (directory walks skip it).
+fn sibling_temp(path: &Path, label: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let n = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}-{}", name, label, std::process::id(), n))
+}
+
+/// Replace `path` with `contents` by writing a temporary sibling and renaming it over the file:
+/// readers never see a partial file, and hard-linked copies keep the old content.
+pub fn replace_file(
+    storage: &dyn Storage,
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    let temp = sibling_temp(path, "tmp");
+    let result = match storage.write(&temp, contents.as_ref()) {
+        Ok(()) => storage.rename(&temp, path),
+        Err(err) => Err(err),
+    };
+    if result.is_err() {
+        storage.remove_file(&temp).ok();
+    }
+    result
+}
+
+/// Remove the directories from `path`'s parent up to `root` that are empty (`root` itself only
+/// with `include_root`).
+fn remove_empty_parents(storage: &dyn Storage, path: &Path, root: &Path, include_root: bool) {
+    let mut current = path.parent();
+    while let Some(dir) = current.filter(|d| d.starts_with(root) && (include_root || *d != root)) {
+        // remove_dir fails on non-empty directories, which ends the walk.
+        if storage.remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+/// What a run does (or would do) to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Overwrite,
+    /// The file exists with exactly the content written.
+    Unchanged,
+    Delete,
+}
+
+impl ChangeKind {
+    fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Create => "create",
+            ChangeKind::Overwrite => "overwrite",
+            ChangeKind::Unchanged => "unchanged",
+            ChangeKind::Delete => "delete",
+        }
+    }
+}
+
+/// One recorded change. `diff` holds a unified diff against the previous file for overwrites
+/// recorded with a diff (reassembly output).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    pub diff: Option<String>,
+}
+
+/// Number of paths per [`ChangeKind`] in a set of changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeCounts {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+}
+
+impl ChangeCounts {
+    pub fn from_changes(changes: &[PlannedChange]) -> Self {
+        let mut counts = Self::default();
+        for change in changes {
+            match change.kind {
+                ChangeKind::Create => counts.created += 1,
+                ChangeKind::Overwrite => counts.updated += 1,
+                ChangeKind::Unchanged => counts.unchanged += 1,
+                ChangeKind::Delete => counts.deleted += 1,
+            }
+        }
+        counts
+    }
+}
+
+impl fmt::Display for ChangeCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} unchanged, {} deleted",
+            self.created, self.updated, self.unchanged, self.deleted
+        )
+    }
+}
+
+/// Hex SHA-256 of `contents`, as stored in hash indexes.
+pub fn content_hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The state a transaction (or dry run) leaves a path in, applied on commit.
+#[derive(Debug)]
+enum Staged {
+    /// New content, written to this hidden sibling and renamed over the path.
+    File(PathBuf),
+    /// Written in dry-run mode: the content the path would get.
+    Planned(Arc<[u8]>),
+    /// The file or directory tree is deleted.
+    Deleted,
+}
+
+/// Where the content of a path is as a transaction (or dry run) leaves it.
+#[derive(Debug)]
+enum Content {
+    /// In this file: the path itself or its staged sibling.
+    File(PathBuf),
+    /// Only planned by a dry run.
+    Planned(Arc<[u8]>),
+}
+
+#[derive(Debug, Default)]
+struct Transaction {
+    active: bool,
+    /// Every path the transaction writes or deletes, sorted so that a directory precedes its
+    /// contents.
+    staged: BTreeMap<PathBuf, Staged>,
+    /// Directories the transaction created (removed on rollback).
+    created: Vec<PathBuf>,
+    /// Work copies written directly, never recorded, and removed when the transaction ends.
+    scratch: Vec<PathBuf>,
+    /// `(path, root, include_root)` of deferred deletions whose emptied parents are pruned after
+    /// the commit.
+    prune: Vec<(PathBuf, PathBuf, bool)>,
+}
+
+impl Transaction {
+    fn in_scratch(&self, path: &Path) -> bool {
+        self.scratch.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Whether operations on `path` are staged rather than performed right away.
+    fn stages(&self, path: &Path) -> bool {
+        self.active && !self.in_scratch(path)
+    }
+
+    /// Where the content of `path` is as the transaction leaves it: the staged sibling or
+    /// planned content of a written file, nothing for a deleted one (or one below a deleted
+    /// directory), otherwise `path` itself.
+    fn view(&self, path: &Path) -> Option<Content> {
+        match self.staged.get(path) {
+            Some(Staged::File(staged)) => return Some(Content::File(staged.clone())),
+            Some(Staged::Planned(contents)) => return Some(Content::Planned(contents.clone())),
+            Some(Staged::Deleted) => return None,
+            None => {}
+        }
+        let deleted = path
+            .ancestors()
+            .skip(1)
+            .any(|dir| matches!(self.staged.get(dir), Some(Staged::Deleted)));
+        (!deleted).then(|| Content::File(path.to_path_buf()))
+    }
+
+    /// Drop what is staged at and below `path`, removing the staged siblings.
+    fn unstage(&mut self, storage: &dyn Storage, path: &Path) {
+        let below: Vec<PathBuf> = self
+            .staged
+            .range(path.to_path_buf()..)
+            .take_while(|(p, _)| p.starts_with(path))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for p in below {
+            if let Some(Staged::File(staged)) = self.staged.remove(&p) {
+                storage.remove_file(&staged).ok();
+            }
+        }
+    }
+
+    /// Remove the staged siblings, scratch copies and created directories.
+    fn discard(self, storage: &dyn Storage) {
+        for scratch in &self.scratch {
+            storage.remove_dir_all(scratch).ok();
+        }
+        for staged in self.staged.into_values() {
+            if let Staged::File(staged) = staged {
+                storage.remove_file(&staged).ok();
+            }
+        }
+        for dir in self.created.iter().rev() {
+            storage.remove_dir_all(dir).ok();
+        }
+    }
+}
+
+/// Records the changes of a run; safe to share between concurrent tasks.
+#[derive(Debug)]
+pub struct OutputSink {
+    dry_run: bool,
+    storage: Arc<dyn Storage>,
+    /// Receives the events of the run.
+    observer: Option<Arc<dyn Observer>>,
+    /// Stops the run before its next part.
+    cancellation: CancellationToken,
+    /// Hash written files and trust [`Self::load_hashes`] entries instead of reading files.
+    hash_index: bool,
+    changes: Mutex<Vec<PlannedChange>>,
+    /// (source file, disassembly directory) pairs of a disassembly.
+    outputs: Mutex<Vec<(PathBuf, PathBuf)>>,
+    /// Content hashes from stored indexes (before the run).
+    known_hashes: Mutex<HashMap<PathBuf, String>>,
+    /// Content hashes of the files written by this run.
+    hashes: Mutex<HashMap<PathBuf, String>>,
+    transaction: Mutex<Transaction>,
+}
+
+impl Default for OutputSink {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            storage: Arc::new(LocalStorage),
+            observer: None,
+            cancellation: CancellationToken::default(),
+            hash_index: false,
+            changes: Mutex::default(),
+            outputs: Mutex::default(),
+            known_hashes: Mutex::default(),
+            hashes: Mutex::default(),
+            transaction: Mutex::default(),
+        }
+    }
+}
+
+impl OutputSink {
+    /// A sink that records changes; with `dry_run` it does not perform them.
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            ..Self::default()
+        }
+    }
+
+    /// Keep content hashes of written files, and treat a file whose stored hash (see
+    /// [`Self::load_hashes`]) matches the new content as unchanged without reading it.
+    pub fn with_hash_index(mut self, enabled: bool) -> Self {
+        self.hash_index = enabled;
+        self
+    }
+
+    /// Perform every operation on `storage` instead of the local file system.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Send the run's [`Event`]s to `observer`.
+    pub fn with_observer(mut self, observer: Option<Arc<dyn Observer>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    pub fn observer(&self) -> Option<&dyn Observer> {
+        self.observer.as_deref()
+    }
+
+    /// Stop the run once `token` is cancelled (see [`Self::check_cancelled`]).
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// `Err(Cancelled)` once the run's token was cancelled; checked before each file and part.
+    pub fn check_cancelled(&self) -> Result<(), Cancelled> {
+        self.cancellation.check()
+    }
+
+    /// Send the event built by `event` to the observer, if any.
+    pub fn notify(&self, event: impl FnOnce() -> Event) {
+        if let Some(observer) = &self.observer {
+            observer.on_event(&event());
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn has_hash_index(&self) -> bool {
+        self.hash_index
+    }
+
+    /// Add the entries of a stored hash index; `hashes` maps paths relative to `dir` to
+    /// [`content_hash`] values.
+    pub fn load_hashes(&self, dir: &Path, hashes: &HashMap<String, String>) {
+        let mut known = self.known_hashes.lock().unwrap();
+        for (relative, hash) in hashes {
+            known.insert(dir.join(relative), hash.clone());
+        }
+    }
+
+    /// Hashes of the files written under `dir` by this run, keyed by path relative to `dir`.
+    pub fn hashes_under(&self, dir: &Path) -> HashMap<PathBuf, String> {
+        self.hashes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, hash)| {
+                let relative = path.strip_prefix(dir).ok()?;
+                Some((relative.to_path_buf(), hash.clone()))
+            })
+            .collect()
+    }
+
+    fn record(&self, change: PlannedChange) {
+        // Work files inside a scratch copy are not changes of the run.
+        if self.transaction.lock().unwrap().in_scratch(&change.path) {
+            return;
+        }
+        self.changes.lock().unwrap().push(change);
+    }
+
+    /// Whether an operation on `path` only goes into the plan: in dry-run mode, outside scratch
+    /// copies.
+    fn plans(&self, path: &Path) -> bool {
+        self.is_dry_run() && !self.transaction.lock().unwrap().in_scratch(path)
+    }
+
+    fn view(&self, path: &Path) -> Option<Content> {
+        self.transaction.lock().unwrap().view(path)
+    }
+
+    /// Whether `path` is a file as the running transaction (or dry run) leaves it.
+    pub fn is_file(&self, path: &Path) -> bool {
+        match self.view(path) {
+            Some(Content::File(file)) => self.storage.is_file(&file),
+            Some(Content::Planned(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Read `path` as the running transaction (or dry run) leaves it.
+    pub fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        match self.view(path) {
+            Some(Content::File(file)) => self.storage.read(&file),
+            Some(Content::Planned(contents)) => Ok(contents.to_vec()),
+            None => Err(std::io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    /// Read `path` as UTF-8 text as the running transaction (or dry run) leaves it.
+    pub fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Metadata of `path` as the running transaction (or dry run) leaves it.
+    pub fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        match self.view(path) {
+            Some(Content::File(file)) => self.storage.metadata(&file),
+            Some(Content::Planned(contents)) => Ok(Metadata::file(contents.len() as u64)),
+            None => Err(std::io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    /// Files below `dir` as the running transaction (or dry run) leaves them, listed like
+    /// [`relative_files`].
+    pub fn files_under(&self, dir: &Path) -> Vec<String> {
+        let on_disk = relative_files(self.storage(), dir);
+        let transaction = self.transaction.lock().unwrap();
+        let siblings: HashSet<&PathBuf> = transaction
+            .staged
+            .values()
+            .filter_map(|staged| match staged {
+                Staged::File(sibling) => Some(sibling),
+                Staged::Planned(_) | Staged::Deleted => None,
+            })
+            .collect();
+        let mut files: Vec<String> = on_disk
+            .into_iter()
+            .filter(|relative| {
+                let path = dir.join(relative);
+                !siblings.contains(&path) && transaction.view(&path).is_some()
+            })
+            .collect();
+        for (path, staged) in transaction
+            .staged
+            .range(dir.to_path_buf()..)
+            .take_while(|(p, _)| p.starts_with(dir))
+        {
+            if let (Staged::File(_) | Staged::Planned(_), Ok(relative)) =
+                (staged, path.strip_prefix(dir))
+            {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Write `contents` to `path` (only planned in dry-run mode, where the sink keeps the
+    /// content for later reads), comparing with the current file; a file that already has this
+    /// content is left untouched (its mtime is kept). With `diff`, an overwrite also carries a
+    /// unified diff of the text. Scratch copies are written directly, even in dry-run mode.
+    pub fn write(&self, path: &Path, contents: &[u8], diff: bool) -> std::io::Result<()> {
+        let hash = self.has_hash_index().then(|| content_hash(contents));
+        if let Some(hash) = &hash {
+            self.hashes
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), hash.clone());
+            let indexed = self.known_hashes.lock().unwrap().get(path) == Some(hash);
+            if indexed && self.is_file(path) {
+                self.record(PlannedChange {
+                    path: path.to_path_buf(),
+                    kind: ChangeKind::Unchanged,
+                    diff: None,
+                });
+                return Ok(());
+            }
+        }
+        let (kind, diff) = match self.read(path) {
+            Err(_) => (ChangeKind::Create, None),
+            Ok(existing) if existing == contents => (ChangeKind::Unchanged, None),
+            Ok(existing) => {
+                let text = diff.then(|| {
+                    let old = String::from_utf8_lossy(&existing);
+                    let new = String::from_utf8_lossy(contents);
+                    let name = path.display().to_string();
+                    TextDiff::from_lines(old.as_ref(), new.as_ref())
+                        .unified_diff()
+                        .header(&name, &name)
+                        .to_string()
+                });
+                (ChangeKind::Overwrite, text)
+            }
+        };
+        self.record(PlannedChange {
+            path: path.to_path_buf(),
+            kind,
+            diff,
+        });
+        if kind == ChangeKind::Unchanged {
+            return Ok(());
+        }
+        if self.plans(path) {
+            self.transaction
+                .lock()
+                .unwrap()
+                .staged
+                .insert(path.to_path_buf(), Staged::Planned(Arc::from(contents)));
+            return Ok(());
+        }
+        if !self.transaction.lock().unwrap().stages(path) {
+            return replace_file(self.storage(), path, contents);
+        }
+        // Written next to the file, moved in on commit.
+        let staged = sibling_temp(path, "staged");
+        if let Err(err) = self.storage.write(&staged, contents) {
+            self.storage.remove_file(&staged).ok();
+            return Err(err);
+        }
+        let replaced = self
+            .transaction
+            .lock()
+            .unwrap()
+            .staged
+            .insert(path.to_path_buf(), Staged::File(staged));
+        if let Some(Staged::File(replaced)) = replaced {
+            self.storage.remove_file(&replaced).ok();
+        }
+        Ok(())
+    }
+
+    /// Whether `path` exists as the running transaction (or dry run) leaves it.
+    fn exists(&self, path: &Path) -> bool {
+        match self.view(path) {
+            Some(Content::File(file)) => self.storage.exists(&file),
+            Some(Content::Planned(_)) => true,
+            None => false,
+        }
+    }
+
+    fn record_delete(&self, path: &Path) {
+        if self.exists(path) {
+            self.record(PlannedChange {
+                path: path.to_path_buf(),
+                kind: ChangeKind::Delete,
+                diff: None,
+            });
+        }
+    }
+
+    /// Remove a file (only planned in dry-run mode, deferred to the commit in a transaction).
+    pub fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        self.record_delete(path);
+        self.delete(path, |storage, path| storage.remove_file(path))
+    }
+
+    /// Remove a directory tree (only planned in dry-run mode, deferred to the commit in a
+    /// transaction). Nothing may be written below it afterwards in the same transaction.
+    pub fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        self.record_delete(path);
+        self.delete(path, |storage, path| storage.remove_dir_all(path))
+    }
+
+    /// Delete `path` with `remove` now, or, in a transaction or dry run, drop what was staged
+    /// there and plan the deletion of what is on disk (carried out on commit): later writes and
+    /// reads see the path as gone.
+    fn delete(
+        &self,
+        path: &Path,
+        remove: impl FnOnce(&dyn Storage, &Path) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let plans = self.plans(path);
+        let mut transaction = self.transaction.lock().unwrap();
+        if !plans && !transaction.stages(path) {
+            drop(transaction);
+            return remove(self.storage(), path);
+        }
+        transaction.unstage(self.storage(), path);
+        if transaction.view(path).is_some() && self.storage.exists(path) {
+            transaction
+                .staged
+                .insert(path.to_path_buf(), Staged::Deleted);
+        }
+        Ok(())
+    }
+
+    /// Remove the directories between the (deleted) file `path` and `root` that are left empty,
+    /// `root` included with `include_root`; after the commit in a transaction.
+    pub fn prune_empty_parents(&self, path: &Path, root: &Path, include_root: bool) {
+        if self.plans(path) {
+            return;
+        }
+        let mut transaction = self.transaction.lock().unwrap();
+        if transaction.stages(path) {
+            transaction
+                .prune
+                .push((path.to_path_buf(), root.to_path_buf(), include_root));
+            return;
+        }
+        drop(transaction);
+        remove_empty_parents(self.storage(), path, root, include_root);
+    }
+
+    /// Create `path` and its parents (nothing to do in dry-run mode). In a transaction the
+    /// directories are created right away, since staged files are written into them, and
+    /// removed again on rollback.
+    pub fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        if self.plans(path) {
+            return Ok(());
+        }
+        if self.transaction.lock().unwrap().stages(path) {
+            if let Some(top) = path
+                .ancestors()
+                .take_while(|p| !self.storage.exists(p))
+                .last()
+            {
+                self.transaction
+                    .lock()
+                    .unwrap()
+                    .created
+                    .push(top.to_path_buf());
+            }
+        }
+        self.storage.create_dir_all(path)
+    }
+
+    /// Start a transaction (see the module docs); a no-op apart from clearing the records in
+    /// dry-run mode.
+    pub fn begin(&self) {
+        self.clear();
+        *self.transaction.lock().unwrap() = Transaction {
+            active: !self.is_dry_run(),
+            ..Transaction::default()
+        };
+    }
+
+    /// A scratch copy of `dir` (hard links where possible) for work that must not touch `dir`.
+    /// Writes inside it are direct and not recorded; it is removed when the transaction ends.
+    pub fn scratch_copy(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        let scratch = sibling_temp(dir, "scratch");
+        if let Err(err) = self.storage.copy_tree(dir, &scratch) {
+            self.storage.remove_dir_all(&scratch).ok();
+            return Err(err);
+        }
+        self.transaction
+            .lock()
+            .unwrap()
+            .scratch
+            .push(scratch.clone());
+        Ok(scratch)
+    }
+
+    fn end_transaction(&self) -> Transaction {
+        std::mem::take(&mut *self.transaction.lock().unwrap())
+    }
+
+    /// Apply the transaction path by path: move what a path holds aside as a backup, then
+    /// rename its staged sibling into place (nothing for a deletion). The backups are removed
+    /// once every path succeeded; if one fails, the paths already done are restored from their
+    /// backups and the error is returned.
+    pub fn commit(&self) -> std::io::Result<()> {
+        let mut transaction = self.end_transaction();
+        for scratch in std::mem::take(&mut transaction.scratch) {
+            self.storage.remove_dir_all(&scratch).ok();
+        }
+        if !transaction.active {
+            // Dry run: the plan is all there is.
+            return Ok(());
+        }
+        let storage = self.storage();
+        // (target, backup, whether a staged file was moved in)
+        let mut done: Vec<(PathBuf, Option<PathBuf>, bool)> = Vec::new();
+        let mut failure = None;
+        for (target, staged) in std::mem::take(&mut transaction.staged) {
+            if failure.is_some() {
+                transaction.staged.insert(target, staged);
+                continue;
+            }
+            let backup = if storage.exists(&target) {
+                let backup = sibling_temp(&target, "backup");
+                if let Err(err) = storage.rename(&target, &backup) {
+                    failure = Some(err);
+                    transaction.staged.insert(target, staged);
+                    continue;
+                }
+                Some(backup)
+            } else {
+                None
+            };
+            if let Staged::File(file) = &staged {
+                if let Err(err) = storage.rename(file, &target) {
+                    if let Some(backup) = &backup {
+                        storage.rename(backup, &target).ok();
+                    }
+                    failure = Some(err);
+                    transaction.staged.insert(target, staged);
+                    continue;
+                }
+            }
+            done.push((target, backup, matches!(staged, Staged::File(_))));
+        }
+
+        let Some(err) = failure else {
+            for backup in done.into_iter().filter_map(|(_, backup, _)| backup) {
+                if let Err(err) = storage.remove(&backup) {
+                    log::warn!("Could not remove backup {}: {}", backup.display(), err);
+                }
+            }
+            for (path, root, include_root) in &transaction.prune {
+                remove_empty_parents(storage, path, root, *include_root);
+            }
+            return Ok(());
+        };
+        for (target, backup, placed) in done.into_iter().rev() {
+            if placed {
+                storage.remove(&target).ok();
+            }
+            if let Some(backup) = backup {
+                storage.rename(&backup, &target).ok();
+            }
+        }
+        transaction.discard(storage);
+        Err(err)
+    }
+
+    /// Abandon the transaction: remove every staged file and created directory. Nothing else was
+    /// touched.
+    pub fn rollback(&self) {
+        self.end_transaction().discard(self.storage());
+    }
+
+    /// End the transaction with the outcome of the run: commit on success, roll back on error.
+    pub fn finish(&self, result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Ok(()) => Ok(self.commit()?),
+            Err(err) => {
+                self.rollback();
+                Err(err)
+            }
+        }
+    }
+
+    /// Record that `source` disassembles into `output_dir`.
+    pub fn record_output(&self, source: &Path, output_dir: &Path) {
+        self.outputs
+            .lock()
+            .unwrap()
+            .push((source.to_path_buf(), output_dir.to_path_buf()));
+    }
+
+    /// Whether a write was recorded anywhere under `dir` (and not deleted since).
+    pub fn has_writes_under(&self, dir: &Path) -> bool {
+        !self.written_under(dir).is_empty()
+    }
+
+    /// Paths written under `dir` so far and not deleted since, sorted.
+    pub fn written_under(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut written = BTreeMap::new();
+        for change in self.changes.lock().unwrap().iter() {
+            if change.path.starts_with(dir) {
+                written.insert(change.path.clone(), change.kind != ChangeKind::Delete);
+            }
+        }
+        written
+            .into_iter()
+            .filter_map(|(path, kept)| kept.then_some(path))
+            .collect()
+    }
+
+    /// Forget everything recorded so far.
+    pub fn clear(&self) {
+        self.changes.lock().unwrap().clear();
+        self.outputs.lock().unwrap().clear();
+        self.known_hashes.lock().unwrap().clear();
+        self.hashes.lock().unwrap().clear();
+    }
+
+    /// Take the recorded (source file, disassembly directory) pairs, sorted by source.
+    pub fn take_outputs(&self) -> Vec<(PathBuf, PathBuf)> {
+        let mut outputs = std::mem::take(&mut *self.outputs.lock().unwrap());
+        outputs.sort();
+        outputs
+    }
+
+    /// Take the recorded changes sorted by path (deletions first), one per path (see
+    /// [`merge_changes`]).
+    pub fn take_changes(&self) -> Vec<PlannedChange> {
+        let mut changes = std::mem::take(&mut *self.changes.lock().unwrap());
+        // Stable sort keeps recording order within a path.
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut merged: Vec<PlannedChange> = Vec::with_capacity(changes.len());
+        let mut rest = changes.as_slice();
+        while let Some(first) = rest.first() {
+            let len = rest.iter().take_while(|c| c.path == first.path).count();
+            let (group, tail) = rest.split_at(len);
+            merged.extend(merge_changes(group));
+            rest = tail;
+        }
+        merged.sort_by(|a, b| {
+            (a.kind != ChangeKind::Delete, &a.path).cmp(&(b.kind != ChangeKind::Delete, &b.path))
+        });
+        merged
+    }
+}
+
+/// The net change of the `changes` recorded for one path, in recording order. A path deleted
+/// last is reported deleted if it existed before the run, and not at all if the run created it.
+/// A path written after a deletion is reported created. Otherwise its writes merge: created if
+/// the first write created it, else overwritten if any write changed it.
+fn merge_changes(changes: &[PlannedChange]) -> Option<PlannedChange> {
+    let (first, last) = (changes.first()?, changes.last()?);
+    let has = |kind: ChangeKind| changes.iter().any(|c| c.kind == kind);
+    let kind = match last.kind {
+        ChangeKind::Delete if first.kind == ChangeKind::Create => return None,
+        ChangeKind::Delete => ChangeKind::Delete,
+        _ if first.kind == ChangeKind::Create || has(ChangeKind::Delete) => ChangeKind::Create,
+        _ if has(ChangeKind::Overwrite) => ChangeKind::Overwrite,
+        _ => ChangeKind::Unchanged,
+    };
+    let diff = match kind {
+        ChangeKind::Overwrite => changes.iter().rev().find_map(|c| c.diff.clone()),
+        _ => None,
+    };
+    Some(PlannedChange {
+        path: first.path.clone(),
+        kind,
+        diff,
+    })
+}
+
+/// Human-readable dry-run report: one `kind path` line per change (followed by its diff, if
+/// any) and a summary line.
+pub fn render_report(changes: &[PlannedChange]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        out.push_str(&format!(
+            "{:<9} {}\n",
+            change.kind.label(),
+            change.path.display()
+        ));
+        if let Some(diff) = &change.diff {
+            out.push_str(diff);
+        }
+    }
+    let counts = ChangeCounts::from_changes(changes);
+    out.push_str(&format!(
+        "Dry run: {} to create, {} to overwrite, {} unchanged, {} to delete\n",
+        counts.created, counts.updated, counts.unchanged, counts.deleted
+    ));
+    out
+}
+
+/// The storage of the sink, if any, otherwise the local file system.
+pub fn storage_of(sink: Option<&OutputSink>) -> &dyn Storage {
+    match sink {
+        Some(sink) => sink.storage(),
+        None => &LocalStorage,
+    }
+}
+
+/// Metadata of `path` through the sink, if any (see [`OutputSink::metadata`]).
+pub fn metadata(sink: Option<&OutputSink>, path: &Path) -> std::io::Result<Metadata> {
+    match sink {
+        Some(sink) => sink.metadata(path),
+        None => LocalStorage.metadata(path),
+    }
+}
+
+/// Read `path` as text through the sink, if any (see [`OutputSink::read_to_string`]).
+pub fn read_to_string(sink: Option<&OutputSink>, path: &Path) -> std::io::Result<String> {
+    match sink {
+        Some(sink) => sink.read_to_string(path),
+        None => LocalStorage.read_to_string(path),
+    }
+}
+
+/// `Err(Cancelled)` once the sink's run was cancelled (never without a sink).
+pub fn check_cancelled(sink: Option<&OutputSink>) -> Result<(), Cancelled> {
+    match sink {
+        Some(sink) => sink.check_cancelled(),
+        None => Ok(()),
+    }
+}
+
+/// Send the event built by `event` to the sink's observer, if any.
+pub fn notify(sink: Option<&OutputSink>, event: impl FnOnce() -> Event) {
+    if let Some(sink) = sink {
+        sink.notify(event);
+    }
+}
+
+/// Write `contents` to `path` through the sink, if any.
+pub fn write_file(
+    sink: Option<&OutputSink>,
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    match sink {
+        Some(sink) => sink.write(path, contents.as_ref(), false),
+        None => replace_file(&LocalStorage, path, contents),
+    }
+}
+
+/// Create `path` and its parents through the sink, if any (nothing to do when dry-running).
+pub fn create_dir_all(sink: Option<&OutputSink>, path: &Path) -> std::io::Result<()> {
+    match sink {
+        Some(sink) => sink.create_dir_all(path),
+        None => LocalStorage.create_dir_all(path),
+    }
+}
+
+/// Remove a directory tree through the sink, if any.
+pub fn remove_dir_all(sink: Option<&OutputSink>, path: &Path) -> std::io::Result<()> {
+    match sink {
+        Some(sink) => sink.remove_dir_all(path),
+        None => LocalStorage.remove_dir_all(path),
+    }
+}
+
+/// Remove a file through the sink, if any.
+pub fn remove_file(sink: Option<&OutputSink>, path: &Path) -> std::io::Result<()> {
+    match sink {
+        Some(sink) => sink.remove_file(path),
+        None => LocalStorage.remove_file(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_records_instead_of_touching_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing.xml");
+        let same = dir.path().join("same.xml");
+        let gone = dir.path().join("gone.xml");
+        let replaced = dir.path().join("replaced.xml");
+        std::fs::write(&existing, "<a>\n1\n</a>\n").unwrap();
+        for path in [&same, &gone, &replaced] {
+            std::fs::write(path, "x").unwrap();
+        }
+        let sink = OutputSink::new(true);
+
+        let created = dir.path().join("new/part.xml");
+        let transient = dir.path().join("new/transient.xml");
+        write_file(Some(&sink), &created, "<b/>").unwrap();
+        write_file(Some(&sink), &same, "x").unwrap();
+        sink.write(&existing, b"<a>\n2\n</a>\n", true).unwrap();
+        remove_file(Some(&sink), &gone).unwrap();
+        remove_dir_all(Some(&sink), &dir.path().join("missing")).unwrap();
+        // Planned deletions: writing the path again creates it, and a file the run creates
+        // and deletes is no change at all.
+        remove_file(Some(&sink), &replaced).unwrap();
+        assert!(!sink.is_file(&replaced));
+        write_file(Some(&sink), &replaced, "x").unwrap();
+        write_file(Some(&sink), &transient, "<c/>").unwrap();
+        remove_file(Some(&sink), &transient).unwrap();
+
+        assert!(!created.exists());
+        assert!(gone.exists() && replaced.exists());
+        assert_eq!(sink.written_under(&dir.path().join("new")), vec![created]);
+        let changes = sink.take_changes();
+        let kinds: Vec<ChangeKind> = changes.iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Delete,
+                ChangeKind::Overwrite,
+                ChangeKind::Create,
+                ChangeKind::Create,
+                ChangeKind::Unchanged
+            ]
+        );
+        assert_eq!(changes[0].path, gone);
+        assert_eq!(changes[3].path, replaced);
+        let diff = changes[1].diff.as_deref().unwrap();
+        assert!(diff.contains("-1\n") && diff.contains("+2\n"));
+        let report = render_report(&changes);
+        assert!(report.ends_with("2 to create, 1 to overwrite, 1 unchanged, 1 to delete\n"));
+    }
+
+    #[test]
+    fn real_sink_writes_and_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.xml");
+        let sink = OutputSink::new(false);
+        write_file(Some(&sink), &path, "<a/>").unwrap();
+        write_file(Some(&sink), &path, "<a/>").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "<a/>");
+        assert_eq!(sink.written_under(dir.path()), vec![path.clone()]);
+        let other = dir.path().join("other.xml");
+        std::fs::write(&other, "<b/>").unwrap();
+        remove_file(Some(&sink), &other).unwrap();
+        assert!(!other.exists());
+        let changes = sink.take_changes();
+        assert_eq!(changes[0].kind, ChangeKind::Delete);
+        // Written twice: the first write created it.
+        assert_eq!(changes[1].kind, ChangeKind::Create);
+
+        // Created and deleted by the same run: no change.
+        write_file(Some(&sink), &other, "<b/>").unwrap();
+        remove_file(Some(&sink), &other).unwrap();
+        assert!(sink.written_under(dir.path()).is_empty());
+        assert!(sink.take_changes().is_empty());
+    }
+
+    #[test]
+    fn unchanged_files_are_not_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.xml");
+        std::fs::write(&path, "<a/>").unwrap();
+        let before = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        let sink = OutputSink::new(false);
+        write_file(Some(&sink), &path, "<a/>").unwrap();
+        let after = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(before, after);
+        let counts = ChangeCounts::from_changes(&sink.take_changes());
+        assert_eq!(
+            counts.to_string(),
+            "0 created, 0 updated, 1 unchanged, 0 deleted"
+        );
+    }
+
+    #[test]
+    fn hash_index_skips_reading_indexed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.xml");
+        // The index is trusted: a matching hash means unchanged whatever the file holds.
+        std::fs::write(&path, "edited").unwrap();
+        let sink = OutputSink::new(false).with_hash_index(true);
+        let stored = HashMap::from([("part.xml".to_string(), content_hash(b"<a/>"))]);
+        sink.load_hashes(dir.path(), &stored);
+
+        write_file(Some(&sink), &path, "<a/>").unwrap();
+        write_file(Some(&sink), &dir.path().join("new.xml"), "<b/>").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "edited");
+        let hashes = sink.hashes_under(dir.path());
+        assert_eq!(hashes[Path::new("new.xml")], content_hash(b"<b/>"));
+        let counts = ChangeCounts::from_changes(&sink.take_changes());
+        assert_eq!((counts.created, counts.unchanged), (1, 1));
+    }
+
+    /// Names in `dir`, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Names in `dir` without hidden ones, sorted.
+    fn visible(dir: &Path) -> Vec<String> {
+        names(dir)
+            .into_iter()
+            .filter(|name| !name.starts_with('.'))
+            .collect()
+    }
+
+    #[test]
+    fn transaction_applies_nothing_until_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        std::fs::write(out.join("kept.xml"), "kept").unwrap();
+        std::fs::write(out.join("old.xml"), "old").unwrap();
+        let source = dir.path().join("source.xml");
+        std::fs::write(&source, "<a/>").unwrap();
+        let report = dir.path().join("report.xml");
+
+        let sink = OutputSink::new(false);
+        sink.begin();
+        create_dir_all(Some(&sink), &out.join("sub")).unwrap();
+        write_file(Some(&sink), &out.join("sub/new.xml"), "new").unwrap();
+        write_file(Some(&sink), &out.join("kept.xml"), "changed").unwrap();
+        remove_file(Some(&sink), &out.join("old.xml")).unwrap();
+        write_file(Some(&sink), &report, "report").unwrap();
+        remove_file(Some(&sink), &source).unwrap();
+
+        // Nothing visible changed yet; the sink's view holds the new state.
+        assert_eq!(visible(&out), vec!["kept.xml", "old.xml", "sub"]);
+        assert_eq!(
+            std::fs::read_to_string(out.join("kept.xml")).unwrap(),
+            "kept"
+        );
+        assert_eq!(sink.files_under(&out), vec!["kept.xml", "sub/new.xml"]);
+        assert_eq!(
+            sink.read_to_string(&out.join("kept.xml")).unwrap(),
+            "changed"
+        );
+        assert!(!sink.is_file(&source));
+        assert!(source.exists() && !report.exists());
+
+        sink.commit().unwrap();
+        assert_eq!(names(&out), vec!["kept.xml", "sub"]);
+        assert_eq!(names(&out.join("sub")), vec!["new.xml"]);
+        assert_eq!(
+            std::fs::read_to_string(out.join("kept.xml")).unwrap(),
+            "changed"
+        );
+        assert_eq!(std::fs::read_to_string(&report).unwrap(), "report");
+        assert!(!source.exists());
+        assert_eq!(names(dir.path()), vec!["out", "report.xml"]);
+    }
+
+    #[test]
+    fn rollback_removes_everything_staged() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        std::fs::write(out.join("part.xml"), "before").unwrap();
+        let nested = dir.path().join("new/deeper");
+
+        let sink = OutputSink::new(false);
+        sink.begin();
+        write_file(Some(&sink), &out.join("part.xml"), "after").unwrap();
+        create_dir_all(Some(&sink), &nested).unwrap();
+        write_file(Some(&sink), &nested.join("part.xml"), "new").unwrap();
+        remove_dir_all(Some(&sink), &out).unwrap();
+        sink.finish(Err(Error::Cancelled)).unwrap_err();
+
+        assert_eq!(names(&out), vec!["part.xml"]);
+        assert_eq!(
+            std::fs::read_to_string(out.join("part.xml")).unwrap(),
+            "before"
+        );
+        assert_eq!(names(dir.path()), vec!["out"]);
+    }
+
+    #[test]
+    fn transaction_leaves_unchanged_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        std::fs::write(out.join("same.xml"), "same").unwrap();
+        std::fs::write(out.join("other.xml"), "other").unwrap();
+        let before = std::fs::metadata(out.join("same.xml"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        let sink = OutputSink::new(false);
+        sink.begin();
+        write_file(Some(&sink), &out.join("same.xml"), "same").unwrap();
+        write_file(Some(&sink), &out.join("other.xml"), "changed").unwrap();
+        // Only the changed file is staged.
+        assert_eq!(names(&out).len(), 3);
+        sink.commit().unwrap();
+
+        assert_eq!(names(&out), vec!["other.xml", "same.xml"]);
+        let after = std::fs::metadata(out.join("same.xml"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn take_changes_merges_writes_per_path() {
+        let sink = OutputSink::new(true);
+        for kind in [
+            ChangeKind::Unchanged,
+            ChangeKind::Overwrite,
+            ChangeKind::Unchanged,
+        ] {
+            sink.record(PlannedChange {
+                path: PathBuf::from("a.xml"),
+                kind,
+                diff: None,
+            });
+        }
+        let changes = sink.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Overwrite);
+    }
+}
@@ -0,0 +1,536 @@
+//! Storage backends: every file a disassembly or reassembly reads, writes, lists or removes goes
+//! through a [`Storage`]. [`LocalStorage`] (the default) is the local file system;
+//! [`MemoryStorage`] keeps a tree of files in memory, for in-memory runs and tests.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Kind and size of a stored entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    kind: EntryKind,
+    len: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Dir,
+    Other,
+}
+
+impl Metadata {
+    pub fn file(len: u64) -> Self {
+        Self {
+            kind: EntryKind::File,
+            len,
+        }
+    }
+
+    pub fn dir() -> Self {
+        Self {
+            kind: EntryKind::Dir,
+            len: 0,
+        }
+    }
+
+    /// Neither a regular file nor a directory (e.g. a socket or device).
+    pub fn other() -> Self {
+        Self {
+            kind: EntryKind::Other,
+            len: 0,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    /// Size in bytes (0 for directories).
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// File operations the library needs, with the semantics of [`std::fs`]: writes and directory
+/// creation need an existing parent, and renames replace a file or an empty directory.
+pub trait Storage: fmt::Debug + Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Create or truncate the file at `path` with `contents`.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Paths of the entries directly in `dir`, in no particular order.
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Create `path` and any missing parents.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove an empty directory.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove a directory and everything below it.
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|m| m.is_file())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|m| m.is_dir())
+    }
+
+    /// Remove a file or a directory tree.
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if self.metadata(path)?.is_dir() {
+            self.remove_dir_all(path)
+        } else {
+            self.remove_file(path)
+        }
+    }
+
+    /// Recreate the tree at `from` under the new directory `to`.
+    fn copy_tree(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.create_dir_all(to)?;
+        for entry in self.list(from)? {
+            let Some(name) = entry.file_name() else {
+                continue;
+            };
+            if self.is_dir(&entry) {
+                self.copy_tree(&entry, &to.join(name))?;
+            } else {
+                self.write(&to.join(name), &self.read(&entry)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Absolute form of `path`, or `path` itself where the backend has no such notion.
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+
+    /// Whether paths are paths of the local file system; directory walks then also honour
+    /// `.gitignore` files.
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// The local file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let meta = std::fs::metadata(path)?;
+        Ok(if meta.is_dir() {
+            Metadata::dir()
+        } else if meta.is_file() {
+            Metadata::file(meta.len())
+        } else {
+            Metadata::other()
+        })
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_dir(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    /// Also true for a dangling symbolic link, which a rename would replace.
+    fn exists(&self, path: &Path) -> bool {
+        std::fs::symlink_metadata(path).is_ok()
+    }
+
+    /// A symbolic link is removed itself, never what it points to.
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if std::fs::symlink_metadata(path)?.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        }
+    }
+
+    /// Hard links where possible (copies otherwise): cheap, and since files are replaced by
+    /// renaming a new file over them, writing to one tree never changes the other.
+    fn copy_tree(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                self.copy_tree(&entry.path(), &target)?;
+            } else if std::fs::hard_link(entry.path(), &target).is_err() {
+                std::fs::copy(entry.path(), &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+}
+
+/// Files and directories kept in memory. Paths are used as given (relative paths are not
+/// resolved against the working directory); `.` components are ignored and `..` removes the
+/// previous component. The root (`""` or `/`) always exists.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<PathBuf, Entry>>,
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: not found", path.display()),
+    )
+}
+
+fn invalid(path: &Path, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{}: {}", path.display(), message),
+    )
+}
+
+fn is_root(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::RootDir | Component::Prefix(_)))
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a file, creating its parent directories.
+    pub fn insert(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        self.write(path, &contents.into())
+    }
+
+    /// Every file below `dir` with its content, keyed by the path relative to `dir`.
+    pub fn files_under(&self, dir: impl AsRef<Path>) -> BTreeMap<PathBuf, Vec<u8>> {
+        let dir = Self::key(dir.as_ref());
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, entry)| match entry {
+                Entry::File(bytes) => {
+                    let relative = path.strip_prefix(&dir).ok()?;
+                    Some((relative.to_path_buf(), bytes.clone()))
+                }
+                Entry::Dir => None,
+            })
+            .collect()
+    }
+
+    fn key(path: &Path) -> PathBuf {
+        let mut key = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    key.pop();
+                }
+                other => key.push(other),
+            }
+        }
+        key
+    }
+
+    fn parent_is_dir(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> bool {
+        match path.parent() {
+            Some(parent) if !is_root(parent) => matches!(entries.get(parent), Some(Entry::Dir)),
+            _ => true,
+        }
+    }
+
+    /// Keys of `dir` and everything below it.
+    fn subtree(entries: &BTreeMap<PathBuf, Entry>, dir: &Path) -> Vec<PathBuf> {
+        entries
+            .range(dir.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(dir))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.entries.lock().unwrap().get(&Self::key(path)) {
+            Some(Entry::File(bytes)) => Ok(bytes.clone()),
+            Some(Entry::Dir) => Err(invalid(path, "is a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        if is_root(&key) || matches!(entries.get(&key), Some(Entry::Dir)) {
+            return Err(invalid(path, "is a directory"));
+        }
+        if !Self::parent_is_dir(&entries, &key) {
+            return Err(not_found(path));
+        }
+        entries.insert(key, Entry::File(contents.to_vec()));
+        Ok(())
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let key = Self::key(dir);
+        let entries = self.entries.lock().unwrap();
+        if !is_root(&key) {
+            match entries.get(&key) {
+                Some(Entry::Dir) => {}
+                Some(Entry::File(_)) => return Err(invalid(dir, "not a directory")),
+                None => return Err(not_found(dir)),
+            }
+        }
+        Ok(entries
+            .keys()
+            .filter(|path| path.parent() == Some(key.as_path()))
+            .map(|path| dir.join(path.file_name().unwrap_or_default()))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let key = Self::key(path);
+        if is_root(&key) {
+            return Ok(Metadata::dir());
+        }
+        match self.entries.lock().unwrap().get(&key) {
+            Some(Entry::File(bytes)) => Ok(Metadata::file(bytes.len() as u64)),
+            Some(Entry::Dir) => Ok(Metadata::dir()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        let mut ancestors: Vec<&Path> = key.ancestors().filter(|a| !is_root(a)).collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            match entries.get(dir) {
+                Some(Entry::Dir) => {}
+                Some(Entry::File(_)) => return Err(invalid(dir, "not a directory")),
+                None => {
+                    entries.insert(dir.to_path_buf(), Entry::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(Entry::File(_)) => {
+                entries.remove(&key);
+                Ok(())
+            }
+            Some(Entry::Dir) => Err(invalid(path, "is a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(Entry::Dir) if Self::subtree(&entries, &key).len() == 1 => {
+                entries.remove(&key);
+                Ok(())
+            }
+            Some(Entry::Dir) => Err(invalid(path, "directory not empty")),
+            Some(Entry::File(_)) => Err(invalid(path, "not a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(Entry::Dir) => {
+                for path in Self::subtree(&entries, &key) {
+                    entries.remove(&path);
+                }
+                Ok(())
+            }
+            Some(Entry::File(_)) => Err(invalid(path, "not a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from_key, to_key) = (Self::key(from), Self::key(to));
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get(&from_key).cloned() else {
+            return Err(not_found(from));
+        };
+        if !Self::parent_is_dir(&entries, &to_key) {
+            return Err(not_found(to));
+        }
+        if from_key == to_key {
+            return Ok(());
+        }
+        match (&entry, entries.get(&to_key)) {
+            (Entry::File(_), Some(Entry::Dir)) => return Err(invalid(to, "is a directory")),
+            (Entry::Dir, Some(Entry::File(_))) => return Err(invalid(to, "not a directory")),
+            (Entry::Dir, Some(Entry::Dir)) if Self::subtree(&entries, &to_key).len() > 1 => {
+                return Err(invalid(to, "directory not empty"))
+            }
+            _ => {}
+        }
+        if matches!(entry, Entry::Dir) && to_key.starts_with(&from_key) {
+            return Err(invalid(to, "cannot move a directory into itself"));
+        }
+        for path in Self::subtree(&entries, &from_key) {
+            if let Some(moved) = entries.remove(&path) {
+                let rest = path.strip_prefix(&from_key).unwrap_or(Path::new(""));
+                let target = if rest.as_os_str().is_empty() {
+                    to_key.clone()
+                } else {
+                    to_key.join(rest)
+                };
+                entries.insert(target, moved);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_storage_behaves_like_a_file_system() {
+        let storage = MemoryStorage::new();
+        assert!(storage.write(Path::new("a/b.xml"), b"x").is_err());
+        storage.insert("a/b.xml", "x").unwrap();
+        storage.insert("./a/c/d.xml", "y").unwrap();
+        storage.insert("ab.xml", "z").unwrap();
+        assert_eq!(storage.read(Path::new("a/c/../b.xml")).unwrap(), b"x");
+        assert!(storage.is_dir(Path::new("a/c")));
+        assert_eq!(storage.metadata(Path::new("a/b.xml")).unwrap().len(), 1);
+
+        let mut listed = storage.list(Path::new("a")).unwrap();
+        listed.sort();
+        assert_eq!(listed, vec![PathBuf::from("a/b.xml"), PathBuf::from("a/c")]);
+        assert_eq!(storage.list(Path::new("")).unwrap().len(), 2);
+
+        assert!(storage.remove_dir(Path::new("a")).is_err());
+        storage
+            .copy_tree(Path::new("a"), Path::new("copy"))
+            .unwrap();
+        storage.rename(Path::new("a"), Path::new("moved")).unwrap();
+        assert!(!storage.exists(Path::new("a/c/d.xml")));
+        assert_eq!(storage.read(Path::new("moved/c/d.xml")).unwrap(), b"y");
+        assert_eq!(storage.read(Path::new("copy/c/d.xml")).unwrap(), b"y");
+
+        storage.remove_dir_all(Path::new("moved")).unwrap();
+        assert_eq!(
+            storage.files_under("").keys().collect::<Vec<_>>(),
+            vec![
+                Path::new("ab.xml"),
+                Path::new("copy/b.xml"),
+                Path::new("copy/c/d.xml")
+            ]
+        );
+    }
+
+    #[test]
+    fn local_storage_copies_trees_with_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        std::fs::create_dir_all(from.join("sub")).unwrap();
+        std::fs::write(from.join("sub/a.xml"), "a").unwrap();
+        let storage = LocalStorage;
+        storage.copy_tree(&from, &dir.path().join("to")).unwrap();
+        assert_eq!(
+            storage
+                .read_to_string(&dir.path().join("to/sub/a.xml"))
+                .unwrap(),
+            "a"
+        );
+        assert!(storage.is_local());
+        storage.remove(&from).unwrap();
+        assert!(!storage.exists(&from));
+    }
+}
@@ -0,0 +1,258 @@
+//! Element transforms: change the content of a document in flight, after a source file is parsed
+//! for disassembly and before a reassembled document is written.
+
+use crate::types::XmlElement;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// What to do with an element after an [`ElementTransform`] looked at it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformAction {
+    /// Keep the element (with whatever changes the transform made to it).
+    Keep,
+    /// Keep the element under another tag.
+    Rename(String),
+    /// Remove the element and everything below it.
+    Drop,
+}
+
+/// Changes, renames or drops elements. Registered with
+/// [`crate::DisassembleOptionsBuilder::transform`] (applied to each parsed source file) or
+/// [`crate::ReassembleOptionsBuilder::transform`] (applied to each merged document).
+pub trait ElementTransform: fmt::Debug + Send + Sync {
+    /// Called for every element, parents before their children. `path` holds the tags from the
+    /// root element down to this one (e.g. `["PermissionSet", "fieldPermissions", "editable"]`);
+    /// `element` is its text (a string) or an object of attributes (`@name`), text (`#text`) and
+    /// child elements, and may be changed in place.
+    fn transform(&self, path: &[String], element: &mut XmlElement) -> TransformAction;
+}
+
+/// What a [`TransformRule`] does with the elements it selects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    Drop,
+    Rename(String),
+    /// Replace every occurrence of `from` in the element's own text with `to`.
+    Replace {
+        from: String,
+        to: String,
+    },
+}
+
+/// A declarative [`ElementTransform`] for the common cases. The selector is a tag
+/// (`editable`, any element with that tag) or a `/`-separated path matched against the end of
+/// the element's path (`fieldPermissions/editable`); a leading `/` anchors it at the root element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformRule {
+    pub selector: String,
+    pub action: RuleAction,
+}
+
+impl TransformRule {
+    fn matches(&self, path: &[String]) -> bool {
+        let (anchored, selector) = match self.selector.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, self.selector.as_str()),
+        };
+        let segments: Vec<&str> = selector.split('/').collect();
+        if path.len() < segments.len() || (anchored && path.len() != segments.len()) {
+            return false;
+        }
+        path[path.len() - segments.len()..]
+            .iter()
+            .zip(segments)
+            .all(|(tag, segment)| tag == segment)
+    }
+}
+
+impl ElementTransform for TransformRule {
+    fn transform(&self, path: &[String], element: &mut XmlElement) -> TransformAction {
+        if !self.matches(path) {
+            return TransformAction::Keep;
+        }
+        match &self.action {
+            RuleAction::Drop => TransformAction::Drop,
+            RuleAction::Rename(tag) => TransformAction::Rename(tag.clone()),
+            RuleAction::Replace { from, to } => {
+                let text = match element {
+                    Value::Object(obj) => obj.get_mut("#text"),
+                    other => Some(other),
+                };
+                if let Some(Value::String(text)) = text {
+                    if !from.is_empty() && text.contains(from.as_str()) {
+                        *text = text.replace(from.as_str(), to);
+                    }
+                }
+                TransformAction::Keep
+            }
+        }
+    }
+}
+
+/// Keys of an element object that are not child elements.
+fn is_element_key(key: &str) -> bool {
+    !key.starts_with('@') && !key.starts_with('#') && key != "?xml"
+}
+
+/// Add `element` under `tag`, turning the value into an array when the tag repeats.
+fn push_element(map: &mut Map<String, Value>, tag: String, element: Value) {
+    match map.get_mut(&tag) {
+        None => {
+            map.insert(tag, element);
+        }
+        Some(Value::Array(items)) => items.push(element),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, element]);
+        }
+    }
+}
+
+/// Run `transforms` over every element of `document` (a parsed document or a merged one), in
+/// order: an element renamed by one transform is seen by the next under its new tag, and a
+/// dropped one is not seen again. Walks the tree without recursion.
+pub(crate) fn apply_transforms(
+    document: &mut XmlElement,
+    transforms: &[Arc<dyn ElementTransform>],
+) {
+    if transforms.is_empty() {
+        return;
+    }
+    let Value::Object(root) = document else {
+        return;
+    };
+    let mut stack: Vec<(&mut Map<String, Value>, Vec<String>)> = vec![(root, Vec::new())];
+    while let Some((map, path)) = stack.pop() {
+        let mut kept = Map::new();
+        for (tag, value) in std::mem::take(map) {
+            if !is_element_key(&tag) {
+                kept.insert(tag, value);
+                continue;
+            }
+            let items = match value {
+                Value::Array(items) => items,
+                single => vec![single],
+            };
+            'items: for mut item in items {
+                let mut item_tag = tag.clone();
+                let mut item_path = path.clone();
+                item_path.push(item_tag.clone());
+                for transform in transforms {
+                    match transform.transform(&item_path, &mut item) {
+                        TransformAction::Keep => {}
+                        TransformAction::Rename(new_tag) => {
+                            item_path.pop();
+                            item_path.push(new_tag.clone());
+                            item_tag = new_tag;
+                        }
+                        TransformAction::Drop => continue 'items,
+                    }
+                }
+                push_element(&mut kept, item_tag, item);
+            }
+        }
+        *map = kept;
+        for (tag, value) in map {
+            if !is_element_key(tag) {
+                continue;
+            }
+            let mut child_path = path.clone();
+            child_path.push(tag.clone());
+            match value {
+                Value::Object(child) => stack.push((child, child_path)),
+                Value::Array(items) => {
+                    for item in items {
+                        if let Value::Object(child) = item {
+                            stack.push((child, child_path.clone()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(selector: &str, action: RuleAction) -> Arc<dyn ElementTransform> {
+        Arc::new(TransformRule {
+            selector: selector.to_string(),
+            action,
+        })
+    }
+
+    #[test]
+    fn rules_drop_rename_and_replace_by_tag_and_path() {
+        let mut doc = json!({
+            "?xml": { "@version": "1.0" },
+            "Root": {
+                "@xmlns": "urn:x",
+                "perm": [
+                    { "field": "A", "editable": "True", "stamp": "1" },
+                    { "field": "B", "editable": "false", "stamp": "2" }
+                ],
+                "stamp": "3",
+                "label": { "#text": "dev.example.com", "@lang": "en" }
+            }
+        });
+        apply_transforms(
+            &mut doc,
+            &[
+                rule("perm/stamp", RuleAction::Drop),
+                rule(
+                    "editable",
+                    RuleAction::Replace {
+                        from: "True".to_string(),
+                        to: "true".to_string(),
+                    },
+                ),
+                rule("/Root/label", RuleAction::Rename("title".to_string())),
+                rule(
+                    "title",
+                    RuleAction::Replace {
+                        from: "dev.".to_string(),
+                        to: "prod.".to_string(),
+                    },
+                ),
+            ],
+        );
+        assert_eq!(
+            doc,
+            json!({
+                "?xml": { "@version": "1.0" },
+                "Root": {
+                    "@xmlns": "urn:x",
+                    "perm": [
+                        { "field": "A", "editable": "true" },
+                        { "field": "B", "editable": "false" }
+                    ],
+                    "stamp": "3",
+                    "title": { "#text": "prod.example.com", "@lang": "en" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn dropping_items_keeps_the_rest_of_the_tag() {
+        #[derive(Debug)]
+        struct DropField(&'static str);
+        impl ElementTransform for DropField {
+            fn transform(&self, path: &[String], element: &mut XmlElement) -> TransformAction {
+                if path.last().map(String::as_str) == Some("perm") && element["field"] == self.0 {
+                    TransformAction::Drop
+                } else {
+                    TransformAction::Keep
+                }
+            }
+        }
+        let mut doc = json!({ "Root": { "perm": [{ "field": "A" }, { "field": "B" }] } });
+        apply_transforms(&mut doc, &[Arc::new(DropField("A"))]);
+        assert_eq!(doc, json!({ "Root": { "perm": { "field": "B" } } }));
+    }
+}
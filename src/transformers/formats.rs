@@ -2,15 +2,15 @@
 
 use crate::types::XmlElement;
 
-pub async fn transform_to_yaml(parsed_xml: &XmlElement) -> String {
+pub fn transform_to_yaml(parsed_xml: &XmlElement) -> String {
     serde_yaml::to_string(parsed_xml).unwrap_or_default()
 }
 
-pub async fn transform_to_json5(parsed_xml: &XmlElement) -> String {
+pub fn transform_to_json5(parsed_xml: &XmlElement) -> String {
     serde_json::to_string_pretty(parsed_xml).unwrap_or_default()
 }
 
-pub async fn transform_to_json(parsed_xml: &XmlElement) -> String {
+pub fn transform_to_json(parsed_xml: &XmlElement) -> String {
     serde_json::to_string_pretty(parsed_xml).unwrap_or_default()
 }
 
@@ -19,18 +19,18 @@ mod tests {
     use super::*;
     use serde_json::json;
 
-    #[tokio::test]
-    async fn transform_to_yaml_produces_valid_yaml() {
+    #[test]
+    fn transform_to_yaml_produces_valid_yaml() {
         let el = json!({ "root": { "a": 1, "b": "two" } });
-        let out = transform_to_yaml(&el).await;
+        let out = transform_to_yaml(&el);
         assert!(out.contains("root:"));
         assert!(out.contains("a: 1") || out.contains("a:\n") || out.contains("a:1"));
     }
 
-    #[tokio::test]
-    async fn transform_to_json_produces_valid_json() {
+    #[test]
+    fn transform_to_json_produces_valid_json() {
         let el = json!({ "root": { "a": 1 } });
-        let out = transform_to_json(&el).await;
+        let out = transform_to_json(&el);
         let parsed: serde_json::Value = serde_json::from_str(&out).unwrap();
         assert_eq!(
             parsed
@@ -41,10 +41,10 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn transform_to_json5_produces_valid_json5() {
+    #[test]
+    fn transform_to_json5_produces_valid_json5() {
         let el = json!({ "root": { "a": 1 } });
-        let out = transform_to_json5(&el).await;
+        let out = transform_to_json5(&el);
         let parsed: serde_json::Value = json5::from_str(&out).unwrap();
         assert_eq!(
             parsed
@@ -1,16 +1,17 @@
 //! Get transformer for format - returns output string from XmlElement.
 
+use crate::options::Format;
 use crate::transformers::{transform_to_json, transform_to_json5, transform_to_yaml};
 use crate::types::XmlElement;
 
 /// Transform XmlElement to string in the given format.
-/// Returns None if format is not supported (e.g. "xml" uses build_xml_string instead).
-pub async fn transform_format(format: &str, xml_content: &XmlElement) -> Option<String> {
-    let result = match format.to_lowercase().as_str() {
-        "yaml" | "yml" => transform_to_yaml(xml_content).await,
-        "json5" => transform_to_json5(xml_content).await,
-        "json" => transform_to_json(xml_content).await,
-        _ => return None,
+/// Returns None for [`Format::Xml`], which uses build_xml_string instead.
+pub fn transform_format(format: Format, xml_content: &XmlElement) -> Option<String> {
+    let result = match format {
+        Format::Yaml => transform_to_yaml(xml_content),
+        Format::Json5 => transform_to_json5(xml_content),
+        Format::Json => transform_to_json(xml_content),
+        Format::Xml => return None,
     };
     Some(result)
 }
@@ -20,29 +21,27 @@ mod tests {
     use super::*;
     use serde_json::json;
 
-    #[tokio::test]
-    async fn transform_format_yaml() {
+    #[test]
+    fn transform_format_yaml() {
         let el = json!({ "r": {} });
-        assert!(transform_format("yaml", &el).await.is_some());
-        assert!(transform_format("yml", &el).await.is_some());
+        assert!(transform_format(Format::Yaml, &el).is_some());
     }
 
-    #[tokio::test]
-    async fn transform_format_json() {
+    #[test]
+    fn transform_format_json() {
         let el = json!({ "r": {} });
-        assert!(transform_format("json", &el).await.is_some());
+        assert!(transform_format(Format::Json, &el).is_some());
     }
 
-    #[tokio::test]
-    async fn transform_format_json5() {
+    #[test]
+    fn transform_format_json5() {
         let el = json!({ "r": {} });
-        assert!(transform_format("json5", &el).await.is_some());
+        assert!(transform_format(Format::Json5, &el).is_some());
     }
 
-    #[tokio::test]
-    async fn transform_format_unsupported_returns_none() {
+    #[test]
+    fn transform_format_xml_returns_none() {
         let el = json!({ "r": {} });
-        assert!(transform_format("xml", &el).await.is_none());
-        assert!(transform_format("unknown", &el).await.is_none());
+        assert!(transform_format(Format::Xml, &el).is_none());
     }
 }
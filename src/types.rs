@@ -4,7 +4,13 @@
 //! - Object with keys: element names, @attr for attributes, #text for text content, ?xml for declaration
 //! - Values: string, nested object, or array of objects/strings
 
+use crate::limits::{Limits, PartBudget};
+use crate::options::{DecomposeMode, Format, Strategy};
+use crate::sink::OutputSink;
+use crate::transform::ElementTransform;
 use serde_json::Value as JsonValue;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// XmlElement is a flexible representation of XML - equivalent to TypeScript's XmlElement type.
 /// Uses serde_json::Value for compatibility with quickxml_to_serde output.
@@ -22,9 +28,13 @@ pub struct XmlElementParams<'a> {
     pub leaf_content: XmlElement,
     pub leaf_count: usize,
     pub has_nested_elements: bool,
-    pub format: &'a str,
+    pub format: Format,
     pub xml_declaration: Option<XmlElement>,
-    pub strategy: &'a str,
+    pub strategy: Strategy,
+    pub bare: bool,
+    pub sidecars: Option<&'a [SidecarRule]>,
+    pub sink: Option<&'a OutputSink>,
+    pub parts: Option<&'a PartBudget>,
 }
 
 /// Options for building a single disassembled file.
@@ -38,9 +48,17 @@ pub struct BuildDisassembledFileOptions<'a> {
     pub is_grouped_array: bool,
     pub root_element_name: &'a str,
     pub root_attributes: XmlElement,
-    pub format: &'a str,
+    pub format: Format,
     pub xml_declaration: Option<XmlElement>,
     pub unique_id_elements: Option<&'a str>,
+    /// Write only the element(s) without the declaration and root wrapper (see [`RootWrapper`]).
+    pub bare: bool,
+    /// Extract matching text/CDATA content into sidecar files next to the part.
+    pub sidecars: Option<&'a [SidecarRule]>,
+    /// Record the write instead of performing it.
+    pub sink: Option<&'a OutputSink>,
+    /// Counts the part against the limits of its source file.
+    pub parts: Option<&'a PartBudget>,
 }
 
 /// Result from unified element parsing.
@@ -57,16 +75,133 @@ pub type XmlElementArrayMap = std::collections::HashMap<String, Vec<XmlElement>>
 
 /// Rule for decomposing a nested tag when using grouped-by-tag strategy.
 /// E.g. write each &lt;objectPermissions&gt; to its own file, or group &lt;fieldPermissions&gt; by object.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DecomposeRule {
     /// Element tag to decompose (e.g. "objectPermissions", "fieldPermissions").
     pub tag: String,
     /// Subdirectory under disassembled path (defaults to tag if empty).
     pub path_segment: String,
-    /// "split" = one file per array item (filename from field); "group" = group by field, one file per group.
-    pub mode: String,
+    /// Split = one file per array item (filename from field); group = group by field, one file per group.
+    pub mode: DecomposeMode,
     /// Field name: for split, used for filename; for group, used to group items.
+    /// Prefix with '@' to use an attribute instead (e.g. "@id", "@xml:lang", or "child/@attr").
     pub field: String,
+    /// Rules applied inside each item written in split mode: matching child tags are moved out of
+    /// the item into `{path_segment}/{item}/{child path_segment}/` (ignored for other modes).
+    pub children: Vec<DecomposeRule>,
+}
+
+impl DecomposeRule {
+    /// Rule for `tag` written under a directory named after the tag, without child rules.
+    pub fn new(tag: impl Into<String>, mode: DecomposeMode, field: impl Into<String>) -> Self {
+        let tag = tag.into();
+        Self {
+            path_segment: tag.clone(),
+            tag,
+            mode,
+            field: field.into(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Use `path_segment` as the directory name instead of the tag.
+    pub fn with_path_segment(mut self, path_segment: impl Into<String>) -> Self {
+        self.path_segment = path_segment.into();
+        self
+    }
+
+    /// Decompose each split item further with `children`.
+    pub fn with_children(mut self, children: Vec<DecomposeRule>) -> Self {
+        self.children = children;
+        self
+    }
+}
+
+/// Rule for moving leaf elements (text-only tags such as &lt;tabs&gt;) out of the base file into
+/// one file per tag (`{tag}.{format}` next to the base file). A tag whose file would be the base
+/// file itself stays in the base file.
+#[derive(Debug, Clone, Default)]
+pub struct LeafSplitRule {
+    /// Leaf tags that always get their own file.
+    pub tags: Vec<String>,
+    /// Any leaf tag repeated more than this many times also gets its own file.
+    pub threshold: Option<usize>,
+}
+
+impl LeafSplitRule {
+    /// True when no tags are listed and no threshold is set.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.threshold.is_none()
+    }
+
+    /// Whether `count` leaf elements named `tag` should be written to their own file.
+    pub fn matches(&self, tag: &str, count: usize) -> bool {
+        self.tags.iter().any(|t| t == tag) || self.threshold.is_some_and(|n| count > n)
+    }
+}
+
+/// Rule for extracting the text or CDATA content of a tag into a raw sidecar file next to the part
+/// (e.g. `htmlContent` → `.html`). The element keeps a reference to the file.
+#[derive(Debug, Clone, Default)]
+pub struct SidecarRule {
+    /// Element tag whose content is extracted.
+    pub tag: String,
+    /// Sidecar file extension without the dot (e.g. "html", "js", "soql"). "bin" decodes base64
+    /// content and writes the raw bytes.
+    pub extension: String,
+    /// Only extract content of at least this many bytes (0 = always).
+    pub min_len: usize,
+}
+
+/// Root element name, attributes and XML declaration of a document disassembled with bare part
+/// files (stored once as .root.json). Reassembly wraps the merged parts in it again.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RootWrapper {
+    pub name: String,
+    /// Root attributes as `@name` keys (e.g. `@xmlns`).
+    pub attributes: serde_json::Map<String, JsonValue>,
+    pub declaration: Option<JsonValue>,
+}
+
+/// Source file of a disassembly (stored as .source.json in the output directory). Reassembly uses
+/// it to rebuild the original file name, including for directories named with the metadata-type
+/// suffix (e.g. `Admin.permissionset/` for `Admin.permissionset-meta.xml`), and a recursive
+/// reassembly to put the file back where it came from, also when it was disassembled into
+/// another directory tree.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SourceMarker {
+    pub file_name: String,
+    /// Source file relative to the directory the disassembly started from (its input root),
+    /// e.g. `sub/Admin.permissionset-meta.xml`. Absent in markers of earlier versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<String>,
+    /// The input root relative to this disassembly directory (e.g. `../../../src`), so the two
+    /// trees can move together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_root: Option<String>,
+}
+
+impl SourceMarker {
+    /// Where the source file was, for the disassembly directory `dir`; `None` for markers
+    /// without a recorded location.
+    pub fn source_location(&self, dir: &Path) -> Option<PathBuf> {
+        let source_path = self.source_path.as_deref()?;
+        let input_root = self.input_root.as_deref()?;
+        Some(crate::utils::normalize_lexically(
+            &dir.join(input_root).join(source_path),
+        ))
+    }
+}
+
+/// Marker written into the directory of a split item that was further decomposed by child rules
+/// (stored as .nested_item.json). Reassembly merges the directory back into a single item.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct NestedItemMarker {
+    /// Tags from below the root down to the item (e.g. ["profileSection"] or
+    /// ["profileSection", "recordTypeVisibilities"]); every part file in the directory is wrapped in them.
+    pub tags: Vec<String>,
+    /// Original child key order of the item.
+    pub key_order: Vec<String>,
 }
 
 /// Options for building disassembled files from a source file.
@@ -76,11 +211,25 @@ pub struct BuildDisassembledFilesOptions<'a> {
     pub disassembled_path: &'a str,
     pub base_name: &'a str,
     pub post_purge: bool,
-    pub format: &'a str,
+    pub format: Format,
     pub unique_id_elements: Option<&'a str>,
-    pub strategy: &'a str,
-    /// When strategy is grouped-by-tag, optionally decompose specific tags (split or group by field).
+    pub strategy: Strategy,
+    /// Under [`Strategy::GroupedByTag`], optionally decompose specific tags (split or group by field).
     pub decompose_rules: Option<&'a [DecomposeRule]>,
+    /// Optionally move selected leaf tags out of the base file into their own files.
+    pub leaf_split: Option<&'a LeafSplitRule>,
+    /// Write bare part files and store the root wrapper once in `.root.json`.
+    pub bare: bool,
+    /// Extract matching text/CDATA content of parts into sidecar files.
+    pub sidecars: Option<&'a [SidecarRule]>,
+    /// Maximum number of part files written concurrently (1 writes them one at a time).
+    pub jobs: usize,
+    /// Record writes and deletions instead of performing them.
+    pub sink: Option<&'a OutputSink>,
+    /// Limits the source file must stay within; `None` applies [`Limits::default`].
+    pub limits: Option<&'a Limits>,
+    /// Run over the parsed source before it is split into parts.
+    pub transforms: &'a [Arc<dyn ElementTransform>],
 }
 
 /// Parameters for writing leaf content.
@@ -88,7 +237,7 @@ pub struct BuildDisassembledFilesOptions<'a> {
 pub struct LeafWriteParams<'a> {
     pub leaf_count: usize,
     pub leaf_content: XmlElement,
-    pub strategy: &'a str,
+    pub strategy: Strategy,
     pub key_order: Vec<String>,
     pub options: LeafWriteOptions<'a>,
 }
@@ -100,7 +249,7 @@ pub struct LeafWriteOptions<'a> {
     pub root_element_name: &'a str,
     pub root_attributes: XmlElement,
     pub xml_declaration: Option<XmlElement>,
-    pub format: &'a str,
+    pub format: Format,
 }
 
 /// Rule for multi-level disassembly: which files to further disassemble and how.
@@ -1,17 +1,23 @@
 //! Async task queue with concurrency control.
 
+#![allow(deprecated)]
+
 use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 
+#[deprecated(
+    note = "the pipeline no longer runs on tokio; use `TaskQueue` for blocking tasks or a tokio semaphore directly"
+)]
 pub struct AsyncTaskQueue {
     semaphore: Arc<Semaphore>,
 }
 
 impl AsyncTaskQueue {
+    /// Queue running at most `concurrency` tasks at a time (at least one).
     pub fn new(concurrency: usize) -> Self {
         Self {
-            semaphore: Arc::new(Semaphore::new(concurrency)),
+            semaphore: Arc::new(Semaphore::new(concurrency.max(1))),
         }
     }
 
@@ -23,11 +29,22 @@ impl AsyncTaskQueue {
         let _permit = self.semaphore.acquire().await.unwrap();
         task.await
     }
+
+    /// Run every task through the queue and return the results in input order.
+    pub async fn run_all<T, F, I>(&self, tasks: I) -> Vec<T>
+    where
+        I: IntoIterator<Item = F>,
+        F: Future<Output = T> + Send,
+        T: Send,
+    {
+        futures::future::join_all(tasks.into_iter().map(|task| self.add(task))).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[tokio::test]
     async fn new_and_add_returns_task_result() {
@@ -37,11 +54,26 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn add_respects_concurrency() {
-        let queue = AsyncTaskQueue::new(1);
-        let a = queue.add(async { 1 });
-        let b = queue.add(async { 2 });
-        let (x, y) = tokio::join!(a, b);
-        assert_eq!((x, y), (1, 2));
+    async fn run_all_keeps_order_and_bounds_concurrency() {
+        let queue = AsyncTaskQueue::new(2);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let results = queue
+            .run_all((0..8u64).map(|i| {
+                let running = &running;
+                let peak = &peak;
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    for _ in 0..8 - i {
+                        tokio::task::yield_now().await;
+                    }
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            }))
+            .await;
+        assert_eq!(results, (0..8).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 2);
     }
 }
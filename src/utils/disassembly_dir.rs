@@ -0,0 +1,158 @@
+//! Recognise directories written by a disassembly, and purge what a disassembly generated.
+
+use crate::sink::OutputSink;
+use crate::storage::Storage;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+
+/// File in each disassembly directory listing the files the last disassembly generated. It marks
+/// the directory as created by a disassembly: only directories with a manifest are purged.
+pub const MANIFEST_FILE: &str = ".manifest.json";
+
+/// File in a disassembly directory mapping its generated files to content hashes (written with
+/// `DisassembleOptionsBuilder::hash_index`).
+pub const HASH_INDEX_FILE: &str = ".hashes.json";
+
+/// Marker files a disassembly writes into its output directory.
+pub const DISASSEMBLY_MARKERS: [&str; 5] = [
+    ".key_order.json",
+    ".root.json",
+    ".source.json",
+    ".multi_level.json",
+    MANIFEST_FILE,
+];
+
+/// Whether `dir` holds the output of a disassembly (contains one of [`DISASSEMBLY_MARKERS`]).
+pub fn is_disassembly_dir(storage: &dyn Storage, dir: &Path) -> bool {
+    DISASSEMBLY_MARKERS
+        .iter()
+        .any(|marker| storage.is_file(&dir.join(marker)))
+}
+
+/// Read the `.manifest.json` of a disassembly directory, if present.
+pub fn read_manifest(storage: &dyn Storage, dir: &Path) -> Option<Vec<String>> {
+    let bytes = storage.read(&dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Whether a manifest entry is a plain relative path, so it cannot point outside its directory.
+pub fn is_plain_relative(entry: &str) -> bool {
+    !entry.is_empty()
+        && Path::new(entry)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Files below `dir` as sorted relative paths with `/` separators (none when it does not exist).
+pub fn relative_files(storage: &dyn Storage, dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = storage.list(&current) else {
+            continue;
+        };
+        for path in entries {
+            if storage.is_dir(&path) {
+                stack.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Delete what a disassembly generated in `dir` through `sink`: the files listed in its manifest,
+/// the manifest and hash index themselves, and the directories left empty. A directory without a
+/// manifest is left alone, since it may not have been created by a disassembly; other files are
+/// kept. Returns whether `dir` was purged.
+pub fn purge_generated(sink: &OutputSink, dir: &Path) -> std::io::Result<bool> {
+    let storage = sink.storage();
+    let Some(manifest) = read_manifest(storage, dir) else {
+        if storage.exists(dir) {
+            log::warn!(
+                "Not purging {}: it has no {} showing it was created by a disassembly",
+                dir.display(),
+                MANIFEST_FILE
+            );
+        }
+        return Ok(false);
+    };
+    let generated: HashSet<String> = manifest
+        .into_iter()
+        .filter(|entry| is_plain_relative(entry))
+        .chain([MANIFEST_FILE.to_string(), HASH_INDEX_FILE.to_string()])
+        .collect();
+    let mut kept = 0usize;
+    for file in sink.files_under(dir) {
+        if !generated.contains(&file) {
+            kept += 1;
+            continue;
+        }
+        let path = dir.join(&file);
+        sink.remove_file(&path)?;
+        sink.prune_empty_parents(&path, dir, true);
+    }
+    if kept > 0 {
+        log::warn!(
+            "Kept {} file(s) in {} that were not generated by a disassembly",
+            kept,
+            dir.display()
+        );
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    #[test]
+    fn detects_marker_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_disassembly_dir(&LocalStorage, dir.path()));
+        std::fs::write(dir.path().join(".key_order.json"), "[]").unwrap();
+        assert!(is_disassembly_dir(&LocalStorage, dir.path()));
+    }
+
+    #[test]
+    fn manifest_entries_must_stay_inside_the_directory() {
+        assert!(is_plain_relative("a/b.xml"));
+        assert!(!is_plain_relative("../b.xml"));
+        assert!(!is_plain_relative("/etc/passwd"));
+        assert!(!is_plain_relative(""));
+    }
+
+    #[test]
+    fn purge_deletes_only_generated_files() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("parts");
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        std::fs::write(dir.join("a/x.xml"), "x").unwrap();
+        std::fs::write(dir.join("b/y.xml"), "y").unwrap();
+        std::fs::write(dir.join("b/notes.md"), "mine").unwrap();
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            r#"["a/x.xml","b/y.xml","../outside"]"#,
+        )
+        .unwrap();
+        let unowned = root.path().join("unowned");
+        std::fs::create_dir(&unowned).unwrap();
+        std::fs::write(unowned.join("z.xml"), "z").unwrap();
+
+        let sink = OutputSink::new(false);
+        assert!(purge_generated(&sink, &dir).unwrap());
+        assert_eq!(relative_files(&LocalStorage, &dir), vec!["b/notes.md"]);
+        assert!(!dir.join("a").exists());
+
+        assert!(!purge_generated(&sink, &unowned).unwrap());
+        assert_eq!(relative_files(&LocalStorage, &unowned), vec!["z.xml"]);
+    }
+}
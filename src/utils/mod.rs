@@ -1,5 +1,15 @@
+#[cfg(feature = "async")]
 mod async_queue;
+mod disassembly_dir;
 mod path;
+mod task_queue;
 
+#[cfg(feature = "async")]
+#[allow(deprecated)]
 pub use async_queue::AsyncTaskQueue;
-pub use path::normalize_path_unix;
+pub use disassembly_dir::{
+    is_disassembly_dir, is_plain_relative, purge_generated, read_manifest, relative_files,
+    DISASSEMBLY_MARKERS, HASH_INDEX_FILE, MANIFEST_FILE,
+};
+pub use path::{canonicalize_partial, normalize_lexically, normalize_path_unix, relative_path};
+pub use task_queue::{collect_task_errors, TaskQueue};
@@ -1,5 +1,8 @@
 //! Path normalization for cross-platform consistency.
 
+use crate::storage::Storage;
+use std::path::{Component, Path, PathBuf};
+
 /// Normalize a path to Unix style (forward slashes).
 /// Strips Windows extended path prefix `\\?\` if present so file operations
 /// behave consistently across platforms.
@@ -8,10 +11,108 @@ pub fn normalize_path_unix(path: &str) -> String {
     s.replace('\\', "/")
 }
 
+/// Absolute form of `path` in `storage` (see [`Storage::canonicalize`]), also for a path that
+/// does not exist yet: its longest existing ancestor is canonicalized and the rest appended.
+pub fn canonicalize_partial(storage: &dyn Storage, path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while !existing.as_os_str().is_empty() && !storage.exists(existing) {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    let mut absolute = if existing.as_os_str().is_empty() {
+        storage.canonicalize(Path::new("."))
+    } else {
+        storage.canonicalize(existing)
+    };
+    absolute.extend(missing.iter().rev());
+    absolute
+}
+
+/// Resolve `.` and `..` components without touching the file system. A `..` at the start of a
+/// relative path is kept.
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) {
+                    out.pop();
+                } else if !out.has_root() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// `path` relative to the directory `base`, from their components alone (normalize both first).
+/// `None` when they do not share a root, e.g. one is absolute and the other is not.
+pub fn relative_path(base: &Path, path: &Path) -> Option<PathBuf> {
+    let base: Vec<Component> = base
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let path: Vec<Component> = path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    if base
+        .first()
+        .is_some_and(|c| !matches!(c, Component::Normal(_)))
+        != path
+            .first()
+            .is_some_and(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    if base[common..].contains(&Component::ParentDir)
+        || (common == 0 && !matches!(base.first(), None | Some(Component::Normal(_))))
+    {
+        return None;
+    }
+    let mut relative: PathBuf = base[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(&path[common..]);
+    Some(relative)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn relative_path_walks_up_to_the_common_ancestor() {
+        assert_eq!(
+            relative_path(Path::new("/w/out/sub/Admin"), Path::new("/w/src")),
+            Some(PathBuf::from("../../../src"))
+        );
+        assert_eq!(
+            relative_path(Path::new("src"), Path::new("src/sub/A.xml")),
+            Some(PathBuf::from("sub/A.xml"))
+        );
+        assert_eq!(relative_path(Path::new("/w"), Path::new("w")), None);
+        assert_eq!(
+            normalize_lexically(Path::new("/w/out/sub/Admin/../../../src/./sub/A.xml")),
+            PathBuf::from("/w/src/sub/A.xml")
+        );
+        assert_eq!(
+            normalize_lexically(Path::new("out/x/../../../src")),
+            PathBuf::from("../src")
+        );
+    }
+
     #[test]
     fn leaves_unix_paths_unchanged() {
         assert_eq!(normalize_path_unix("foo/bar/baz"), "foo/bar/baz");
@@ -0,0 +1,158 @@
+//! Task queue running closures on a bounded number of threads.
+
+use crate::error::Error;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Stack size of worker threads: parsed documents are walked recursively, so workers get as much
+/// stack as the main thread.
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+pub struct TaskQueue {
+    concurrency: usize,
+}
+
+impl TaskQueue {
+    /// Queue running at most `concurrency` tasks at a time (at least one).
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    /// Run every task through the queue and return the results in input order. With a
+    /// concurrency of one (or a single task) the tasks run on the calling thread, one after the
+    /// other; otherwise on scoped worker threads, which may borrow from the caller. When a worker
+    /// thread cannot be started, the calling thread runs the remaining tasks itself.
+    pub fn run_all<T, F, I>(&self, tasks: I) -> Vec<T>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce() -> T + Send,
+        T: Send,
+    {
+        let tasks: VecDeque<(usize, F)> = tasks.into_iter().enumerate().collect();
+        let workers = self.concurrency.min(tasks.len());
+        if workers <= 1 {
+            return tasks.into_iter().map(|(_, task)| task()).collect();
+        }
+        let total = tasks.len();
+        let pending = Mutex::new(tasks);
+        let results: Mutex<Vec<Option<T>>> = Mutex::new((0..total).map(|_| None).collect());
+        let work = || loop {
+            let next = pending.lock().unwrap().pop_front();
+            let Some((idx, task)) = next else {
+                break;
+            };
+            let result = task();
+            results.lock().unwrap()[idx] = Some(result);
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                let spawned = std::thread::Builder::new()
+                    .stack_size(WORKER_STACK_SIZE)
+                    .spawn_scoped(scope, work);
+                if let Err(err) = spawned {
+                    log::warn!(
+                        "Could not start a worker thread ({}); running the remaining tasks on the current thread",
+                        err
+                    );
+                    work();
+                    break;
+                }
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.expect("every task ran"))
+            .collect()
+    }
+}
+
+type TaskResult = Result<(), Error>;
+
+/// Combine the outcome of labelled tasks: Ok when all succeeded, the error itself when exactly one
+/// failed or the run was [`Error::Cancelled`], otherwise [`Error::Tasks`] listing every failure.
+pub fn collect_task_errors(results: Vec<(String, TaskResult)>) -> TaskResult {
+    let total = results.len();
+    let mut failures: Vec<(String, Error)> = results
+        .into_iter()
+        .filter_map(|(label, result)| result.err().map(|err| (label, err)))
+        .collect();
+    if failures
+        .iter()
+        .any(|(_, err)| matches!(err, Error::Cancelled))
+    {
+        return Err(Error::Cancelled);
+    }
+    match failures.len() {
+        0 => Ok(()),
+        1 => Err(failures.remove(0).1),
+        _ => Err(Error::Tasks { total, failures }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn run_all_runs_inline_with_one_job() {
+        let queue = TaskQueue::new(0);
+        let caller = std::thread::current().id();
+        let results = queue.run_all((0..3).map(|i| move || (i, std::thread::current().id())));
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|(_, id)| *id == caller));
+        assert_eq!(
+            results.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn run_all_keeps_order_and_bounds_concurrency() {
+        let queue = TaskQueue::new(2);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let results = queue.run_all((0..8u64).map(|i| {
+            let running = &running;
+            let peak = &peak;
+            move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(8 - i));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        }));
+        assert_eq!(results, (0..8).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn collect_task_errors_reports_every_failure() {
+        let failure = |message: &str| Err(io::Error::other(message.to_string()).into());
+        assert!(collect_task_errors(vec![("a".into(), Ok(()))]).is_ok());
+        let single = collect_task_errors(vec![("a".into(), failure("boom"))]).unwrap_err();
+        assert_eq!(single.to_string(), "boom");
+        let many = collect_task_errors(vec![
+            ("a".into(), failure("one")),
+            ("b".into(), Ok(())),
+            ("c".into(), failure("two")),
+        ])
+        .unwrap_err();
+        assert!(matches!(&many, Error::Tasks { total: 3, failures } if failures.len() == 2));
+        let many = many.to_string();
+        assert!(many.starts_with("2 of 3 tasks failed"));
+        assert!(many.contains("a: one") && many.contains("c: two"));
+        let cancelled = collect_task_errors(vec![
+            ("a".into(), failure("one")),
+            ("b".into(), Err(Error::Cancelled)),
+        ])
+        .unwrap_err();
+        assert!(matches!(cancelled, Error::Cancelled));
+    }
+}
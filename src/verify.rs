@@ -0,0 +1,681 @@
+//! Round-trip verification: disassemble a file into a temporary directory, reassemble it and
+//! compare the result with the original.
+
+use crate::handlers::{DisassembleXmlFileHandler, ReassembleXmlFileHandler};
+use crate::options::{DisassembleOptions, ReassembleOptions};
+use crate::storage::{LocalStorage, Storage};
+use crate::utils::is_disassembly_dir;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use similar::{DiffOp, TextDiff};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How the reassembled file is compared with the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareMode {
+    /// Compare the parsed documents: element order, attributes, text, CDATA and comments.
+    /// Insignificant whitespace between elements is ignored.
+    #[default]
+    Semantic,
+    /// Compare the files byte for byte, reporting differing lines.
+    Bytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifferenceKind {
+    /// Present in the original, missing after the round trip.
+    Missing,
+    /// Not in the original, present after the round trip.
+    Added,
+    /// Present in both with a different value.
+    Changed,
+    /// Same content in a different order.
+    Reordered,
+}
+
+impl fmt::Display for DifferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DifferenceKind::Missing => "missing",
+            DifferenceKind::Added => "added",
+            DifferenceKind::Changed => "changed",
+            DifferenceKind::Reordered => "reordered",
+        })
+    }
+}
+
+/// One difference between the original and the reassembled file. `path` locates it: an
+/// element path such as `/PermissionSet/fieldPermissions[2]/editable` (1-based indexes) in
+/// semantic mode, `line N` in byte mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub path: String,
+    pub kind: DifferenceKind,
+    pub detail: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.kind, self.path, self.detail)
+    }
+}
+
+/// Result of [`verify_round_trip`].
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub file: PathBuf,
+    pub mode: CompareMode,
+    pub differences: Vec<Difference>,
+}
+
+impl VerifyReport {
+    /// Whether the reassembled file matched the original.
+    pub fn is_lossless(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            CompareMode::Semantic => "semantic",
+            CompareMode::Bytes => "byte",
+        };
+        if self.is_lossless() {
+            return writeln!(
+                f,
+                "{}: round trip is lossless ({} comparison)",
+                self.file.display(),
+                mode
+            );
+        }
+        writeln!(
+            f,
+            "{}: {} difference(s) after round trip ({} comparison)",
+            self.file.display(),
+            self.differences.len(),
+            mode
+        )?;
+        for difference in &self.differences {
+            writeln!(f, "  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`verify_round_trip`]: how to disassemble (purging, the ignore file, the output
+/// directory and dry-run mode do not apply) and how to compare.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    pub disassemble: DisassembleOptions,
+    pub mode: CompareMode,
+}
+
+/// Disassemble `file_path` with `handler` (its observer and cancellation apply) into a temporary
+/// directory, reassemble it and compare the result with the original. The
+/// original file is never modified. Both are on the local file system, whatever storage the
+/// handler was given.
+pub fn verify_round_trip(
+    handler: DisassembleXmlFileHandler,
+    file_path: &str,
+    options: &VerifyOptions,
+) -> Result<VerifyReport, Box<dyn std::error::Error + Send + Sync>> {
+    let source = Path::new(file_path);
+    let original = fs::read(source)?;
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", file_path))?;
+
+    let work = tempfile::tempdir()?;
+    let input_dir = work.path().join("input");
+    let output_dir = work.path().join("output");
+    fs::create_dir_all(&input_dir)?;
+    let copy = input_dir.join(file_name);
+    fs::write(&copy, &original)?;
+
+    let disassemble_options = DisassembleOptions {
+        pre_purge: false,
+        post_purge: false,
+        ignore_path: work
+            .path()
+            .join(".xmldisassemblerignore")
+            .to_string_lossy()
+            .into_owned(),
+        output_dir: Some(output_dir.clone()),
+        dry_run: false,
+        ..options.disassemble.clone()
+    };
+    let mut handler = handler.with_storage(Arc::new(LocalStorage));
+    handler.disassemble(&copy.to_string_lossy(), &disassemble_options)?;
+    if !contains_disassembly_dir(&output_dir) {
+        return Err(format!("{} was not disassembled; nothing to verify", file_path).into());
+    }
+
+    // Into the output tree, not back over the copy of the source.
+    let reassemble_options = ReassembleOptions::builder()
+        .recursive(true)
+        .output_dir(&output_dir)
+        .build()?;
+    ReassembleXmlFileHandler::new()
+        .reassemble(&output_dir.to_string_lossy(), &reassemble_options)?;
+    let rebuilt = fs::read(output_dir.join(file_name))
+        .map_err(|_| format!("reassembling {} produced no file", file_path))?;
+
+    let differences = match options.mode {
+        CompareMode::Semantic => compare_semantic(&original, &rebuilt, file_path)?,
+        CompareMode::Bytes => compare_bytes(&original, &rebuilt),
+    };
+    Ok(VerifyReport {
+        file: source.to_path_buf(),
+        mode: options.mode,
+        differences,
+    })
+}
+
+fn contains_disassembly_dir(dir: &Path) -> bool {
+    let Ok(entries) = LocalStorage.list(dir) else {
+        return false;
+    };
+    entries
+        .iter()
+        .any(|path| path.is_dir() && is_disassembly_dir(&LocalStorage, path))
+}
+
+/// Compare two XML documents structurally, in document order.
+pub fn compare_semantic(
+    original: &[u8],
+    rebuilt: &[u8],
+    file_path: &str,
+) -> Result<Vec<Difference>, Box<dyn std::error::Error + Send + Sync>> {
+    let expected = parse_nodes(&String::from_utf8_lossy(original))
+        .map_err(|err| format!("{} could not be parsed: {}", file_path, err))?;
+    let found = parse_nodes(&String::from_utf8_lossy(rebuilt))
+        .map_err(|err| format!("reassembled {} could not be parsed: {}", file_path, err))?;
+    let mut differences = Vec::new();
+    diff_nodes("", &expected, &found, &mut differences);
+    Ok(differences)
+}
+
+/// Compare two files byte for byte; each differing run of lines is one difference.
+pub fn compare_bytes(original: &[u8], rebuilt: &[u8]) -> Vec<Difference> {
+    if original == rebuilt {
+        return Vec::new();
+    }
+    let old = String::from_utf8_lossy(original);
+    let new = String::from_utf8_lossy(rebuilt);
+    let diff = TextDiff::from_lines(old.as_ref(), new.as_ref());
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let mut differences = Vec::new();
+    for op in diff.ops() {
+        let difference = match *op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => Difference {
+                path: format!("line {}", old_index + 1),
+                kind: DifferenceKind::Missing,
+                detail: format!(
+                    "{} line(s) starting {:?}",
+                    old_len,
+                    line_at(&old_lines, old_index)
+                ),
+            },
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => Difference {
+                path: format!("line {}", old_index + 1),
+                kind: DifferenceKind::Added,
+                detail: format!(
+                    "{} line(s) starting {:?}",
+                    new_len,
+                    line_at(&new_lines, new_index)
+                ),
+            },
+            DiffOp::Replace {
+                old_index,
+                new_index,
+                ..
+            } => Difference {
+                path: format!("line {}", old_index + 1),
+                kind: DifferenceKind::Changed,
+                detail: format!(
+                    "expected {:?}, found {:?}",
+                    line_at(&old_lines, old_index),
+                    line_at(&new_lines, new_index)
+                ),
+            },
+        };
+        differences.push(difference);
+    }
+    if differences.is_empty() {
+        // Same lines, different bytes (e.g. only a trailing newline differs).
+        differences.push(Difference {
+            path: format!("line {}", old_lines.len().max(1)),
+            kind: DifferenceKind::Changed,
+            detail: "line endings differ".to_string(),
+        });
+    }
+    differences
+}
+
+fn line_at<'a>(lines: &[&'a str], idx: usize) -> &'a str {
+    lines.get(idx).map(|l| l.trim_end()).unwrap_or("")
+}
+
+/// A node of a document as the semantic comparison sees it. Children keep document order;
+/// text is trimmed, and whitespace-only text (indentation) is dropped. The declaration,
+/// processing instructions and the doctype are not compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    /// Attributes are sorted by name: their order carries no meaning.
+    Element {
+        name: String,
+        attributes: Vec<(String, String)>,
+        children: Vec<Node>,
+    },
+    Text(String),
+    CData(String),
+    Comment(String),
+}
+
+impl Node {
+    /// Path step of the node, XPath style for text and comment nodes.
+    fn step(&self) -> &str {
+        match self {
+            Node::Element { name, .. } => name,
+            Node::Text(_) => "text()",
+            Node::CData(_) => "cdata()",
+            Node::Comment(_) => "comment()",
+        }
+    }
+
+    /// Short description of the node for reports.
+    fn summary(&self) -> String {
+        let text = match self {
+            Node::Element { name, children, .. } => match children.as_slice() {
+                [] => format!("<{}/>", name),
+                [Node::Text(text) | Node::CData(text)] => format!("<{0}>{1}</{0}>", name, text),
+                _ => format!("<{0}>...</{0}>", name),
+            },
+            Node::Text(content) | Node::CData(content) | Node::Comment(content) => {
+                format!("{:?}", content)
+            }
+        };
+        if text.chars().count() > 80 {
+            format!("{}...", text.chars().take(77).collect::<String>())
+        } else {
+            text
+        }
+    }
+}
+
+/// Parse `xml` into its top-level nodes (see [`Node`]).
+fn parse_nodes(xml: &str) -> Result<Vec<Node>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut top = Vec::new();
+    // Open elements, innermost last.
+    let mut open: Vec<Node> = Vec::new();
+    // Raw text and entity references, unescaped once the text ends.
+    let mut raw = String::new();
+    loop {
+        let event = reader.read_event()?;
+        if !matches!(event, Event::Text(_) | Event::GeneralRef(_)) && !raw.is_empty() {
+            let text = unescape(&raw).map_err(quick_xml::Error::from)?;
+            let text = text.trim();
+            if !text.is_empty() {
+                push_node(&mut top, &mut open, Node::Text(text.to_string()));
+            }
+            raw.clear();
+        }
+        let node = match event {
+            Event::Start(e) => {
+                open.push(Node::Element {
+                    name: lossy(e.name().as_ref()),
+                    attributes: attributes(&reader, &e)?,
+                    children: Vec::new(),
+                });
+                continue;
+            }
+            Event::Empty(e) => Node::Element {
+                name: lossy(e.name().as_ref()),
+                attributes: attributes(&reader, &e)?,
+                children: Vec::new(),
+            },
+            Event::End(_) => match open.pop() {
+                Some(element) => element,
+                None => continue,
+            },
+            Event::Text(e) => {
+                raw.push_str(&e.decode()?);
+                continue;
+            }
+            Event::GeneralRef(e) => {
+                raw.push('&');
+                raw.push_str(&lossy(e.as_ref()));
+                raw.push(';');
+                continue;
+            }
+            Event::CData(e) => Node::CData(lossy(e.as_ref())),
+            Event::Comment(e) => Node::Comment(e.decode()?.trim().to_string()),
+            Event::Eof => break,
+            _ => continue,
+        };
+        push_node(&mut top, &mut open, node);
+    }
+    Ok(top)
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Add `node` to the innermost open element, or to the top level.
+fn push_node(top: &mut Vec<Node>, open: &mut [Node], node: Node) {
+    match open.last_mut() {
+        Some(Node::Element { children, .. }) => children.push(node),
+        _ => top.push(node),
+    }
+}
+
+/// Unescaped attributes of an element, sorted by name.
+fn attributes(
+    reader: &Reader<&[u8]>,
+    element: &BytesStart<'_>,
+) -> Result<Vec<(String, String)>, quick_xml::Error> {
+    let mut attributes = Vec::new();
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let value = attribute.decode_and_unescape_value(reader.decoder())?;
+        attributes.push((lossy(attribute.key.as_ref()), value.into_owned()));
+    }
+    attributes.sort();
+    Ok(attributes)
+}
+
+/// Path of the child `step` of `path`.
+fn child_path(path: &str, step: &str) -> String {
+    format!("{}/{}", path, step)
+}
+
+/// Each node with its step and 1-based occurrence among the siblings of that step.
+fn keyed(nodes: &[Node]) -> Vec<(&str, usize)> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    nodes
+        .iter()
+        .map(|node| {
+            let count = seen.entry(node.step()).or_default();
+            *count += 1;
+            (node.step(), *count)
+        })
+        .collect()
+}
+
+/// Whether `found` holds exactly the nodes of `expected` in some order.
+fn is_permutation(expected: &[Node], found: &[Node]) -> bool {
+    if expected.len() != found.len() {
+        return false;
+    }
+    let mut used = vec![false; found.len()];
+    expected.iter().all(
+        |e| match (0..found.len()).find(|&i| !used[i] && &found[i] == e) {
+            Some(i) => {
+                used[i] = true;
+                true
+            }
+            None => false,
+        },
+    )
+}
+
+/// Compare the children of the node at `path`. Siblings are matched by step and occurrence
+/// (the second `item` with the second `item`), so a changed value is reported where it is, and
+/// a different order of the matched siblings, also across steps, is reported at `path`.
+fn diff_nodes(path: &str, expected: &[Node], found: &[Node], out: &mut Vec<Difference>) {
+    if expected == found {
+        return;
+    }
+    let parent = if path.is_empty() { "/" } else { path };
+    if is_permutation(expected, found) {
+        // Only the order changed: at the siblings' own path when they share a step.
+        let moved: Vec<&str> = expected
+            .iter()
+            .zip(found)
+            .filter(|(e, f)| e != f)
+            .map(|(e, _)| e.step())
+            .collect();
+        let difference = match moved.split_first() {
+            Some((step, rest)) if rest.iter().all(|s| s == step) => Difference {
+                path: child_path(path, step),
+                kind: DifferenceKind::Reordered,
+                detail: format!(
+                    "{} sibling elements in a different order",
+                    expected.iter().filter(|n| n.step() == *step).count()
+                ),
+            },
+            _ => Difference {
+                path: parent.to_string(),
+                kind: DifferenceKind::Reordered,
+                detail: format!(
+                    "expected children {:?}, found {:?}",
+                    expected.iter().map(Node::step).collect::<Vec<_>>(),
+                    found.iter().map(Node::step).collect::<Vec<_>>()
+                ),
+            },
+        };
+        out.push(difference);
+        return;
+    }
+
+    let expected_keys = keyed(expected);
+    let found_keys = keyed(found);
+    let common = |keys: &[(&str, usize)], other: &[(&str, usize)]| -> Vec<String> {
+        keys.iter()
+            .filter(|key| other.contains(key))
+            .map(|(step, n)| format!("{}[{}]", step, n))
+            .collect()
+    };
+    let expected_order = common(&expected_keys, &found_keys);
+    let found_order = common(&found_keys, &expected_keys);
+    if expected_order != found_order {
+        out.push(Difference {
+            path: parent.to_string(),
+            kind: DifferenceKind::Reordered,
+            detail: format!(
+                "expected children {:?}, found {:?}",
+                expected_order, found_order
+            ),
+        });
+    }
+
+    // Indexed only when a step occurs more than once on either side.
+    let total = |step: &str| {
+        let count = |keys: &[(&str, usize)]| keys.iter().filter(|(s, _)| *s == step).count();
+        count(&expected_keys).max(count(&found_keys))
+    };
+    let item_path = |(step, n): (&str, usize)| {
+        if total(step) > 1 {
+            format!("{}[{}]", child_path(path, step), n)
+        } else {
+            child_path(path, step)
+        }
+    };
+    for (node, key) in expected.iter().zip(&expected_keys) {
+        match found_keys.iter().position(|k| k == key) {
+            Some(i) => diff_node(&item_path(*key), node, &found[i], out),
+            None => out.push(Difference {
+                path: item_path(*key),
+                kind: DifferenceKind::Missing,
+                detail: node.summary(),
+            }),
+        }
+    }
+    for (node, key) in found.iter().zip(&found_keys) {
+        if !expected_keys.contains(key) {
+            out.push(Difference {
+                path: item_path(*key),
+                kind: DifferenceKind::Added,
+                detail: node.summary(),
+            });
+        }
+    }
+}
+
+/// Compare two nodes with the same step.
+fn diff_node(path: &str, expected: &Node, found: &Node, out: &mut Vec<Difference>) {
+    let (
+        Node::Element {
+            attributes: expected_attributes,
+            children: expected_children,
+            ..
+        },
+        Node::Element {
+            attributes: found_attributes,
+            children: found_children,
+            ..
+        },
+    ) = (expected, found)
+    else {
+        if expected != found {
+            out.push(Difference {
+                path: path.to_string(),
+                kind: DifferenceKind::Changed,
+                detail: format!("expected {}, found {}", expected.summary(), found.summary()),
+            });
+        }
+        return;
+    };
+    let value = |attributes: &[(String, String)], name: &str| {
+        attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    };
+    for (name, expected_value) in expected_attributes {
+        let path = format!("{}/@{}", path, name);
+        match value(found_attributes, name) {
+            Some(found_value) if found_value == *expected_value => {}
+            Some(found_value) => out.push(Difference {
+                path,
+                kind: DifferenceKind::Changed,
+                detail: format!("expected {:?}, found {:?}", expected_value, found_value),
+            }),
+            None => out.push(Difference {
+                path,
+                kind: DifferenceKind::Missing,
+                detail: format!("{:?}", expected_value),
+            }),
+        }
+    }
+    for (name, found_value) in found_attributes {
+        if value(expected_attributes, name).is_none() {
+            out.push(Difference {
+                path: format!("{}/@{}", path, name),
+                kind: DifferenceKind::Added,
+                detail: format!("{:?}", found_value),
+            });
+        }
+    }
+    diff_nodes(path, expected_children, found_children, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Root>
+    <!-- keep me -->
+    <item>
+        <name>a</name>
+        <value>1</value>
+    </item>
+    <item>
+        <name>b</name>
+        <value>2</value>
+    </item>
+    <flag>true</flag>
+</Root>
+"#;
+
+    fn semantic(rebuilt: &str) -> Vec<Difference> {
+        compare_semantic(ORIGINAL.as_bytes(), rebuilt.as_bytes(), "test.xml").unwrap()
+    }
+
+    #[test]
+    fn semantic_comparison_ignores_formatting() {
+        let compact = ORIGINAL.replace("\n    ", "\n").replace("\n        ", "\n");
+        assert!(semantic(ORIGINAL).is_empty());
+        assert!(semantic(&compact).is_empty());
+    }
+
+    #[test]
+    fn semantic_comparison_reports_changed_values_and_lost_comments() {
+        let changed = ORIGINAL
+            .replace("<value>2</value>", "<value>3</value>")
+            .replace("    <!-- keep me -->\n", "");
+        let differences = semantic(&changed);
+        assert!(differences.iter().any(|d| d.kind == DifferenceKind::Changed
+            && d.path == "/Root/item[2]/value/text()"
+            && d.detail.contains("\"2\"")));
+        assert!(differences
+            .iter()
+            .any(|d| d.kind == DifferenceKind::Missing && d.path == "/Root/comment()"));
+    }
+
+    #[test]
+    fn semantic_comparison_reports_reordered_elements() {
+        let swapped = ORIGINAL.replace(
+            "<item>\n        <name>a</name>\n        <value>1</value>\n    </item>\n    <item>\n        <name>b</name>\n        <value>2</value>\n    </item>",
+            "<item>\n        <name>b</name>\n        <value>2</value>\n    </item>\n    <item>\n        <name>a</name>\n        <value>1</value>\n    </item>",
+        );
+        let differences = semantic(&swapped);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Reordered);
+        assert_eq!(differences[0].path, "/Root/item");
+
+        let moved = ORIGINAL
+            .replace("    <flag>true</flag>\n", "")
+            .replace("<Root>\n", "<Root>\n    <flag>true</flag>\n");
+        assert!(semantic(&moved)
+            .iter()
+            .any(|d| d.kind == DifferenceKind::Reordered && d.path == "/Root"));
+    }
+
+    #[test]
+    fn byte_comparison_reports_lines() {
+        assert!(compare_bytes(b"a\nb\n", b"a\nb\n").is_empty());
+        let differences = compare_bytes(b"a\nb\nc\n", b"a\nB\nc\n");
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, "line 2");
+        assert_eq!(differences[0].kind, DifferenceKind::Changed);
+        let differences = compare_bytes(b"a\n", b"a");
+        assert_eq!(differences.len(), 1);
+    }
+
+    #[test]
+    fn report_display_lists_differences() {
+        let report = VerifyReport {
+            file: PathBuf::from("a.xml"),
+            mode: CompareMode::Semantic,
+            differences: vec![Difference {
+                path: "/Root/x".to_string(),
+                kind: DifferenceKind::Missing,
+                detail: "\"1\"".to_string(),
+            }],
+        };
+        assert!(!report.is_lossless());
+        assert_eq!(
+            report.to_string(),
+            "a.xml: 1 difference(s) after round trip (semantic comparison)\n  missing /Root/x: \"1\"\n"
+        );
+    }
+}
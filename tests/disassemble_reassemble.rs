@@ -3,27 +3,35 @@
 
 use std::path::Path;
 use xml_disassembler::{
-    DecomposeRule, DisassembleXmlFileHandler, MultiLevelRule, ReassembleXmlFileHandler,
+    disassemble_str, reassemble_from_map, CancellationToken, DecomposeMode, DecomposeRule,
+    DisassembleOptions, DisassembleXmlFileHandler, Event, Format, LeafSplitRule, Limits,
+    MemoryStorage, MultiLevelRule, Observer, ReassembleOptions, ReassembleXmlFileHandler,
+    RuleAction, SidecarRule, Storage, Strategy, TransformRule,
 };
 
-#[tokio::test]
-async fn reassemble_with_file_path_returns_ok_no_op() {
+#[test]
+fn reassemble_with_file_path_returns_ok_no_op() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
         Path::new(fixture).exists(),
         "Fixture must exist (run from project root)"
     );
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     // Path is a file, not a directory; validate_directory returns false
     handler
-        .reassemble(fixture, Some("xml"), false)
-        .await
+        .reassemble(
+            fixture,
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble should return Ok(())");
 }
 
-#[tokio::test]
-async fn disassemble_with_unsupported_strategy_defaults_to_unique_id() {
+#[test]
+fn unsupported_strategy_is_rejected_and_default_is_unique_id() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
@@ -34,29 +42,25 @@ async fn disassemble_with_unsupported_strategy_defaults_to_unique_id() {
     let base = temp_dir.path();
     let source = base.join("HR_Admin.permissionset-meta.xml");
     std::fs::copy(fixture, &source).expect("copy fixture");
+    let err = "unsupported-strategy".parse::<Strategy>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown strategy \"unsupported-strategy\" (expected one of: unique-id, grouped-by-tag)"
+    );
+    let options = DisassembleOptions::default();
+    assert_eq!(options.strategy(), Strategy::UniqueId);
     let mut disassemble = DisassembleXmlFileHandler::new();
     disassemble
-        .disassemble(
-            source.to_str().unwrap(),
-            None,
-            Some("unsupported-strategy"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
-        )
-        .await
+        .disassemble(source.to_str().unwrap(), &options)
         .expect("disassemble");
     assert!(
         base.join("HR_Admin").exists(),
-        "Should still disassemble with default strategy"
+        "Should disassemble with the default strategy"
     );
 }
 
-#[tokio::test]
-async fn disassemble_directory_with_ignore_skips_matching_files() {
+#[test]
+fn disassemble_directory_with_ignore_skips_matching_files() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
@@ -78,16 +82,11 @@ async fn disassemble_directory_with_ignore_skips_matching_files() {
     disassemble
         .disassemble(
             dir_path.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            base.join(".xmldisassemblerignore").to_str().unwrap(),
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder()
+                .ignore_path(base.join(".xmldisassemblerignore").to_str().unwrap())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(dir_path.join("A").exists(), "A should be disassembled");
     assert!(
@@ -96,8 +95,8 @@ async fn disassemble_directory_with_ignore_skips_matching_files() {
     );
 }
 
-#[tokio::test]
-async fn disassemble_directory_processes_xml_files() {
+#[test]
+fn disassemble_directory_processes_xml_files() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
@@ -116,25 +115,17 @@ async fn disassemble_directory_processes_xml_files() {
     disassemble
         .disassemble(
             dir_path.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(dir_path.join("A").exists());
     assert!(dir_path.join("B").exists());
 }
 
-#[tokio::test]
-async fn disassemble_directory_ignores_non_xml_files_and_subdirs() {
-    // Directory contains one XML, a non-XML file, and a subdirectory; handle_directory must
-    // skip the non-file / non-XML entries without error.
+#[test]
+fn disassemble_directory_ignores_non_xml_files_and_subdirs() {
+    // Directory contains one XML, a non-XML file, and an empty subdirectory; handle_directory
+    // must skip the non-XML entries without error.
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -148,22 +139,14 @@ async fn disassemble_directory_ignores_non_xml_files_and_subdirs() {
     disassemble
         .disassemble(
             dir_path.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(dir_path.join("A").exists());
 }
 
-#[tokio::test]
-async fn reassemble_with_post_purge_removes_disassembled_dir() {
+#[test]
+fn reassemble_with_post_purge_removes_disassembled_dir() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
@@ -179,22 +162,20 @@ async fn reassemble_with_post_purge_removes_disassembled_dir() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(disassembled_dir.exists());
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("xml"), true)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .post_purge(true)
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
     assert!(
         !disassembled_dir.exists(),
@@ -202,8 +183,8 @@ async fn reassemble_with_post_purge_removes_disassembled_dir() {
     );
 }
 
-#[tokio::test]
-async fn reassemble_applies_key_order_from_dot_key_order_json() {
+#[test]
+fn reassemble_applies_key_order_from_dot_key_order_json() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path();
@@ -214,10 +195,15 @@ async fn reassemble_applies_key_order_from_dot_key_order_json() {
     std::fs::write(disassembled_dir.join("Out.xml"), part_xml).expect("write part");
     let key_order = serde_json::to_string(&["secondKey", "firstKey"]).unwrap();
     std::fs::write(disassembled_dir.join(".key_order.json"), key_order).expect("write key_order");
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
     let out = std::fs::read_to_string(base.join("Out.xml")).expect("read output");
     let second_pos = out.find("<secondKey>").unwrap_or(0);
@@ -228,16 +214,21 @@ async fn reassemble_applies_key_order_from_dot_key_order_json() {
     );
 }
 
-#[tokio::test]
-async fn reassemble_empty_directory_returns_ok_no_output() {
+#[test]
+fn reassemble_empty_directory_returns_ok_no_output() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let empty_dir = temp_dir.path().join("empty");
     std::fs::create_dir_all(&empty_dir).expect("create dir");
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(empty_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            empty_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble should return Ok(())");
     // No output file created when directory has no parsable files
     assert!(!temp_dir.path().join("empty.xml").exists());
@@ -246,8 +237,8 @@ async fn reassemble_empty_directory_returns_ok_no_output() {
 /// Regression test for the `<root></root>` flake: when every parsed file is
 /// empty or declaration-only, `merge_xml_elements` returns None and
 /// `reassemble_plain` must skip writing rather than emit a stub document.
-#[tokio::test]
-async fn reassemble_directory_with_only_empty_xml_files_writes_no_output() {
+#[test]
+fn reassemble_directory_with_only_empty_xml_files_writes_no_output() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let dir = temp_dir.path().join("only_empty");
@@ -262,10 +253,15 @@ async fn reassemble_directory_with_only_empty_xml_files_writes_no_output() {
     )
     .expect("write decl-only xml");
 
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble should return Ok(())");
 
     // No stub file should be produced; the directory's output path must not exist.
@@ -275,8 +271,8 @@ async fn reassemble_directory_with_only_empty_xml_files_writes_no_output() {
     );
 }
 
-#[tokio::test]
-async fn disassemble_non_xml_file_returns_ok_no_op() {
+#[test]
+fn disassemble_non_xml_file_returns_ok_no_op() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path();
@@ -286,16 +282,8 @@ async fn disassemble_non_xml_file_returns_ok_no_op() {
     disassemble
         .disassemble(
             txt_file.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble should return Ok(())");
     assert!(
         !base.join("readme").exists(),
@@ -303,8 +291,8 @@ async fn disassemble_non_xml_file_returns_ok_no_op() {
     );
 }
 
-#[tokio::test]
-async fn disassemble_with_pre_purge_removes_existing_output() {
+#[test]
+fn disassemble_with_pre_purge_removes_only_generated_output() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     assert!(
@@ -316,33 +304,49 @@ async fn disassemble_with_pre_purge_removes_existing_output() {
     let source = base.join("HR_Admin.permissionset-meta.xml");
     std::fs::copy(fixture, &source).expect("copy");
     let out_dir = base.join("HR_Admin");
+    let run = |pre_purge: bool| {
+        let source = source.clone();
+        {
+            DisassembleXmlFileHandler::new()
+                .disassemble(
+                    source.to_str().unwrap(),
+                    &DisassembleOptions::builder()
+                        .pre_purge(pre_purge)
+                        .build()
+                        .unwrap(),
+                )
+                .expect("disassemble");
+        }
+    };
+
+    // A user folder that happens to share the base name is not purged.
     std::fs::create_dir_all(&out_dir).expect("create");
-    let marker = out_dir.join("pre-existing.txt");
-    std::fs::write(&marker, "before").expect("write");
-    let mut disassemble = DisassembleXmlFileHandler::new();
-    disassemble
-        .disassemble(
-            source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            true, // pre_purge
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
-        )
-        .await
-        .expect("disassemble");
-    assert!(out_dir.exists());
+    let user_file = out_dir.join("pre-existing.txt");
+    std::fs::write(&user_file, "before").expect("write");
+    run(true);
+    assert!(user_file.exists(), "no manifest: nothing is purged");
+    assert!(out_dir.join(".manifest.json").exists());
+
+    // Once owned, pre-purge removes the parts of deleted elements but keeps the user file.
+    let original = std::fs::read_to_string(&source).unwrap();
+    let start = original.find("    <tabSettings>").expect("tabSettings");
+    let end = original.rfind("</tabSettings>\n").unwrap() + "</tabSettings>\n".len();
+    std::fs::write(
+        &source,
+        format!("{}{}", &original[..start], &original[end..]),
+    )
+    .unwrap();
+    run(true);
+    assert!(out_dir.join("fieldPermissions").exists());
     assert!(
-        !marker.exists(),
-        "pre_purge should remove existing output dir contents"
+        !out_dir.join("tabSettings").exists(),
+        "generated parts are purged"
     );
+    assert!(user_file.exists(), "files not generated are kept");
 }
 
-#[tokio::test]
-async fn disassemble_then_reassemble_matches_original_xml() {
+#[test]
+fn disassemble_then_reassemble_matches_original_xml() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
@@ -367,16 +371,8 @@ async fn disassemble_then_reassemble_matches_original_xml() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -384,10 +380,15 @@ async fn disassemble_then_reassemble_matches_original_xml() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
 
     let reassembled_path = base.join("HR_Admin.xml");
@@ -401,8 +402,8 @@ async fn disassemble_then_reassemble_matches_original_xml() {
     );
 }
 
-#[tokio::test]
-async fn disassemble_json_format_then_reassemble_round_trip() {
+#[test]
+fn disassemble_json_format_then_reassemble_round_trip() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
@@ -424,16 +425,11 @@ async fn disassemble_json_format_then_reassemble_round_trip() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "json",
-            None,
-            None,
+            &DisassembleOptions::builder()
+                .format(Format::Json)
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -441,10 +437,15 @@ async fn disassemble_json_format_then_reassemble_round_trip() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("json"), false)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("json")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
 
     let reassembled_path = base.join("HR_Admin.json");
@@ -457,8 +458,8 @@ async fn disassemble_json_format_then_reassemble_round_trip() {
     );
 }
 
-#[tokio::test]
-async fn cdata_preserved_round_trip() {
+#[test]
+fn cdata_preserved_round_trip() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/cdata/VidLand_US.marketingappextension-meta.xml";
@@ -481,16 +482,8 @@ async fn cdata_preserved_round_trip() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -498,14 +491,15 @@ async fn cdata_preserved_round_trip() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
         .reassemble(
             disassembled_dir.to_str().unwrap(),
-            Some("marketingappextension-meta.xml"),
-            false,
+            &ReassembleOptions::builder()
+                .extension("marketingappextension-meta.xml")
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("reassemble");
 
     let reassembled_path = base.join("VidLand_US.marketingappextension-meta.xml");
@@ -519,8 +513,8 @@ async fn cdata_preserved_round_trip() {
     );
 }
 
-#[tokio::test]
-async fn comments_preserved_round_trip() {
+#[test]
+fn comments_preserved_round_trip() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/comments/Numbers-fr.globalValueSetTranslation-meta.xml";
@@ -543,16 +537,8 @@ async fn comments_preserved_round_trip() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -560,14 +546,15 @@ async fn comments_preserved_round_trip() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
         .reassemble(
             disassembled_dir.to_str().unwrap(),
-            Some("globalValueSetTranslation-meta.xml"),
-            false,
+            &ReassembleOptions::builder()
+                .extension("globalValueSetTranslation-meta.xml")
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("reassemble");
 
     let reassembled_path = base.join("Numbers-fr.globalValueSetTranslation-meta.xml");
@@ -581,8 +568,8 @@ async fn comments_preserved_round_trip() {
     );
 }
 
-#[tokio::test]
-async fn deeply_nested_unique_id_elements_round_trip() {
+#[test]
+fn deeply_nested_unique_id_elements_round_trip() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/deeply-nested-unique-id-element/Get_Info.flow-meta.xml";
@@ -608,16 +595,11 @@ async fn deeply_nested_unique_id_elements_round_trip() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            Some(unique_id_elements),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements(unique_id_elements)
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -625,14 +607,15 @@ async fn deeply_nested_unique_id_elements_round_trip() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
         .reassemble(
             disassembled_dir.to_str().unwrap(),
-            Some("flow-meta.xml"),
-            false,
+            &ReassembleOptions::builder()
+                .extension("flow-meta.xml")
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("reassemble");
 
     let reassembled_path = base.join("Get_Info.flow-meta.xml");
@@ -648,8 +631,8 @@ async fn deeply_nested_unique_id_elements_round_trip() {
 
 /// Multi-level disassembly: first disassemble by processName etc., then further disassemble
 /// programProcesses by parameterName and ruleName. Reassemble and compare to original.
-#[tokio::test]
-async fn multi_level_disassemble_then_reassemble_matches_original() {
+#[test]
+fn multi_level_disassemble_then_reassemble_matches_original() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/multi-level/Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml";
@@ -681,16 +664,12 @@ async fn multi_level_disassemble_then_reassemble_matches_original() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            Some("fullName,name,processName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("fullName,name,processName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -698,10 +677,15 @@ async fn multi_level_disassemble_then_reassemble_matches_original() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
 
     let reassembled_path = base.join("Cloud_Kicks_Inner_Circle.xml");
@@ -715,53 +699,10 @@ async fn multi_level_disassemble_then_reassemble_matches_original() {
     );
 }
 
-/// Grouped-by-tag with a decompose rule that has mode neither "split" nor "group" (fallback path).
-#[tokio::test]
-async fn grouped_by_tag_with_fallback_mode_writes_single_file() {
-    let _ = env_logger::try_init();
-    let fixture = "fixtures/split-tags/HR_Admin.permissionset-meta.xml";
-    assert!(
-        Path::new(fixture).exists(),
-        "Fixture must exist (run from project root)"
-    );
-    let temp_dir = tempfile::tempdir().expect("temp dir");
-    let base = temp_dir.path();
-    let disassembled_dir = base.join("HR_Admin");
-    let source = base.join("HR_Admin.permissionset-meta.xml");
-    std::fs::copy(fixture, &source).expect("copy fixture");
-    let fallback_rule = DecomposeRule {
-        tag: "objectPermissions".to_string(),
-        path_segment: "objectPermissions".to_string(),
-        mode: "fallback".to_string(),
-        field: "object".to_string(),
-    };
-    let mut disassemble = DisassembleXmlFileHandler::new();
-    disassemble
-        .disassemble(
-            source.to_str().unwrap(),
-            None,
-            Some("grouped-by-tag"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            Some(&[fallback_rule]),
-        )
-        .await
-        .expect("disassemble");
-    assert!(disassembled_dir.exists());
-    let fallback_file = disassembled_dir.join("objectPermissions.xml");
-    assert!(
-        fallback_file.exists(),
-        "fallback mode writes single file to disassembled root"
-    );
-}
-
 /// Grouped-by-tag with --split-tags: objectPermissions split by object, fieldPermissions grouped by object (from field).
 /// Reassemble and compare to original fixture.
-#[tokio::test]
-async fn split_tags_disassemble_then_reassemble_matches_original() {
+#[test]
+fn split_tags_disassemble_then_reassemble_matches_original() {
     let _ = env_logger::try_init();
 
     let fixture = "fixtures/split-tags/HR_Admin.permissionset-meta.xml";
@@ -780,18 +721,20 @@ async fn split_tags_disassemble_then_reassemble_matches_original() {
     let source_in_temp = base.join("HR_Admin.permissionset-meta.xml");
     std::fs::copy(fixture, &source_in_temp).expect("copy fixture to temp");
 
-    let split_tags_rules = vec![
+    let split_tags_rules = [
         DecomposeRule {
             tag: "objectPermissions".to_string(),
             path_segment: "objectPermissions".to_string(),
-            mode: "split".to_string(),
+            mode: DecomposeMode::Split,
             field: "object".to_string(),
+            ..Default::default()
         },
         DecomposeRule {
             tag: "fieldPermissions".to_string(),
             path_segment: "fieldPermissions".to_string(),
-            mode: "group".to_string(),
+            mode: DecomposeMode::Group,
             field: "field".to_string(),
+            ..Default::default()
         },
     ];
 
@@ -799,16 +742,12 @@ async fn split_tags_disassemble_then_reassemble_matches_original() {
     disassemble
         .disassemble(
             source_in_temp.to_str().unwrap(),
-            None,
-            Some("grouped-by-tag"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            Some(&split_tags_rules),
+            &DisassembleOptions::builder()
+                .strategy(Strategy::GroupedByTag)
+                .decompose_rules(split_tags_rules.to_vec())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 
     assert!(
@@ -816,10 +755,15 @@ async fn split_tags_disassemble_then_reassemble_matches_original() {
         "Disassembled directory should exist"
     );
 
-    let reassemble_handler = ReassembleXmlFileHandler::new();
+    let mut reassemble_handler = ReassembleXmlFileHandler::new();
     reassemble_handler
-        .reassemble(disassembled_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            disassembled_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
 
     let reassembled_path = base.join("HR_Admin.xml");
@@ -833,38 +777,33 @@ async fn split_tags_disassemble_then_reassemble_matches_original() {
     );
 }
 
-#[tokio::test]
-async fn disassemble_nonexistent_path_returns_err() {
+#[test]
+fn disassemble_nonexistent_path_returns_err() {
     let _ = env_logger::try_init();
     let mut disassemble = DisassembleXmlFileHandler::new();
-    let result = disassemble
-        .disassemble(
-            "/nonexistent/path/xyz.xml",
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
-        )
-        .await;
+    let result = disassemble.disassemble(
+        "/nonexistent/path/xyz.xml",
+        &DisassembleOptions::builder().build().unwrap(),
+    );
     assert!(result.is_err(), "missing path should surface an error");
 }
 
-#[tokio::test]
-async fn reassemble_nonexistent_path_returns_err() {
+#[test]
+fn reassemble_nonexistent_path_returns_err() {
     let _ = env_logger::try_init();
-    let handler = ReassembleXmlFileHandler::new();
-    let result = handler
-        .reassemble("/nonexistent/dir/xyz", Some("xml"), false)
-        .await;
+    let mut handler = ReassembleXmlFileHandler::new();
+    let result = handler.reassemble(
+        "/nonexistent/dir/xyz",
+        &ReassembleOptions::builder()
+            .extension("xml")
+            .build()
+            .unwrap(),
+    );
     assert!(result.is_err(), "missing directory should surface an error");
 }
 
-#[tokio::test]
-async fn disassemble_leaf_only_xml_logs_and_skips() {
+#[test]
+fn disassemble_leaf_only_xml_logs_and_skips() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path();
@@ -878,26 +817,24 @@ async fn disassemble_leaf_only_xml_logs_and_skips() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
     // No disassembly directory created because only leaf elements are present.
     assert!(
         !base.join("LeafOnly").is_dir()
             || base.join("LeafOnly").read_dir().unwrap().next().is_none()
     );
+    let report = disassemble.take_report();
+    assert!(report.processed.is_empty());
+    assert!(matches!(
+        report.skipped.as_slice(),
+        [(_, xml_disassembler::Error::LeafOnly { .. })]
+    ));
 }
 
-#[tokio::test]
-async fn disassemble_duplicate_leaf_siblings_under_root_no_op_with_log() {
+#[test]
+fn disassemble_duplicate_leaf_siblings_under_root_no_op_with_log() {
     // Exercises the "existing key" branch in disassemble_element_keys when two leaf
     // siblings share the same element name at the root. Ends up short-circuiting
     // with a log message because `has_nested_elements` stays false.
@@ -914,21 +851,13 @@ async fn disassemble_duplicate_leaf_siblings_under_root_no_op_with_log() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
 }
 
-#[tokio::test]
-async fn disassemble_unparseable_xml_is_no_op() {
+#[test]
+fn disassemble_unparseable_xml_is_no_op() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path();
@@ -938,22 +867,18 @@ async fn disassemble_unparseable_xml_is_no_op() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(!base.join("broken").exists());
+    assert!(matches!(
+        disassemble.take_report().skipped.as_slice(),
+        [(_, xml_disassembler::Error::InvalidXml { .. })]
+    ));
 }
 
-#[tokio::test]
-async fn disassemble_empty_xml_document_is_no_op() {
+#[test]
+fn disassemble_empty_xml_document_is_no_op() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path();
@@ -963,21 +888,13 @@ async fn disassemble_empty_xml_document_is_no_op() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder().build().unwrap(),
         )
-        .await
         .expect("disassemble");
 }
 
-#[tokio::test]
-async fn disassemble_with_post_purge_removes_source_file() {
+#[test]
+fn disassemble_with_post_purge_removes_source_file() {
     let _ = env_logger::try_init();
     let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -988,23 +905,18 @@ async fn disassemble_with_post_purge_removes_source_file() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            true, // post_purge
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder()
+                .post_purge(true)
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(base.join("HR_Admin").exists());
     assert!(!source.exists(), "post_purge should remove source");
 }
 
-#[tokio::test]
-async fn grouped_by_tag_split_rule_uses_index_when_field_missing() {
+#[test]
+fn grouped_by_tag_split_rule_uses_index_when_field_missing() {
     // Split mode with an unknown field name: filename falls back to the array index.
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -1023,29 +935,26 @@ async fn grouped_by_tag_split_rule_uses_index_when_field_missing() {
     let rules = [DecomposeRule {
         tag: "objectPermissions".to_string(),
         path_segment: String::new(), // empty path segment → falls back to tag
-        mode: "split".to_string(),
+        mode: DecomposeMode::Split,
         field: "object".to_string(),
+        ..Default::default()
     }];
     let mut disassemble = DisassembleXmlFileHandler::new();
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("grouped-by-tag"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            Some(&rules),
+            &DisassembleOptions::builder()
+                .strategy(Strategy::GroupedByTag)
+                .decompose_rules(rules.to_vec())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(base.join("Perms").join("objectPermissions").exists());
 }
 
-#[tokio::test]
-async fn grouped_by_tag_group_rule_uses_nested_text_value() {
+#[test]
+fn grouped_by_tag_group_rule_uses_nested_text_value() {
     // Group mode where the "field" value is a nested leaf element (#text) - exercises
     // the object + #text branch of get_field_value and the dot-prefix grouping.
     let _ = env_logger::try_init();
@@ -1067,23 +976,20 @@ async fn grouped_by_tag_group_rule_uses_nested_text_value() {
     let rules = [DecomposeRule {
         tag: "fieldPermissions".to_string(),
         path_segment: "fieldPermissions".to_string(),
-        mode: "group".to_string(),
+        mode: DecomposeMode::Group,
         field: "field".to_string(),
+        ..Default::default()
     }];
     let mut disassemble = DisassembleXmlFileHandler::new();
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("grouped-by-tag"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            None,
-            Some(&rules),
+            &DisassembleOptions::builder()
+                .strategy(Strategy::GroupedByTag)
+                .decompose_rules(rules.to_vec())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     let grouped_dir = base.join("Fields").join("fieldPermissions");
     assert!(grouped_dir.exists());
@@ -1103,8 +1009,127 @@ async fn grouped_by_tag_group_rule_uses_nested_text_value() {
     );
 }
 
-#[tokio::test]
-async fn multi_level_with_empty_path_segment_and_xmlns_derives_segment() {
+#[test]
+fn multi_level_changes_include_the_second_level() {
+    use xml_disassembler::ChangeKind::{Create, Unchanged};
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/multi-level/Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let source = temp_dir
+        .path()
+        .join("Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let options = DisassembleOptions::builder()
+        .unique_id_elements("fullName,name,processName")
+        .multi_level(MultiLevelRule {
+            file_pattern: "programProcesses".to_string(),
+            root_to_strip: "programProcesses".to_string(),
+            unique_id_elements: "parameterName,ruleName".to_string(),
+            path_segment: "programProcesses".to_string(),
+            wrap_root_element: "LoyaltyProgramSetup".to_string(),
+            wrap_xmlns: String::new(),
+        })
+        .build()
+        .unwrap();
+    let run = || {
+        let mut handler = DisassembleXmlFileHandler::new();
+        handler
+            .disassemble(source.to_str().unwrap(), &options)
+            .expect("disassemble");
+        handler.take_planned_changes()
+    };
+    // Kinds of the changes to paths ending in `suffix`.
+    let kinds = |changes: &[xml_disassembler::PlannedChange], suffix: &str| {
+        changes
+            .iter()
+            .filter(|c| c.path.to_string_lossy().ends_with(suffix))
+            .map(|c| c.kind)
+            .collect::<Vec<_>>()
+    };
+
+    let first = run();
+    let parts = kinds(&first, ".parameters-meta.xml");
+    assert!(!parts.is_empty());
+    assert!(parts.iter().all(|kind| *kind == Create));
+    assert_eq!(kinds(&first, ".multi_level.json"), [Create]);
+    let second = run();
+    assert_eq!(
+        kinds(&second, ".parameters-meta.xml"),
+        vec![Unchanged; parts.len()]
+    );
+    assert_eq!(kinds(&second, ".multi_level.json"), [Unchanged]);
+}
+
+#[test]
+fn multi_level_dry_runs_plan_both_levels() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/multi-level/Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let disassemble = |dry_run: bool| {
+        let mut handler = DisassembleXmlFileHandler::new();
+        handler
+            .disassemble(
+                source.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .unique_id_elements("fullName,name,processName")
+                    .multi_level(MultiLevelRule {
+                        file_pattern: "programProcesses".to_string(),
+                        root_to_strip: "programProcesses".to_string(),
+                        unique_id_elements: "parameterName,ruleName".to_string(),
+                        path_segment: "programProcesses".to_string(),
+                        wrap_root_element: "LoyaltyProgramSetup".to_string(),
+                        wrap_xmlns: String::new(),
+                    })
+                    .dry_run(dry_run)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+        handler.take_planned_changes()
+    };
+
+    let before = snapshot_tree(base);
+    let planned = disassemble(true);
+    assert_eq!(snapshot_tree(base), before, "dry run wrote nothing");
+    assert!(planned
+        .iter()
+        .any(|c| c.path.to_string_lossy().ends_with(".parameters-meta.xml")));
+    assert_eq!(planned, disassemble(false));
+
+    std::fs::remove_file(&source).unwrap();
+    let output = base.join("Cloud_Kicks_Inner_Circle");
+    let reassemble = |dry_run: bool| {
+        let mut handler = ReassembleXmlFileHandler::new();
+        handler
+            .reassemble(
+                output.to_str().unwrap(),
+                &ReassembleOptions::builder()
+                    .extension("loyaltyProgramSetup-meta.xml")
+                    .dry_run(dry_run)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("reassemble");
+        handler.take_planned_changes()
+    };
+    let before = snapshot_tree(base);
+    let planned = reassemble(true);
+    assert_eq!(snapshot_tree(base), before, "dry run wrote nothing");
+    assert!(planned
+        .iter()
+        .any(|c| c.path == source && c.kind == xml_disassembler::ChangeKind::Create));
+    assert_eq!(planned, reassemble(false));
+    assert_eq!(
+        std::fs::read_to_string(&source).unwrap(),
+        std::fs::read_to_string(fixture).unwrap()
+    );
+}
+
+#[test]
+fn multi_level_with_empty_path_segment_and_xmlns_derives_segment() {
     // Exercises the MultiLevelRule empty path_segment / empty wrap_xmlns branches that fall
     // back to deriving values from file_pattern and the captured root xmlns.
     let _ = env_logger::try_init();
@@ -1131,23 +1156,19 @@ async fn multi_level_with_empty_path_segment_and_xmlns_derives_segment() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     let disassembled_dir = base.join("Sample");
     assert!(disassembled_dir.exists());
 }
 
-#[tokio::test]
-async fn multi_level_with_explicit_xmlns_preserved() {
+#[test]
+fn multi_level_with_explicit_xmlns_preserved() {
     // Exercises the non-empty wrap_xmlns branch: the rule-provided xmlns is used directly.
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -1173,22 +1194,18 @@ async fn multi_level_with_explicit_xmlns_preserved() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(base.join("Inner").exists());
 }
 
-#[tokio::test]
-async fn multi_level_with_multiple_matching_files_appends_rule_once() {
+#[test]
+fn multi_level_with_multiple_matching_files_appends_rule_once() {
     // Multiple programProcesses siblings produce multiple matching output files in a single
     // disassembled tree; first hit pushes the rule to the multi-level config, subsequent hits
     // exercise the `Some(r) if r.wrap_xmlns.is_empty()` update branch.
@@ -1222,23 +1239,19 @@ async fn multi_level_with_multiple_matching_files_appends_rule_once() {
     disassemble
         .disassemble(
             base.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(base.join("A").exists());
     assert!(base.join("B").exists());
 }
 
-#[tokio::test]
-async fn disassemble_single_file_ignored_via_ignore_rules() {
+#[test]
+fn disassemble_single_file_ignored_via_ignore_rules() {
     // Single-file path is matched by the ignore file - hits handle_file's is_ignored branch.
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -1255,23 +1268,18 @@ async fn disassemble_single_file_ignored_via_ignore_rules() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ignore_path.to_str().unwrap(),
-            "xml",
-            None,
-            None,
+            &DisassembleOptions::builder()
+                .ignore_path(ignore_path.to_str().unwrap())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     // File should be ignored: no disassembled output directory.
     assert!(!base.join("skipme").exists());
 }
 
-#[tokio::test]
-async fn multi_level_rule_without_matching_file_is_noop() {
+#[test]
+fn multi_level_rule_without_matching_file_is_noop() {
     // Multi-level rule set but no disassembled file matches the pattern.
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
@@ -1294,22 +1302,17 @@ async fn multi_level_rule_without_matching_file_is_noop() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            None,
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
     assert!(base.join("sample").exists());
 }
 
-#[tokio::test]
-async fn reassemble_with_non_parseable_junk_files_is_skipped() {
+#[test]
+fn reassemble_with_non_parseable_junk_files_is_skipped() {
     // Reassembly walks a directory whose only parseable file is invalid XML -
     // triggers parse_to_xml_object's None path and the "no files parsed" log branch.
     let _ = env_logger::try_init();
@@ -1319,16 +1322,86 @@ async fn reassemble_with_non_parseable_junk_files_is_skipped() {
     std::fs::write(base.join("bogus.xml"), "<<not xml").expect("write");
     std::fs::write(base.join(".hidden.xml"), "<hidden/>").expect("write hidden");
     std::fs::write(base.join("ignored.txt"), "data").expect("write text");
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(base.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            base.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble returns Ok even when nothing parses");
     assert!(!base.with_extension("xml").exists());
+    assert!(matches!(
+        handler.take_report().skipped.as_slice(),
+        [(_, xml_disassembler::Error::NoParts { .. })]
+    ));
 }
 
-#[tokio::test]
-async fn reassemble_directory_with_nested_subdir_is_recursed() {
+#[test]
+fn strict_mode_fails_on_files_that_would_be_skipped() {
+    use xml_disassembler::Error;
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    std::fs::copy(
+        "fixtures/general/HR_Admin.permissionset-meta.xml",
+        base.join("HR_Admin.permissionset-meta.xml"),
+    )
+    .expect("copy fixture");
+    std::fs::write(base.join("LeafOnly.xml"), "<Root><a>1</a><b>2</b></Root>").expect("write");
+    std::fs::write(base.join("notes.txt"), "<Root/>").expect("write");
+    let root = base.to_str().unwrap();
+
+    // Lenient: the leaf-only file is skipped, the rest is disassembled.
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(root, &DisassembleOptions::default())
+        .expect("disassemble");
+    let report = disassemble.take_report();
+    assert_eq!(report.processed.len(), 1);
+    assert!(report.processed[0].ends_with("HR_Admin.permissionset-meta.xml"));
+    assert_eq!(report.skipped.len(), 1);
+    assert!(
+        matches!(&report.skipped[0].1, Error::LeafOnly { path } if path.ends_with("LeafOnly.xml"))
+    );
+    assert!(report.failed.is_empty());
+
+    // Strict: the same file fails the run, which keeps nothing.
+    std::fs::remove_dir_all(base.join("HR_Admin")).expect("remove output");
+    let options = DisassembleOptions::builder().strict(true).build().unwrap();
+    let err = disassemble.disassemble(root, &options).unwrap_err();
+    assert!(matches!(err, Error::LeafOnly { .. }));
+    assert!(!base.join("HR_Admin").exists());
+    // The file that had disassembled was rolled back with the rest, so it is not processed.
+    let report = disassemble.take_report();
+    assert!(report.processed.is_empty());
+    assert!(report.skipped.is_empty());
+    assert_eq!(report.failed.len(), 2);
+    assert!(report.failed[0]
+        .0
+        .ends_with("HR_Admin.permissionset-meta.xml"));
+    assert!(report.failed[0].1.contains("rolled back"));
+    assert!(report.failed[1].0.ends_with("LeafOnly.xml"));
+
+    // A single path that is not XML.
+    let err = disassemble
+        .disassemble(base.join("notes.txt").to_str().unwrap(), &options)
+        .unwrap_err();
+    assert!(matches!(err, Error::NotXml { .. }));
+
+    // Reassembly: a file is not a directory.
+    let mut reassemble = ReassembleXmlFileHandler::new();
+    let strict = ReassembleOptions::builder().strict(true).build().unwrap();
+    let err = reassemble
+        .reassemble(base.join("LeafOnly.xml").to_str().unwrap(), &strict)
+        .unwrap_err();
+    assert!(matches!(err, Error::NotADirectory { .. }));
+}
+
+#[test]
+fn reassemble_directory_with_nested_subdir_is_recursed() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path().join("Out");
@@ -1343,17 +1416,22 @@ async fn reassemble_directory_with_nested_subdir_is_recursed() {
         r#"<?xml version="1.0"?><Root><b>2</b></Root>"#,
     )
     .expect("write inner");
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(base.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            base.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
     let parent = base.parent().unwrap();
     assert!(parent.join("Out.xml").exists());
 }
 
-#[tokio::test]
-async fn reassemble_invalid_key_order_json_still_writes() {
+#[test]
+fn reassemble_invalid_key_order_json_still_writes() {
     let _ = env_logger::try_init();
     let temp_dir = tempfile::tempdir().expect("temp dir");
     let base = temp_dir.path().join("Out");
@@ -1364,10 +1442,15 @@ async fn reassemble_invalid_key_order_json_still_writes() {
     )
     .expect("write");
     std::fs::write(base.join(".key_order.json"), "not valid json").expect("write key order");
-    let handler = ReassembleXmlFileHandler::new();
+    let mut handler = ReassembleXmlFileHandler::new();
     handler
-        .reassemble(base.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            base.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
     assert!(base.with_extension("xml").exists());
 }
@@ -1375,8 +1458,8 @@ async fn reassemble_invalid_key_order_json_still_writes() {
 /// Full round-trip (disassemble → reassemble) for each success fixture.
 /// Excludes: no-root-element (invalid root), no-nested-elements (only leaves), ignore (behavior);
 /// attributes/notes.xml (reassembly differs re declaration/entities), array-of-leaves (sibling order not preserved).
-#[tokio::test]
-async fn fixture_round_trip_matches_original() {
+#[test]
+fn fixture_round_trip_matches_original() {
     let _ = env_logger::try_init();
 
     /// (fixture path, optional unique_id_elements for disassemble, extension for reassemble output)
@@ -1429,20 +1512,13 @@ async fn fixture_round_trip_matches_original() {
         let source_in_temp = base.join(file_name);
         std::fs::copy(fixture, &source_in_temp).expect("copy fixture to temp");
 
+        let mut options = DisassembleOptions::builder();
+        if let Some(ids) = unique_id_elements {
+            options = options.unique_id_elements(*ids);
+        }
         let mut disassemble = DisassembleXmlFileHandler::new();
-        let result = disassemble
-            .disassemble(
-                source_in_temp.to_str().unwrap(),
-                *unique_id_elements,
-                Some("unique-id"),
-                false,
-                false,
-                ".xmldisassemblerignore",
-                "xml",
-                None,
-                None,
-            )
-            .await;
+        let result =
+            disassemble.disassemble(source_in_temp.to_str().unwrap(), &options.build().unwrap());
 
         let Ok(()) = result else {
             panic!(
@@ -1458,14 +1534,15 @@ async fn fixture_round_trip_matches_original() {
             fixture
         );
 
-        let reassemble_handler = ReassembleXmlFileHandler::new();
+        let mut reassemble_handler = ReassembleXmlFileHandler::new();
         reassemble_handler
             .reassemble(
                 disassembled_dir.to_str().unwrap(),
-                Some(reassemble_ext),
-                false,
+                &ReassembleOptions::builder()
+                    .extension(*reassemble_ext)
+                    .build()
+                    .unwrap(),
             )
-            .await
             .expect("reassemble");
 
         let reassembled_path = base.join(format!("{}.{}", base_name, reassemble_ext));
@@ -1487,8 +1564,8 @@ async fn fixture_round_trip_matches_original() {
     }
 }
 
-#[tokio::test]
-async fn multi_level_skips_unparseable_matching_file() {
+#[test]
+fn multi_level_skips_unparseable_matching_file() {
     // Plant an unparseable XML file matching the multi-level file_pattern inside the output
     // directory before disassemble runs; the recursive walk should encounter it and skip on
     // parse failure.
@@ -1521,21 +1598,17 @@ async fn multi_level_skips_unparseable_matching_file() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 }
 
-#[tokio::test]
-async fn multi_level_skips_matching_file_without_root_to_strip() {
+#[test]
+fn multi_level_skips_matching_file_without_root_to_strip() {
     // Plant an XML file matching file_pattern whose root does not contain root_to_strip;
     // recursive walk skips it via the has_element_to_strip branch.
     let _ = env_logger::try_init();
@@ -1569,21 +1642,17 @@ async fn multi_level_skips_matching_file_without_root_to_strip() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 }
 
-#[tokio::test]
-async fn multi_level_skips_matching_file_with_non_object_strip_target() {
+#[test]
+fn multi_level_skips_matching_file_with_non_object_strip_target() {
     // Plant an XML whose root contains the element_to_strip key but with a non-object value;
     // strip_root_and_build_xml returns None and the file is skipped.
     let _ = env_logger::try_init();
@@ -1619,21 +1688,17 @@ async fn multi_level_skips_matching_file_with_non_object_strip_target() {
     disassemble
         .disassemble(
             source.to_str().unwrap(),
-            Some("name,ruleName"),
-            Some("unique-id"),
-            false,
-            false,
-            ".xmldisassemblerignore",
-            "xml",
-            Some(&rule),
-            None,
+            &DisassembleOptions::builder()
+                .unique_id_elements("name,ruleName")
+                .multi_level(rule.clone())
+                .build()
+                .unwrap(),
         )
-        .await
         .expect("disassemble");
 }
 
-#[tokio::test]
-async fn reassemble_multi_level_skips_unparseable_segment_file() {
+#[test]
+fn reassemble_multi_level_skips_unparseable_segment_file() {
     // Write a saved multi-level config next to a segment directory that contains an
     // unparseable XML file; ensure_segment_files_structure must skip it gracefully.
     let _ = env_logger::try_init();
@@ -1664,9 +1729,1784 @@ async fn reassemble_multi_level_skips_unparseable_segment_file() {
         serde_json::to_string_pretty(&config).unwrap(),
     )
     .expect("write config");
-    let reassemble = ReassembleXmlFileHandler::new();
+    let mut reassemble = ReassembleXmlFileHandler::new();
     reassemble
-        .reassemble(out_dir.to_str().unwrap(), Some("xml"), false)
-        .await
+        .reassemble(
+            out_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+}
+
+/// Grouped-by-tag split and group rules keyed on attributes (`@id`, `@xml:lang`) instead of child
+/// elements; reassembly must reproduce the original document.
+#[test]
+fn split_tags_by_attribute_round_trip() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("Strings.xml");
+    let original = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Strings xmlns="urn:example:strings">
+    <item id="farewell">
+        <value>Bye</value>
+    </item>
+    <item id="greeting">
+        <value>Hello</value>
+    </item>
+    <label xml:lang="en">
+        <key>title</key>
+        <text>Title</text>
+    </label>
+    <label xml:lang="en">
+        <key>body</key>
+        <text>Body</text>
+    </label>
+    <label xml:lang="fr">
+        <key>title</key>
+        <text>Titre</text>
+    </label>
+    <version>1</version>
+</Strings>"#;
+    std::fs::write(&source, original).expect("write");
+    let rules = [
+        DecomposeRule {
+            tag: "item".to_string(),
+            path_segment: "item".to_string(),
+            mode: DecomposeMode::Split,
+            field: "@id".to_string(),
+            ..Default::default()
+        },
+        DecomposeRule {
+            tag: "label".to_string(),
+            path_segment: "label".to_string(),
+            mode: DecomposeMode::Group,
+            field: "@xml:lang".to_string(),
+            ..Default::default()
+        },
+    ];
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .strategy(Strategy::GroupedByTag)
+                .decompose_rules(rules.to_vec())
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    let out = base.join("Strings");
+    assert!(out.join("item").join("greeting.item-meta.xml").exists());
+    assert!(out.join("item").join("farewell.item-meta.xml").exists());
+    assert!(out.join("label").join("en.label-meta.xml").exists());
+    assert!(out.join("label").join("fr.label-meta.xml").exists());
+
+    std::fs::remove_file(&source).expect("remove source");
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            out.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("xml")
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+    let reassembled = std::fs::read_to_string(&source).expect("read reassembled");
+    assert_eq!(original, reassembled);
+}
+
+/// Child decompose rules split the contents of each split item into subdirectories; reassembly
+/// merges the nested levels back into the item in its original key order.
+#[test]
+fn nested_decompose_rules_round_trip() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("Admin.profile-meta.xml");
+    let original = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Profile xmlns="http://soap.sforce.com/2006/04/metadata">
+    <custom>false</custom>
+    <profileSection>
+        <name>Account</name>
+        <recordTypeVisibilities>
+            <default>true</default>
+            <recordType>Account.Business</recordType>
+            <visible>true</visible>
+        </recordTypeVisibilities>
+        <recordTypeVisibilities>
+            <default>false</default>
+            <recordType>Account.Person</recordType>
+            <visible>true</visible>
+        </recordTypeVisibilities>
+        <layouts>
+            <layout>Account-Layout</layout>
+            <item id="a1">
+                <field>Name</field>
+            </item>
+            <item id="a2">
+                <field>Phone</field>
+            </item>
+        </layouts>
+        <readable>true</readable>
+    </profileSection>
+    <profileSection>
+        <name>Contact</name>
+        <readable>false</readable>
+    </profileSection>
+    <userLicense>Salesforce</userLicense>
+</Profile>"#;
+    std::fs::write(&source, original).expect("write");
+    let rules = [
+        DecomposeRule::new("profileSection", DecomposeMode::Split, "name")
+            .with_path_segment("sections")
+            .with_children(vec![
+                DecomposeRule::new("recordTypeVisibilities", DecomposeMode::Group, "recordType"),
+                DecomposeRule::new("layouts", DecomposeMode::Split, "layout").with_children(vec![
+                    DecomposeRule::new("item", DecomposeMode::Split, "@id")
+                        .with_path_segment("items"),
+                ]),
+            ]),
+    ];
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .strategy(Strategy::GroupedByTag)
+                .post_purge(true)
+                .decompose_rules(rules.to_vec())
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    let out = base.join("Admin");
+    let account = out.join("sections").join("Account");
+    assert!(account.join("Account.profileSection-meta.xml").exists());
+    assert!(account.join(".nested_item.json").exists());
+    assert!(account
+        .join("recordTypeVisibilities")
+        .join("Account.recordTypeVisibilities-meta.xml")
+        .exists());
+    let layout = account.join("layouts").join("Account-Layout");
+    assert!(layout.join("Account-Layout.layouts-meta.xml").exists());
+    assert!(layout.join("items").join("a1.item-meta.xml").exists());
+    assert!(layout.join("items").join("a2.item-meta.xml").exists());
+    // Items without any child tag stay plain part files.
+    assert!(out
+        .join("sections")
+        .join("Contact.profileSection-meta.xml")
+        .exists());
+
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            out.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("profile-meta.xml")
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+    let reassembled = std::fs::read_to_string(&source).expect("read reassembled");
+    assert_eq!(original, reassembled);
+}
+
+/// Leaf tags selected by name or count threshold are written to their own files, which lets a
+/// leaf-only document be disassembled; reassembly restores the original order.
+#[test]
+fn split_leaves_round_trip_for_leaf_only_document() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("Dreamhouse.app-meta.xml");
+    let original = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CustomApplication xmlns="http://soap.sforce.com/2006/04/metadata">
+    <brand>Dreamhouse</brand>
+    <formFactors>Small</formFactors>
+    <formFactors>Large</formFactors>
+    <label>Dreamhouse</label>
+    <tabs>standard-home</tabs>
+    <tabs>Property__c</tabs>
+    <tabs>Broker__c</tabs>
+    <uiType>Lightning</uiType>
+</CustomApplication>"#;
+    std::fs::write(&source, original).expect("write");
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .post_purge(true)
+                .leaf_split(LeafSplitRule {
+                    tags: vec!["formFactors".to_string()],
+                    threshold: Some(2),
+                })
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    let out = base.join("Dreamhouse");
+    assert!(out.join("tabs.xml").exists());
+    assert!(out.join("formFactors.xml").exists());
+    let base_file = std::fs::read_to_string(out.join("Dreamhouse.app-meta.xml")).expect("base");
+    assert!(base_file.contains("<brand>") && !base_file.contains("<tabs>"));
+
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            out.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("app-meta.xml")
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+    let reassembled = std::fs::read_to_string(&source).expect("read reassembled");
+    assert_eq!(original, reassembled);
+}
+
+/// Bare part files hold only their elements; the root wrapper stored in .root.json is put back
+/// on reassembly, for both strategies and non-XML formats.
+#[test]
+fn bare_parts_round_trip() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let original = std::fs::read_to_string(fixture).expect("read fixture");
+    let rules = [DecomposeRule {
+        tag: "objectPermissions".to_string(),
+        path_segment: "objectPermissions".to_string(),
+        mode: DecomposeMode::Split,
+        field: "object".to_string(),
+        ..Default::default()
+    }];
+
+    for (strategy, format) in [
+        ("unique-id", "xml"),
+        ("grouped-by-tag", "xml"),
+        ("grouped-by-tag", "json"),
+    ] {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let base = temp_dir.path();
+        let source = base.join("HR_Admin.permissionset-meta.xml");
+        std::fs::copy(fixture, &source).expect("copy fixture");
+        let strategy: Strategy = strategy.parse().unwrap();
+        let mut options = DisassembleOptions::builder()
+            .strategy(strategy)
+            .post_purge(true)
+            .bare_parts(true)
+            .format(format.parse().unwrap());
+        if strategy == Strategy::GroupedByTag {
+            options = options.decompose_rules(rules.to_vec());
+        }
+        let mut disassemble = DisassembleXmlFileHandler::new();
+        disassemble
+            .disassemble(source.to_str().unwrap(), &options.build().unwrap())
+            .expect("disassemble");
+        let out = base.join("HR_Admin");
+        assert!(out.join(".root.json").exists());
+        let base_part =
+            std::fs::read_to_string(out.join(format!("HR_Admin.permissionset-meta.{}", format)))
+                .expect("base part");
+        assert!(!base_part.contains("PermissionSet"), "{}", base_part);
+        assert!(!base_part.contains("<?xml"));
+
+        ReassembleXmlFileHandler::new()
+            .reassemble(
+                out.to_str().unwrap(),
+                &ReassembleOptions::builder()
+                    .extension("permissionset-meta.xml")
+                    .build()
+                    .unwrap(),
+            )
+            .expect("reassemble");
+        let reassembled = std::fs::read_to_string(&source).expect("read reassembled");
+        assert_eq!(original, reassembled, "{} / {}", strategy, format);
+    }
+}
+
+/// CDATA content extracted to sidecar files is inlined again on reassembly, for wrapped and bare parts.
+#[test]
+fn sidecar_cdata_round_trip() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/cdata/VidLand_US.marketingappextension-meta.xml";
+    let original = std::fs::read_to_string(fixture).expect("read fixture");
+    let rules = vec![
+        SidecarRule {
+            tag: "actionSchema".to_string(),
+            extension: "json".to_string(),
+            min_len: 0,
+        },
+        SidecarRule {
+            tag: "actionParams".to_string(),
+            extension: "json".to_string(),
+            min_len: 0,
+        },
+    ];
+    for bare in [false, true] {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let base = temp_dir.path();
+        let source = base.join("VidLand_US.marketingappextension-meta.xml");
+        std::fs::copy(fixture, &source).expect("copy fixture");
+        let mut disassemble = DisassembleXmlFileHandler::new();
+        disassemble
+            .disassemble(
+                source.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .post_purge(true)
+                    .bare_parts(bare)
+                    .sidecars(rules.clone())
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+        let part_dir = base.join("VidLand_US").join("marketingAppExtActions");
+        let sidecar = std::fs::read_dir(&part_dir)
+            .expect("part dir")
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .find(|n| n.ends_with(".actionSchema.sidecar.json"))
+            .expect("actionSchema sidecar");
+        let schema = std::fs::read_to_string(part_dir.join(sidecar)).expect("read sidecar");
+        assert!(schema.contains("\"WebinarId\": {"));
+
+        ReassembleXmlFileHandler::new()
+            .reassemble(
+                base.join("VidLand_US").to_str().unwrap(),
+                &ReassembleOptions::builder()
+                    .extension("marketingappextension-meta.xml")
+                    .build()
+                    .unwrap(),
+            )
+            .expect("reassemble");
+        let reassembled = std::fs::read_to_string(&source).expect("read reassembled");
+        assert_eq!(original, reassembled, "bare = {}", bare);
+    }
+}
+
+#[test]
+fn output_dir_keeps_source_tree_untouched_and_reassembles_elsewhere() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let original = std::fs::read_to_string(fixture).expect("read fixture");
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source_root = base.join("src");
+    let nested = source_root.join("permissionsets");
+    std::fs::create_dir_all(&nested).expect("create source dir");
+    std::fs::copy(fixture, nested.join("HR_Admin.permissionset-meta.xml")).expect("copy fixture");
+    let out_root = base.join("out");
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            nested.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .output_dir(&out_root)
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+
+    let parts = out_root.join("HR_Admin");
+    assert!(parts.is_dir(), "disassembly written under output dir");
+    assert!(
+        !nested.join("HR_Admin").exists(),
+        "nothing written next to the source"
+    );
+
+    let target = base.join("dist").join("rebuilt.permissionset-meta.xml");
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            parts.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("permissionset-meta.xml")
+                .output(&target)
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble with output");
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), original);
+
+    let dist_dir = base.join("dist2");
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            parts.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("permissionset-meta.xml")
+                .output_dir(&dist_dir)
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble with output dir");
+    assert_eq!(
+        std::fs::read_to_string(dist_dir.join("HR_Admin.permissionset-meta.xml")).unwrap(),
+        original
+    );
+    assert!(!out_root.join("HR_Admin.permissionset-meta.xml").exists());
+}
+
+#[test]
+fn colliding_short_names_keep_type_suffix_and_round_trip() {
+    let _ = env_logger::try_init();
+    let permission_set = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let bot = "fixtures/no-root-element/Assessment_Bot.bot-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    std::fs::copy(permission_set, base.join("Admin.permissionset-meta.xml")).expect("copy");
+    std::fs::copy(bot, base.join("Admin.bot-meta.xml")).expect("copy");
+
+    for _ in 0..2 {
+        // The second run with pre-purge must not delete the other file's output.
+        let mut disassemble = DisassembleXmlFileHandler::new();
+        disassemble
+            .disassemble(
+                base.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .pre_purge(true)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+    }
+    assert!(!base.join("Admin").exists());
+    assert!(base
+        .join("Admin.permissionset")
+        .join(".source.json")
+        .exists());
+    assert!(base.join("Admin.bot").join(".source.json").exists());
+
+    for (dir, ext, fixture) in [
+        (
+            "Admin.permissionset",
+            "permissionset-meta.xml",
+            permission_set,
+        ),
+        ("Admin.bot", "bot-meta.xml", bot),
+    ] {
+        let rebuilt = base.join(format!("Admin.{}", ext));
+        std::fs::remove_file(&rebuilt).expect("remove source");
+        ReassembleXmlFileHandler::new()
+            .reassemble(
+                base.join(dir).to_str().unwrap(),
+                &ReassembleOptions::builder().extension(ext).build().unwrap(),
+            )
+            .expect("reassemble");
+        assert_eq!(
+            std::fs::read_to_string(&rebuilt).expect("rebuilt file"),
+            std::fs::read_to_string(fixture).unwrap(),
+            "{}",
+            dir
+        );
+    }
+}
+
+#[test]
+fn directory_disassembly_walks_tree_with_filters() {
+    let _ = env_logger::try_init();
+    let permission_set = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let notes = "fixtures/attributes/notes.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let root = temp_dir.path().join("project");
+    for sub in ["a/deep", "legacy", "ignored", ".hidden"] {
+        std::fs::create_dir_all(root.join(sub)).expect("create dir");
+    }
+    std::fs::copy(
+        permission_set,
+        root.join("a/deep/HR_Admin.permissionset-meta.xml"),
+    )
+    .expect("copy");
+    std::fs::copy(notes, root.join("a/notes.xsd")).expect("copy");
+    std::fs::copy(notes, root.join("legacy/notes.xml")).expect("copy");
+    std::fs::copy(notes, root.join("ignored/notes.xml")).expect("copy");
+    std::fs::copy(notes, root.join(".hidden/notes.xml")).expect("copy");
+    std::fs::write(root.join(".gitignore"), "ignored/\n").expect("gitignore");
+    let out = temp_dir.path().join("out");
+
+    for _ in 0..2 {
+        // The second run must not descend into the directories written by the first.
+        let mut disassemble = DisassembleXmlFileHandler::new();
+        disassemble
+            .disassemble(
+                root.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .unique_id_elements("id")
+                    .pre_purge(true)
+                    .extensions(vec!["xml".to_string(), "xsd".to_string()])
+                    .exclude(vec!["legacy/**".to_string()])
+                    .output_dir(&out)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+    }
+
+    assert!(out.join("a/deep/HR_Admin/.key_order.json").exists());
+    assert!(out.join("a/notes/.key_order.json").exists());
+    assert!(!out.join("legacy").exists(), "excluded by glob");
+    assert!(!out.join("ignored").exists(), "excluded by .gitignore");
+    assert!(!out.join(".hidden").exists(), "hidden folders are skipped");
+
+    let rebuilt = temp_dir.path().join("HR_Admin.permissionset-meta.xml");
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            out.join("a/deep/HR_Admin").to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .extension("permissionset-meta.xml")
+                .output(&rebuilt)
+                .build()
+                .unwrap(),
+        )
         .expect("reassemble");
+    assert_eq!(
+        std::fs::read_to_string(rebuilt).unwrap(),
+        std::fs::read_to_string(permission_set).unwrap()
+    );
+
+    // Include globs select files regardless of the extension filter.
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            root.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .include(vec!["**/*.permissionset-meta.xml".to_string()])
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble with include");
+    assert!(root.join("a/deep/HR_Admin").is_dir());
+    assert!(!root.join("a/notes").exists());
+    assert!(!root.join("legacy/notes").exists());
+}
+
+#[test]
+fn recursive_reassembly_rebuilds_every_disassembled_directory() {
+    let _ = env_logger::try_init();
+    let permission_set = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let bot = "fixtures/no-root-element/Assessment_Bot.bot-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("a/b")).expect("create dir");
+    let sources = [
+        (
+            root.join("a/HR_Admin.permissionset-meta.xml"),
+            permission_set,
+        ),
+        (root.join("a/b/Assessment_Bot.bot-meta.xml"), bot),
+    ];
+    for (target, fixture) in &sources {
+        std::fs::copy(fixture, target).expect("copy");
+    }
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            root.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .post_purge(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    for (target, _) in &sources {
+        assert!(!target.exists(), "source purged");
+    }
+
+    // Without --recursive a parent of disassembled directories is refused.
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            root.join("a").to_str().unwrap(),
+            &ReassembleOptions::default(),
+        )
+        .expect("refused without error");
+    assert!(!root.join("a.xml").exists());
+
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            root.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .post_purge(true)
+                .recursive(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("recursive reassemble");
+    for (target, fixture) in &sources {
+        assert_eq!(
+            std::fs::read_to_string(target).expect("rebuilt at original location"),
+            std::fs::read_to_string(fixture).unwrap()
+        );
+    }
+    assert!(!root.join("a/HR_Admin").exists(), "post-purged");
+    assert!(!root.join("a/b/Assessment_Bot").exists(), "post-purged");
+}
+
+#[test]
+fn recursive_reassembly_restores_sources_disassembled_into_an_output_dir() {
+    let _ = env_logger::try_init();
+    let permission_set = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let bot = "fixtures/no-root-element/Assessment_Bot.bot-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("src/a/b")).expect("create dir");
+    std::fs::copy(
+        permission_set,
+        root.join("src/a/HR_Admin.permissionset-meta.xml"),
+    )
+    .expect("copy");
+    std::fs::copy(bot, root.join("src/a/b/Assessment_Bot.bot-meta.xml")).expect("copy");
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(
+            root.join("src").to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .post_purge(true)
+                .output_dir(root.join("out"))
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    assert!(root.join("out/a/HR_Admin").is_dir());
+    assert!(!root.join("src/a/HR_Admin.permissionset-meta.xml").exists());
+
+    // The location is relative, so both trees can move together.
+    let moved = root.join("moved");
+    std::fs::create_dir_all(&moved).expect("create dir");
+    std::fs::rename(root.join("src"), moved.join("src")).expect("move sources");
+    std::fs::rename(root.join("out"), moved.join("out")).expect("move output");
+
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            moved.join("out").to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .recursive(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("recursive reassemble");
+    for (target, fixture) in [
+        ("src/a/HR_Admin.permissionset-meta.xml", permission_set),
+        ("src/a/b/Assessment_Bot.bot-meta.xml", bot),
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(moved.join(target)).expect("rebuilt at source location"),
+            std::fs::read_to_string(fixture).unwrap()
+        );
+    }
+    assert!(!moved.join("out/a/HR_Admin.permissionset-meta.xml").exists());
+}
+
+fn snapshot_tree(dir: &Path) -> std::collections::BTreeMap<String, Vec<u8>> {
+    let mut files = std::collections::BTreeMap::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in std::fs::read_dir(&current).expect("read dir") {
+            let path = entry.expect("entry").path();
+            if path.is_dir() {
+                stack.push(path);
+            } else {
+                let relative = path
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string();
+                files.insert(relative, std::fs::read(&path).expect("read file"));
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn parallel_jobs_produce_identical_output() {
+    let _ = env_logger::try_init();
+    let fixtures = [
+        "fixtures/general/HR_Admin.permissionset-meta.xml",
+        "fixtures/cdata/VidLand_US.marketingappextension-meta.xml",
+        "fixtures/comments/Numbers-fr.globalValueSetTranslation-meta.xml",
+    ];
+    let mut snapshots = Vec::new();
+    for (jobs, strategy) in [
+        (1, "unique-id"),
+        (8, "unique-id"),
+        (1, "grouped-by-tag"),
+        (8, "grouped-by-tag"),
+    ] {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let base = temp_dir.path();
+        for fixture in fixtures {
+            let name = Path::new(fixture).file_name().unwrap();
+            std::fs::copy(fixture, base.join(name)).expect("copy");
+        }
+        let mut disassemble = DisassembleXmlFileHandler::new();
+        disassemble
+            .disassemble(
+                base.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .strategy(strategy.parse().unwrap())
+                    .post_purge(true)
+                    .jobs(jobs)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+        snapshots.push(snapshot_tree(base));
+
+        ReassembleXmlFileHandler::new()
+            .reassemble(
+                base.to_str().unwrap(),
+                &ReassembleOptions::builder()
+                    .post_purge(true)
+                    .recursive(true)
+                    .jobs(jobs)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("reassemble");
+        for fixture in fixtures {
+            let name = Path::new(fixture).file_name().unwrap();
+            assert_eq!(
+                std::fs::read_to_string(base.join(name)).expect("rebuilt"),
+                std::fs::read_to_string(fixture).unwrap(),
+                "{} with {} jobs ({})",
+                fixture,
+                jobs,
+                strategy
+            );
+        }
+    }
+    assert_eq!(
+        snapshots[0], snapshots[1],
+        "unique-id output depends on jobs"
+    );
+    assert_eq!(
+        snapshots[2], snapshots[3],
+        "grouped-by-tag output depends on jobs"
+    );
+}
+
+#[test]
+fn dry_run_reports_changes_without_writing() {
+    use xml_disassembler::ChangeKind;
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path().canonicalize().expect("canonical temp dir");
+    let source = base.join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let before = snapshot_tree(&base);
+
+    let mut dry = DisassembleXmlFileHandler::new();
+    dry.disassemble(
+        source.to_str().unwrap(),
+        &DisassembleOptions::builder()
+            .post_purge(true)
+            .dry_run(true)
+            .build()
+            .unwrap(),
+    )
+    .expect("dry-run disassemble");
+    assert_eq!(snapshot_tree(&base), before, "dry run wrote nothing");
+    let planned = dry.take_planned_changes();
+    assert!(planned
+        .iter()
+        .any(|c| c.path == source && c.kind == ChangeKind::Delete));
+    let planned_creates: Vec<String> = planned
+        .iter()
+        .filter(|c| c.kind == ChangeKind::Create)
+        .map(|c| {
+            c.path
+                .strip_prefix(&base)
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+
+    let mut real = DisassembleXmlFileHandler::new();
+    real.disassemble(
+        source.to_str().unwrap(),
+        &DisassembleOptions::builder().build().unwrap(),
+    )
+    .expect("disassemble");
+    let written: Vec<String> = snapshot_tree(&base)
+        .into_keys()
+        .filter(|path| !before.contains_key(path))
+        .collect();
+    assert_eq!(planned_creates, written, "plan matches the real run");
+
+    dry.disassemble(
+        source.to_str().unwrap(),
+        &DisassembleOptions::builder().build().unwrap(),
+    )
+    .expect("dry-run disassemble again");
+    assert!(dry
+        .take_planned_changes()
+        .iter()
+        .all(|c| c.kind == ChangeKind::Unchanged));
+
+    // Edit one part; a dry-run reassembly reports the overwrite with a diff and leaves the
+    // original file alone.
+    let part = written
+        .iter()
+        .map(|p| base.join(p))
+        .find(|p| {
+            std::fs::read_to_string(p)
+                .map(|s| s.contains("<editable>true</editable>"))
+                .unwrap_or(false)
+        })
+        .expect("part with an editable flag");
+    let edited = std::fs::read_to_string(&part).unwrap().replacen(
+        "<editable>true</editable>",
+        "<editable>false</editable>",
+        1,
+    );
+    std::fs::write(&part, edited).unwrap();
+
+    let mut reassemble = ReassembleXmlFileHandler::new();
+    reassemble
+        .reassemble(
+            base.join("HR_Admin").to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .post_purge(true)
+                .dry_run(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("dry-run reassemble");
+    assert_eq!(
+        std::fs::read_to_string(&source).unwrap(),
+        std::fs::read_to_string(fixture).unwrap()
+    );
+    assert!(base.join("HR_Admin").exists(), "post-purge only planned");
+    let planned = reassemble.take_planned_changes();
+    let overwrite = planned
+        .iter()
+        .find(|c| c.kind == ChangeKind::Overwrite)
+        .expect("overwrite planned");
+    assert_eq!(overwrite.path, source);
+    let diff = overwrite.diff.as_deref().expect("diff");
+    assert!(diff.contains("-        <editable>true</editable>"));
+    assert!(diff.contains("+        <editable>false</editable>"));
+    // Post-purge plans the deletion of each generated file.
+    let deleted: Vec<_> = planned
+        .iter()
+        .filter(|c| c.kind == ChangeKind::Delete)
+        .collect();
+    assert!(deleted.iter().any(|c| c.path == part));
+    assert!(deleted
+        .iter()
+        .all(|c| c.path.starts_with(base.join("HR_Admin"))));
+}
+
+#[test]
+fn verify_round_trip_reports_lossless_and_lossy_files() {
+    use xml_disassembler::{verify_round_trip, CompareMode, VerifyOptions};
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    for mode in [CompareMode::Semantic, CompareMode::Bytes] {
+        let options = VerifyOptions {
+            mode,
+            ..VerifyOptions::default()
+        };
+        let report =
+            verify_round_trip(DisassembleXmlFileHandler::new(), fixture, &options).expect("verify");
+        assert!(report.is_lossless(), "{}", report);
+    }
+    assert!(
+        !Path::new("fixtures/general/HR_Admin").exists(),
+        "verify works in a temp dir"
+    );
+
+    // This file does not keep the order of its actionOverrides siblings through a round trip.
+    let report = verify_round_trip(
+        DisassembleXmlFileHandler::new(),
+        "fixtures/array-of-leaves/Dreamhouse.app-meta.xml",
+        &VerifyOptions::default(),
+    )
+    .expect("verify");
+    assert_eq!(report.differences.len(), 1, "{}", report);
+    assert_eq!(
+        report.differences[0].path,
+        "/CustomApplication/actionOverrides"
+    );
+    assert_eq!(
+        report.differences[0].kind,
+        xml_disassembler::verify::DifferenceKind::Reordered
+    );
+}
+
+#[test]
+fn verify_reports_interleaved_siblings_that_lose_their_order() {
+    use xml_disassembler::verify::{compare_semantic, DifferenceKind};
+    use xml_disassembler::{verify_round_trip, VerifyOptions};
+    let _ = env_logger::try_init();
+    let interleaved = "<Root>\n    <a>1</a>\n    <b>x</b>\n    <a>2</a>\n</Root>\n";
+    let grouped = "<Root>\n    <a>1</a>\n    <a>2</a>\n    <b>x</b>\n</Root>\n";
+    let differences =
+        compare_semantic(interleaved.as_bytes(), grouped.as_bytes(), "Root.xml").unwrap();
+    assert_eq!(differences.len(), 1, "{:?}", differences);
+    assert_eq!(differences[0].kind, DifferenceKind::Reordered);
+    assert_eq!(differences[0].path, "/Root");
+    assert!(
+        compare_semantic(interleaved.as_bytes(), interleaved.as_bytes(), "Root.xml")
+            .unwrap()
+            .is_empty()
+    );
+
+    // A round trip that groups the siblings by tag is reported.
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let source = temp_dir.path().join("Root.xml");
+    std::fs::write(
+        &source,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Root>\n    <a>\n        <name>1</name>\n    </a>\n    <b>\n        <name>x</name>\n    </b>\n    <a>\n        <name>2</name>\n    </a>\n</Root>\n",
+    )
+    .unwrap();
+    let report = verify_round_trip(
+        DisassembleXmlFileHandler::new(),
+        source.to_str().unwrap(),
+        &VerifyOptions::default(),
+    )
+    .expect("verify");
+    assert_eq!(report.differences.len(), 1, "{}", report);
+    assert_eq!(report.differences[0].kind, DifferenceKind::Reordered);
+    assert_eq!(report.differences[0].path, "/Root");
+}
+
+#[test]
+fn check_reports_missing_stale_and_extra_parts() {
+    use xml_disassembler::check_disassembly;
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path().canonicalize().expect("canonical temp dir");
+    std::fs::copy(fixture, base.join("HR_Admin.permissionset-meta.xml")).expect("copy");
+    let root = base.to_str().unwrap();
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble(root, &DisassembleOptions::builder().build().unwrap())
+        .expect("disassemble");
+    let report = check_disassembly(
+        DisassembleXmlFileHandler::new(),
+        root,
+        &DisassembleOptions::default(),
+    )
+    .expect("check");
+    assert!(report.up_to_date, "{}", report);
+    assert_eq!(report.files.len(), 1);
+
+    let parts_dir = base.join("HR_Admin");
+    let mut parts: Vec<_> = snapshot_tree(&parts_dir)
+        .into_keys()
+        .filter(|p| !p.starts_with('.') && p.contains('/'))
+        .collect();
+    parts.sort();
+    let (removed, edited) = (&parts[0], &parts[1]);
+    std::fs::remove_file(parts_dir.join(removed)).unwrap();
+    let content = std::fs::read_to_string(parts_dir.join(edited)).unwrap();
+    std::fs::write(parts_dir.join(edited), content.replace("true", "false")).unwrap();
+    std::fs::write(parts_dir.join("leftover.xml"), "<x/>").unwrap();
+    let before = snapshot_tree(&base);
+
+    let report = check_disassembly(
+        DisassembleXmlFileHandler::new(),
+        root,
+        &DisassembleOptions::default(),
+    )
+    .expect("check");
+    assert_eq!(snapshot_tree(&base), before, "check writes nothing");
+    assert!(!report.up_to_date);
+    let file = &report.files[0];
+    assert_eq!(file.directory, parts_dir);
+    assert_eq!(file.missing, vec![Path::new(removed).to_path_buf()]);
+    assert_eq!(file.stale, vec![Path::new(edited).to_path_buf()]);
+    assert_eq!(file.extra, vec![Path::new("leftover.xml").to_path_buf()]);
+}
+
+#[test]
+fn sync_removes_parts_of_deleted_elements_only() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let run = |sync: bool| {
+        let source = source.clone();
+        {
+            let mut handler = DisassembleXmlFileHandler::new();
+            handler
+                .disassemble(
+                    source.to_str().unwrap(),
+                    &DisassembleOptions::builder().sync(sync).build().unwrap(),
+                )
+                .expect("disassemble");
+            handler.take_planned_changes()
+        }
+    };
+    run(false);
+    let parts_dir = base.join("HR_Admin");
+    assert!(parts_dir.join(".manifest.json").exists());
+    let user_file = parts_dir.join("NOTES.md");
+    std::fs::write(&user_file, "kept").unwrap();
+
+    // Drop every <tabSettings> element from the source.
+    let original = std::fs::read_to_string(&source).unwrap();
+    let start = original.find("    <tabSettings>").expect("tabSettings");
+    let end = original.rfind("</tabSettings>\n").unwrap() + "</tabSettings>\n".len();
+    let edited = format!("{}{}", &original[..start], &original[end..]);
+    std::fs::write(&source, &edited).unwrap();
+
+    run(false);
+    assert!(
+        parts_dir.join("tabSettings").exists(),
+        "without sync the orphaned parts stay"
+    );
+
+    let changes = run(true);
+    assert!(changes
+        .iter()
+        .any(|c| c.kind == xml_disassembler::ChangeKind::Delete
+            && c.path.to_string_lossy().contains("tabSettings")));
+    assert!(
+        !parts_dir.join("tabSettings").exists(),
+        "orphans and their empty dir removed"
+    );
+    assert!(user_file.exists(), "files not in the manifest are kept");
+    std::fs::remove_file(&user_file).unwrap();
+
+    std::fs::remove_file(&source).unwrap();
+    ReassembleXmlFileHandler::new()
+        .reassemble(parts_dir.to_str().unwrap(), &ReassembleOptions::default())
+        .expect("reassemble");
+    assert_eq!(std::fs::read_to_string(&source).unwrap(), edited);
+}
+
+#[test]
+fn dry_run_plans_the_same_changes_as_a_pre_purging_run() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let run = |dry_run: bool| {
+        let mut handler = DisassembleXmlFileHandler::new();
+        handler
+            .disassemble(
+                source.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .unique_id_elements("apexClass,field")
+                    .pre_purge(true)
+                    .dry_run(dry_run)
+                    .build()
+                    .unwrap(),
+            )
+            .expect("disassemble");
+        handler.take_planned_changes()
+    };
+    run(false);
+    // One part goes away, one changes.
+    let original = std::fs::read_to_string(&source).unwrap();
+    let edited = original
+        .replacen(
+            "    <classAccesses>\n        <apexClass>Send_Email_Confirmation</apexClass>\n        <enabled>true</enabled>\n    </classAccesses>\n",
+            "",
+            1,
+        )
+        .replacen("<label>HR Administration</label>", "<label>HR</label>", 1);
+    assert_ne!(edited, original);
+    std::fs::write(&source, edited).unwrap();
+
+    let before = snapshot_tree(base);
+    let planned = run(true);
+    assert_eq!(snapshot_tree(base), before, "dry run wrote nothing");
+    let applied = run(false);
+    let counts = xml_disassembler::ChangeCounts::from_changes;
+    assert_eq!(counts(&planned), counts(&applied));
+    assert_eq!(planned, applied);
+    // Purged and written again: created; purged only: deleted.
+    let planned = counts(&planned);
+    assert!(planned.created > 0 && planned.deleted > 0);
+    assert_eq!(planned.unchanged, 0);
+}
+
+#[test]
+fn redisassembly_only_rewrites_changed_parts() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    let run = |hash_index: bool| {
+        let source = source.clone();
+        {
+            let mut handler = DisassembleXmlFileHandler::new();
+            handler
+                .disassemble(
+                    source.to_str().unwrap(),
+                    &DisassembleOptions::builder()
+                        .unique_id_elements("apexClass,field")
+                        .hash_index(hash_index)
+                        .build()
+                        .unwrap(),
+                )
+                .expect("disassemble");
+            xml_disassembler::ChangeCounts::from_changes(&handler.take_planned_changes())
+        }
+    };
+    let first = run(true);
+    assert!(first.created > 0);
+    assert_eq!((first.updated, first.unchanged), (0, 0));
+    let parts_dir = base.join("HR_Admin");
+    assert!(parts_dir.join(".hashes.json").exists());
+    let mtimes = |dir: &Path| {
+        let mut times = Vec::new();
+        for name in snapshot_tree(dir).into_keys() {
+            let modified = std::fs::metadata(dir.join(&name))
+                .unwrap()
+                .modified()
+                .unwrap();
+            times.push((name, modified));
+        }
+        times
+    };
+    let before = mtimes(&parts_dir);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+
+    let second = run(true);
+    assert_eq!((second.created, second.updated), (0, 0));
+    assert_eq!(second.unchanged, first.created);
+    assert_eq!(
+        mtimes(&parts_dir),
+        before,
+        "unchanged parts keep their mtime"
+    );
+
+    // Change one element: only its part is rewritten.
+    let original = std::fs::read_to_string(&source).unwrap();
+    let edited = original.replacen("<editable>true</editable>", "<editable>false</editable>", 1);
+    assert_ne!(edited, original);
+    std::fs::write(&source, &edited).unwrap();
+    let third = run(false);
+    // The changed part is rewritten; the hash index is removed when running without it.
+    assert_eq!((third.created, third.updated, third.deleted), (0, 1, 1));
+    assert!(!parts_dir.join(".hashes.json").exists());
+
+    std::fs::remove_file(&source).unwrap();
+    ReassembleXmlFileHandler::new()
+        .reassemble(parts_dir.to_str().unwrap(), &ReassembleOptions::default())
+        .expect("reassemble");
+    assert_eq!(std::fs::read_to_string(&source).unwrap(), edited);
+}
+
+#[test]
+fn failed_disassembly_rolls_back_every_file() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    for fixture in [
+        "fixtures/general/HR_Admin.permissionset-meta.xml",
+        "fixtures/array-of-leaves/Dreamhouse.app-meta.xml",
+    ] {
+        let name = Path::new(fixture).file_name().unwrap();
+        std::fs::copy(fixture, base.join(name)).expect("copy fixture");
+    }
+    // A file where a part directory has to go makes the HR_Admin disassembly fail.
+    let blocked = base.join("HR_Admin");
+    std::fs::create_dir(&blocked).unwrap();
+    std::fs::write(blocked.join("fieldPermissions"), "not a directory").unwrap();
+    let before = snapshot_tree(base);
+    let entries = |dir: &Path| {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    };
+    let before_entries = entries(base);
+
+    let mut handler = DisassembleXmlFileHandler::new();
+    let result = handler.disassemble(
+        base.to_str().unwrap(),
+        &DisassembleOptions::builder()
+            .post_purge(true)
+            .build()
+            .unwrap(),
+    );
+    assert!(result.is_err(), "the write error is reported");
+    assert_eq!(
+        snapshot_tree(base),
+        before,
+        "no part, staging copy or purge survives a failed run"
+    );
+    assert_eq!(entries(base), before_entries);
+}
+
+#[test]
+fn disassembly_fails_cleanly_when_a_limit_is_exceeded() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let cases = [
+        (
+            Limits {
+                max_depth: 2,
+                ..Limits::default()
+            },
+            "(max depth)",
+        ),
+        (
+            Limits {
+                max_document_size: 100,
+                ..Limits::default()
+            },
+            "(max document size)",
+        ),
+        (
+            Limits {
+                max_parts: 3,
+                ..Limits::default()
+            },
+            "(max parts)",
+        ),
+        (
+            Limits {
+                max_filename_length: 20,
+                ..Limits::default()
+            },
+            "(max filename length)",
+        ),
+    ];
+    for (limits, expected) in cases {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let source = temp_dir.path().join("HR_Admin.permissionset-meta.xml");
+        std::fs::copy(fixture, &source).expect("copy fixture");
+
+        let mut handler = DisassembleXmlFileHandler::new();
+        let err = handler
+            .disassemble(
+                source.to_str().unwrap(),
+                &DisassembleOptions::builder()
+                    .unique_id_elements("apexClass,field")
+                    .post_purge(true)
+                    .limits(limits)
+                    .build()
+                    .unwrap(),
+            )
+            .expect_err("the limit is enforced");
+        assert!(
+            err.to_string().contains(expected),
+            "{} mentions {}",
+            err,
+            expected
+        );
+        assert!(source.exists(), "the source is not purged");
+        assert!(
+            !temp_dir.path().join("HR_Admin").exists(),
+            "no part of a rejected file is kept"
+        );
+    }
+}
+
+#[test]
+fn deeply_nested_document_is_rejected_without_overflowing() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let source = temp_dir.path().join("Deep.xml");
+    let depth = 200_000;
+    let xml = format!(
+        "<Root><items>{}x{}</items></Root>",
+        "<a>".repeat(depth),
+        "</a>".repeat(depth)
+    );
+    std::fs::write(&source, xml).unwrap();
+
+    let mut handler = DisassembleXmlFileHandler::new();
+    let err = handler
+        .disassemble(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder().build().unwrap(),
+        )
+        .expect_err("default max depth");
+    assert!(err.to_string().contains("(max depth)"), "{}", err);
+    assert!(!temp_dir.path().join("Deep").exists());
+}
+
+#[test]
+fn reassemble_post_purge_deletes_only_generated_files() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    let source = base.join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy(fixture, &source).expect("copy fixture");
+    DisassembleXmlFileHandler::new()
+        .disassemble(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder()
+                .post_purge(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("disassemble");
+    let parts_dir = base.join("HR_Admin");
+    std::fs::write(parts_dir.join("NOTES.md"), "mine").unwrap();
+
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            parts_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .post_purge(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+    assert_eq!(
+        std::fs::read_to_string(&source).unwrap(),
+        std::fs::read_to_string(fixture).unwrap()
+    );
+    let left: Vec<_> = std::fs::read_dir(&parts_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(left, vec!["NOTES.md"], "only the user file is left");
+
+    // Without the manifest written at disassembly time the directory is not purged.
+    std::fs::write(parts_dir.join(".key_order.json"), "[\"label\"]").unwrap();
+    std::fs::write(
+        parts_dir.join("HR_Admin.permissionset-meta.xml"),
+        "<PermissionSet><label>x</label></PermissionSet>",
+    )
+    .unwrap();
+    ReassembleXmlFileHandler::new()
+        .reassemble(
+            parts_dir.to_str().unwrap(),
+            &ReassembleOptions::builder()
+                .post_purge(true)
+                .build()
+                .unwrap(),
+        )
+        .expect("reassemble");
+    assert!(parts_dir.join(".key_order.json").exists());
+    assert!(parts_dir.join("NOTES.md").exists());
+}
+
+#[test]
+fn in_memory_round_trip_matches_disk() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/general/HR_Admin.permissionset-meta.xml";
+    let xml = std::fs::read_to_string(fixture).unwrap();
+    let options = DisassembleOptions::default();
+
+    let parts = disassemble_str(&xml, &options).unwrap();
+    assert!(parts.contains_key(Path::new(".key_order.json")));
+    let rebuilt = reassemble_from_map(&parts, &ReassembleOptions::default()).unwrap();
+
+    // Same files and the same document as a round trip through the file system.
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    // The in-memory document is named `document.xml`.
+    let source = temp_dir.path().join("document.xml");
+    std::fs::write(&source, &xml).unwrap();
+    DisassembleXmlFileHandler::new()
+        .disassemble(source.to_str().unwrap(), &options)
+        .unwrap();
+    let dir = temp_dir.path().join("document");
+    let on_disk: std::collections::BTreeSet<_> = snapshot_tree(&dir)
+        .into_keys()
+        .map(std::path::PathBuf::from)
+        .collect();
+    assert_eq!(on_disk, parts.keys().cloned().collect());
+    std::fs::remove_file(&source).unwrap();
+    ReassembleXmlFileHandler::new()
+        .reassemble(dir.to_str().unwrap(), &ReassembleOptions::default())
+        .unwrap();
+    assert_eq!(rebuilt, std::fs::read_to_string(&source).unwrap());
+}
+
+#[test]
+fn in_memory_multi_level_round_trip_matches_original() {
+    let _ = env_logger::try_init();
+    let fixture = "fixtures/multi-level/Cloud_Kicks_Inner_Circle.loyaltyProgramSetup-meta.xml";
+    let xml = std::fs::read_to_string(fixture).unwrap();
+    let rule = MultiLevelRule {
+        file_pattern: "programProcesses".to_string(),
+        root_to_strip: "programProcesses".to_string(),
+        unique_id_elements: "parameterName,ruleName".to_string(),
+        path_segment: "programProcesses".to_string(),
+        wrap_root_element: "LoyaltyProgramSetup".to_string(),
+        wrap_xmlns: String::new(),
+    };
+    let options = DisassembleOptions::builder()
+        .unique_id_elements("fullName,name,processName")
+        .multi_level(rule)
+        .build()
+        .unwrap();
+
+    let parts = disassemble_str(&xml, &options).unwrap();
+    assert!(parts.contains_key(Path::new(".multi_level.json")));
+    let rebuilt = reassemble_from_map(&parts, &ReassembleOptions::default()).unwrap();
+    assert_eq!(rebuilt, xml);
+}
+
+#[test]
+fn handlers_walk_directories_in_memory_storage() {
+    let _ = env_logger::try_init();
+    let xml = std::fs::read_to_string("fixtures/general/HR_Admin.permissionset-meta.xml").unwrap();
+    let storage = std::sync::Arc::new(MemoryStorage::new());
+    storage
+        .insert("in-memory/a/HR_Admin.permissionset-meta.xml", xml.as_str())
+        .unwrap();
+    storage
+        .insert("in-memory/Other.permissionset-meta.xml", xml.as_str())
+        .unwrap();
+    storage
+        .insert("in-memory/.hidden/Skip.xml", xml.as_str())
+        .unwrap();
+    storage.insert("in-memory/notes.txt", "not xml").unwrap();
+
+    let mut disassemble = DisassembleXmlFileHandler::new().with_storage(storage.clone());
+    disassemble
+        .disassemble("in-memory", &DisassembleOptions::default())
+        .unwrap();
+    let report = disassemble.take_report();
+    assert_eq!(
+        report.processed,
+        vec![
+            Path::new("in-memory/Other.permissionset-meta.xml"),
+            Path::new("in-memory/a/HR_Admin.permissionset-meta.xml")
+        ]
+    );
+    assert!(storage.is_file(Path::new("in-memory/a/HR_Admin/.key_order.json")));
+    assert!(!storage.exists(Path::new("in-memory/.hidden/Skip")));
+    assert!(!Path::new("in-memory").exists());
+
+    // A second run skips the disassembled directories and changes nothing.
+    disassemble
+        .disassemble("in-memory", &DisassembleOptions::default())
+        .unwrap();
+    assert!(disassemble
+        .take_planned_changes()
+        .iter()
+        .all(|c| c.kind == xml_disassembler::ChangeKind::Unchanged));
+
+    let mut reassemble = ReassembleXmlFileHandler::new().with_storage(storage.clone());
+    storage
+        .remove_file(Path::new("in-memory/Other.permissionset-meta.xml"))
+        .unwrap();
+    reassemble
+        .reassemble(
+            "in-memory",
+            &ReassembleOptions::builder()
+                .recursive(true)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(
+        storage
+            .read_to_string(Path::new("in-memory/Other.permissionset-meta.xml"))
+            .unwrap(),
+        xml
+    );
+}
+
+#[test]
+fn type_suffixed_directories_reassemble_into_distinct_files() {
+    let _ = env_logger::try_init();
+    let xml = std::fs::read_to_string("fixtures/general/HR_Admin.permissionset-meta.xml").unwrap();
+    let storage = std::sync::Arc::new(MemoryStorage::new());
+    for name in ["Admin.permissionset-meta.xml", "Admin.profile-meta.xml"] {
+        storage
+            .insert(Path::new("types").join(name), xml.as_str())
+            .unwrap();
+    }
+    let mut disassemble = DisassembleXmlFileHandler::new().with_storage(storage.clone());
+    let options = DisassembleOptions::builder()
+        .post_purge(true)
+        .build()
+        .unwrap();
+    disassemble.disassemble("types", &options).unwrap();
+    assert!(storage.is_dir(Path::new("types/Admin.permissionset")));
+    assert!(storage.is_dir(Path::new("types/Admin.profile")));
+
+    let mut reassemble = ReassembleXmlFileHandler::new().with_storage(storage.clone());
+    let xml_extension = ReassembleOptions::builder()
+        .extension("xml")
+        .recursive(true)
+        .build()
+        .unwrap();
+    reassemble.reassemble("types", &xml_extension).unwrap();
+    for name in ["Admin.permissionset-meta.xml", "Admin.profile-meta.xml"] {
+        assert_eq!(
+            storage
+                .read_to_string(&Path::new("types").join(name))
+                .unwrap(),
+            xml
+        );
+    }
+
+    // A renamed copy recording the same source would overwrite it: the run fails first.
+    storage
+        .rename(
+            Path::new("types/Admin.profile"),
+            Path::new("types/Admin.copy"),
+        )
+        .unwrap();
+    storage
+        .insert(
+            "types/Admin.copy/.source.json",
+            r#"{"file_name":"Admin.permissionset-meta.xml"}"#,
+        )
+        .unwrap();
+    storage
+        .remove_file(Path::new("types/Admin.permissionset-meta.xml"))
+        .unwrap();
+    let err = reassemble.reassemble("types", &xml_extension).unwrap_err();
+    assert!(err.to_string().contains("would both be reassembled into"));
+    assert!(!storage.exists(Path::new("types/Admin.permissionset-meta.xml")));
+}
+
+/// Keeps every event it receives.
+#[derive(Debug, Default)]
+struct EventLog(std::sync::Mutex<Vec<Event>>);
+
+impl Observer for EventLog {
+    fn on_event(&self, event: &Event) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[test]
+fn observer_receives_the_events_of_a_run() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let source = temp_dir.path().join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy("fixtures/general/HR_Admin.permissionset-meta.xml", &source).unwrap();
+    std::fs::write(temp_dir.path().join("leaves.xml"), "<Root><a>1</a></Root>").unwrap();
+
+    let log = std::sync::Arc::new(EventLog::default());
+    let mut disassemble = DisassembleXmlFileHandler::new().with_observer(log.clone());
+    disassemble
+        .disassemble(
+            temp_dir.path().to_str().unwrap(),
+            &DisassembleOptions::default(),
+        )
+        .unwrap();
+    let events = std::mem::take(&mut *log.0.lock().unwrap());
+    let started = events
+        .iter()
+        .filter(|e| matches!(e, Event::FileStarted { .. }))
+        .count();
+    assert_eq!(started, 2);
+    let parts: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::PartWritten {
+                path,
+                tag,
+                id,
+                bytes,
+            } => Some((path.clone(), tag.clone(), id.clone(), *bytes)),
+            _ => None,
+        })
+        .collect();
+    let output = temp_dir.path().join("HR_Admin");
+    let written: Vec<_> = snapshot_tree(&output)
+        .into_keys()
+        .filter(|name| {
+            !Path::new(name)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with('.')
+        })
+        .collect();
+    assert_eq!(parts.len(), written.len());
+    for (path, _, _, bytes) in &parts {
+        assert_eq!(std::fs::metadata(path).unwrap().len() as usize, *bytes);
+    }
+    assert!(parts
+        .iter()
+        .any(|(_, tag, id, _)| tag == "fieldPermissions" && id.is_some()));
+    assert!(parts
+        .iter()
+        .any(|(_, tag, id, _)| tag == "PermissionSet" && id.is_none()));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        Event::FileDisassembled { output: o, .. } if o.ends_with("HR_Admin")
+    )));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        Event::Skipped { path, .. } if path.ends_with("leaves.xml")
+    )));
+
+    std::fs::remove_file(&source).unwrap();
+    let mut reassemble = ReassembleXmlFileHandler::new().with_observer(log.clone());
+    reassemble
+        .reassemble(output.to_str().unwrap(), &ReassembleOptions::default())
+        .unwrap();
+    let events = std::mem::take(&mut *log.0.lock().unwrap());
+    assert!(matches!(events.first(), Some(Event::FileStarted { path }) if path == &output));
+    assert!(matches!(
+        events.last(),
+        Some(Event::FileReassembled { output: o, .. }) if o == &source
+    ));
+}
+
+#[test]
+fn transforms_change_elements_on_both_sides() {
+    let _ = env_logger::try_init();
+    let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Root>\n    <item>\n        <name>a</name>\n        <flag>True</flag>\n        <stamp>1</stamp>\n    </item>\n    <item>\n        <name>b</name>\n        <flag>false</flag>\n        <stamp>2</stamp>\n    </item>\n    <label>dev</label>\n</Root>\n";
+    let rule = |selector: &str, action| {
+        std::sync::Arc::new(TransformRule {
+            selector: selector.to_string(),
+            action,
+        })
+    };
+    let options = DisassembleOptions::builder()
+        .unique_id_elements("name")
+        .transform(rule("item/stamp", RuleAction::Drop))
+        .transform(rule(
+            "flag",
+            RuleAction::Replace {
+                from: "True".to_string(),
+                to: "true".to_string(),
+            },
+        ))
+        .build()
+        .unwrap();
+    let parts = disassemble_str(xml, &options).unwrap();
+    let item_a = parts
+        .iter()
+        .find(|(path, _)| path.ends_with("a.item-meta.xml"))
+        .map(|(_, content)| content)
+        .expect("part of item a");
+    assert!(item_a.contains("<flag>true</flag>"));
+    assert!(parts.values().all(|content| !content.contains("stamp")));
+
+    let options = ReassembleOptions::builder()
+        .transform(rule("/Root/label", RuleAction::Rename("title".to_string())))
+        .build()
+        .unwrap();
+    let rebuilt = reassemble_from_map(&parts, &options).unwrap();
+    assert!(rebuilt.contains("<title>dev</title>"));
+    assert!(!rebuilt.contains("<label>"));
+    assert!(!rebuilt.contains("<stamp>"));
+}
+
+/// Cancels its token once the run wrote `after` parts.
+#[derive(Debug)]
+struct CancelAfterParts {
+    token: CancellationToken,
+    after: usize,
+    parts: std::sync::atomic::AtomicUsize,
+}
+
+impl Observer for CancelAfterParts {
+    fn on_event(&self, event: &Event) {
+        if matches!(event, Event::PartWritten { .. })
+            && self.parts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1 >= self.after
+        {
+            self.token.cancel();
+        }
+    }
+}
+
+#[test]
+fn cancelled_runs_stop_and_roll_back() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let base = temp_dir.path();
+    for fixture in [
+        "fixtures/general/HR_Admin.permissionset-meta.xml",
+        "fixtures/cdata/VidLand_US.marketingappextension-meta.xml",
+    ] {
+        std::fs::copy(fixture, base.join(Path::new(fixture).file_name().unwrap())).unwrap();
+    }
+    let before = snapshot_tree(base);
+
+    for jobs in [1, 4] {
+        let token = CancellationToken::new();
+        let observer = std::sync::Arc::new(CancelAfterParts {
+            token: token.clone(),
+            after: 2,
+            parts: Default::default(),
+        });
+        let mut handler = DisassembleXmlFileHandler::new()
+            .with_observer(observer)
+            .with_cancellation(token);
+        let options = DisassembleOptions::builder()
+            .post_purge(true)
+            .jobs(jobs)
+            .build()
+            .unwrap();
+        let err = handler
+            .disassemble(base.to_str().unwrap(), &options)
+            .unwrap_err();
+        assert!(matches!(err, xml_disassembler::Error::Cancelled), "{}", err);
+        assert_eq!(snapshot_tree(base), before);
+    }
+
+    let mut handler = DisassembleXmlFileHandler::new();
+    handler
+        .disassemble(base.to_str().unwrap(), &DisassembleOptions::default())
+        .unwrap();
+    let disassembled = snapshot_tree(base);
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut handler = ReassembleXmlFileHandler::new().with_cancellation(token);
+    let options = ReassembleOptions::builder()
+        .post_purge(true)
+        .recursive(true)
+        .build()
+        .unwrap();
+    let err = handler
+        .reassemble(base.to_str().unwrap(), &options)
+        .unwrap_err();
+    assert!(matches!(err, xml_disassembler::Error::Cancelled));
+    assert_eq!(snapshot_tree(base), disassembled);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_wrappers_run_the_pipeline_and_return_the_handler() {
+    let _ = env_logger::try_init();
+    let temp_dir = tempfile::tempdir().expect("temp dir");
+    let source = temp_dir.path().join("HR_Admin.permissionset-meta.xml");
+    std::fs::copy("fixtures/general/HR_Admin.permissionset-meta.xml", &source).unwrap();
+    let original = std::fs::read_to_string(&source).unwrap();
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble_async(
+            source.to_str().unwrap(),
+            &DisassembleOptions::builder().dry_run(true).build().unwrap(),
+        )
+        .await
+        .unwrap();
+    // The handler keeps its records.
+    assert!(!disassemble.take_planned_changes().is_empty());
+    assert!(!temp_dir.path().join("HR_Admin").exists());
+
+    let mut disassemble = DisassembleXmlFileHandler::new();
+    disassemble
+        .disassemble_async(source.to_str().unwrap(), &DisassembleOptions::default())
+        .await
+        .unwrap();
+    std::fs::remove_file(&source).unwrap();
+    let mut reassemble = ReassembleXmlFileHandler::new();
+    reassemble
+        .reassemble_async(
+            temp_dir.path().join("HR_Admin").to_str().unwrap(),
+            &ReassembleOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(reassemble.take_report().processed.len(), 1);
+    assert_eq!(std::fs::read_to_string(&source).unwrap(), original);
 }